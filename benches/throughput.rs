@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use chip_8::{
+    display::Display,
+    emulator::{Backend, Emulator},
+    keyboard::Keyboard,
+};
+
+const TICKS: usize = 10_000_000;
+
+/// A tight loop of arithmetic, jumps and draws standing in for a game
+#[rustfmt::skip]
+const DRAWS: [u8; 18] = [
+    0x60, 0x01, // LD V0, 0x01
+    0x71, 0x01, // ADD V1, 0x01
+    0x80, 0x14, // ADD V0, V1
+    0x82, 0x06, // SHR V2
+    0xA3, 0x00, // LD I, 0x300
+    0xD1, 0x25, // DRW V1, V2, 5
+    0x31, 0x00, // SE V1, 0x00
+    0x12, 0x02, // JP 0x202
+    0x12, 0x00, // JP 0x200
+];
+
+/// A long straight run of arithmetic such as a game's physics, where the
+/// recompiler never has to leave a block until the jump back
+#[rustfmt::skip]
+const ARITHMETIC: [u8; 32] = [
+    0x71, 0x01, // ADD V1, 0x01
+    0x80, 0x14, // ADD V0, V1
+    0x82, 0x06, // SHR V2
+    0x83, 0x03, // XOR V3, V0
+    0x84, 0x11, // OR V4, V1
+    0x85, 0x02, // AND V5, V0
+    0x86, 0x15, // SUB V6, V1
+    0x87, 0x0E, // SHL V7
+    0x88, 0x30, // LD V8, V3
+    0x79, 0x03, // ADD V9, 0x03
+    0x8A, 0x94, // ADD VA, V9
+    0x8B, 0xA7, // SUBN VB, VA
+    0x8C, 0xB3, // XOR VC, VB
+    0xA3, 0x00, // LD I, 0x300
+    0xF1, 0x1E, // ADD I, V1
+    0x12, 0x00, // JP 0x200
+];
+
+/// Runs the rom uncapped and returns the number of ticks per second
+fn measure(rom: &[u8], backend: Backend, decode_cache: bool) -> f64 {
+    let mut emulator = Emulator::new(rom).unwrap();
+    let mut display = Display::default();
+    let keyboard = Keyboard::default();
+
+    emulator.set_backend(backend);
+
+    if decode_cache {
+        emulator.enable_decode_cache();
+    }
+
+    let start = Instant::now();
+
+    emulator.run(&mut display, &keyboard, TICKS).unwrap();
+
+    TICKS as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Prints the throughput of every backend on each rom, along with how many
+/// times faster than the plain interpreter it is
+fn main() {
+    let roms: [(&str, &[u8]); 2] = [("draws", &DRAWS), ("arithmetic", &ARITHMETIC)];
+    let cases = [
+        ("interpreter", Backend::Interpreter, false),
+        ("decode cache", Backend::Interpreter, true),
+        ("recompiler", Backend::Recompiler, false),
+    ];
+
+    for &(rom_name, rom) in &roms {
+        let results: Vec<f64> = cases
+            .iter()
+            .map(|&(_, backend, decode_cache)| measure(rom, backend, decode_cache))
+            .collect();
+
+        for (&(name, _, _), &throughput) in cases.iter().zip(&results) {
+            println!(
+                "{:<12}{:<16}{:>12.0} ticks/s {:>6.2}x",
+                rom_name,
+                name,
+                throughput,
+                throughput / results[0]
+            );
+        }
+    }
+}
@@ -0,0 +1,176 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated with cbindgen from capi/src/lib.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The version of this interface, which changes whenever an existing
+ * function or type does
+ */
+#define CHIP8_ABI_VERSION 1
+
+/**
+ * The width of the screen in pixels
+ */
+#define CHIP8_WIDTH 64
+
+/**
+ * The height of the screen in pixels, which is also the number of rows in
+ * the framebuffer
+ */
+#define CHIP8_HEIGHT 32
+
+/**
+ * The outcome of a call, where anything but `CHIP8_STATUS_OK` leaves a
+ * message for `chip8_last_error`
+ */
+typedef enum Chip8Status {
+  /**
+   * The call succeeded
+   */
+  CHIP8_STATUS_OK = 0,
+  /**
+   * The program stopped on an instruction that could not be run
+   */
+  CHIP8_STATUS_FAULT = 1,
+  /**
+   * A pointer was null or a value was out of range
+   */
+  CHIP8_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The buffer given was too small, and the size needed was written
+   */
+  CHIP8_STATUS_BUFFER_TOO_SMALL = 3,
+} Chip8Status;
+
+/**
+ * An emulator along with the screen it draws to and the keys it reads
+ */
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the version of the interface the library was built with
+ */
+uint32_t chip8_abi_version(void);
+
+/**
+ * Creates an emulator with the rom loaded at 0x200, or returns null when
+ * the rom does not fit in memory
+ *
+ * The rom is copied, so the caller may free it once this returns.
+ */
+struct Chip8 *chip8_new(const uint8_t *rom, size_t length);
+
+/**
+ * Releases an emulator, after which the handle must not be used again
+ */
+void chip8_free(struct Chip8 *chip8);
+
+/**
+ * Seeds the random number generator so that runs can be repeated
+ */
+enum Chip8Status chip8_set_seed(struct Chip8 *chip8, uint64_t seed);
+
+/**
+ * Uses the quirks of a platform named as on the command line, such as
+ * `"originalChip8"` or `"superchip"`
+ */
+enum Chip8Status chip8_set_platform(struct Chip8 *chip8, const char *name);
+
+/**
+ * Chooses how instructions are run, either `"interpreter"` or
+ * `"recompiler"`
+ */
+enum Chip8Status chip8_set_backend(struct Chip8 *chip8, const char *name);
+
+/**
+ * Runs the given number of instructions, stopping early on a fault
+ *
+ * After a fault the message is available from `chip8_last_error` and the
+ * program counter stays on the instruction that failed.
+ */
+enum Chip8Status chip8_tick(struct Chip8 *chip8, size_t ticks);
+
+/**
+ * Counts the delay and sound timers down once, which should happen sixty
+ * times a second
+ */
+void chip8_decrement_timers(struct Chip8 *chip8);
+
+/**
+ * Presses or releases one of the keys 0x0 to 0xF
+ */
+enum Chip8Status chip8_set_key(struct Chip8 *chip8, uint8_t key, bool pressed);
+
+/**
+ * Sets every key at once, with bit n held when key n is pressed
+ */
+void chip8_set_keys(struct Chip8 *chip8, uint16_t state);
+
+/**
+ * Returns the `CHIP8_HEIGHT` rows of the screen, or null for a null handle
+ *
+ * Each row is a 64 bit integer with the leftmost pixel in its most
+ * significant bit. The pointer stays valid until the emulator is freed and
+ * its contents change as instructions run.
+ */
+const uint64_t *chip8_framebuffer(const struct Chip8 *chip8);
+
+/**
+ * Returns whether the screen has changed since `chip8_mark_clean` was last
+ * called, so that hosts can skip redrawing
+ */
+bool chip8_is_dirty(const struct Chip8 *chip8);
+
+/**
+ * Records that the host has drawn the current screen
+ */
+void chip8_mark_clean(struct Chip8 *chip8);
+
+/**
+ * Returns whether the buzzer should be sounding
+ */
+bool chip8_is_sound_playing(const struct Chip8 *chip8);
+
+/**
+ * Saves the program and its screen into a buffer
+ *
+ * The size of the state is always written to `length`. When `buffer` is
+ * null or smaller than that, nothing else is written and
+ * `CHIP8_STATUS_BUFFER_TOO_SMALL` is returned, so the call can be made once
+ * to find the size and again to fill the buffer. The bytes are only
+ * meaningful to `chip8_load_state`.
+ */
+enum Chip8Status chip8_save_state(struct Chip8 *chip8,
+                                  uint8_t *buffer,
+                                  size_t capacity,
+                                  size_t *length);
+
+/**
+ * Returns the program and its screen to a state from `chip8_save_state`
+ */
+enum Chip8Status chip8_load_state(struct Chip8 *chip8, const uint8_t *buffer, size_t length);
+
+/**
+ * Returns the message for the last call that failed, or null if none has
+ *
+ * The string belongs to the emulator and stays valid until the next call
+ * that fails or until the emulator is freed.
+ */
+const char *chip8_last_error(const struct Chip8 *chip8);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CHIP8_H */
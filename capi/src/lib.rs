@@ -0,0 +1,482 @@
+//! A C interface for embedding the emulator in other programs
+//!
+//! Each emulator is an opaque `Chip8` handle created from the bytes of a rom
+//! and released with `chip8_free`. The host owns the clock: it calls
+//! `chip8_tick` for the instructions of a frame and `chip8_decrement_timers`
+//! sixty times a second, then reads the framebuffer and sound flag. The
+//! header in `include/chip8.h` declares everything exported here.
+//!
+//! Every handle passed in must come from `chip8_new` and not yet be freed,
+//! and every buffer must be valid for the length given with it. Null handles
+//! are ignored or reported as `CHIP8_STATUS_INVALID_ARGUMENT`.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    ptr, slice,
+};
+
+use chip_8::{
+    display::{Display, HEIGHT, WIDTH},
+    emulator::{Backend, Emulator, SaveState, DEFAULT_STACK_LIMIT, VIP_STACK_LIMIT},
+    keyboard::{Key, Keyboard},
+    quirks::Platform,
+};
+
+/// The version of this interface, which changes whenever an existing
+/// function or type does
+pub const CHIP8_ABI_VERSION: u32 = 1;
+
+/// The width of the screen in pixels
+pub const CHIP8_WIDTH: usize = 64;
+
+/// The height of the screen in pixels, which is also the number of rows in
+/// the framebuffer
+pub const CHIP8_HEIGHT: usize = 32;
+
+const _: () = assert!(CHIP8_WIDTH == WIDTH && CHIP8_HEIGHT == HEIGHT);
+
+/// The outcome of a call, where anything but `CHIP8_STATUS_OK` leaves a
+/// message for `chip8_last_error`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Status {
+    /// The call succeeded
+    Ok = 0,
+    /// The program stopped on an instruction that could not be run
+    Fault = 1,
+    /// A pointer was null or a value was out of range
+    InvalidArgument = 2,
+    /// The buffer given was too small, and the size needed was written
+    BufferTooSmall = 3,
+}
+
+/// An emulator along with the screen it draws to and the keys it reads
+pub struct Chip8 {
+    emulator: Emulator,
+    display: Display,
+    keyboard: Keyboard,
+    error: Option<CString>,
+}
+
+impl Chip8 {
+    /// Remembers the message for a failed call and returns its status
+    fn fail(&mut self, status: Chip8Status, message: impl Into<Vec<u8>>) -> Chip8Status {
+        // Messages come from this crate so never hold a nul byte
+        self.error = CString::new(message).ok();
+        status
+    }
+}
+
+/// Returns the version of the interface the library was built with
+#[no_mangle]
+pub extern "C" fn chip8_abi_version() -> u32 {
+    CHIP8_ABI_VERSION
+}
+
+/// Creates an emulator with the rom loaded at 0x200, or returns null when
+/// the rom does not fit in memory
+///
+/// The rom is copied, so the caller may free it once this returns.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_new(rom: *const u8, length: usize) -> *mut Chip8 {
+    let rom = match bytes(rom, length) {
+        Some(rom) => rom,
+        None => return ptr::null_mut(),
+    };
+
+    match Emulator::new(rom) {
+        Ok(emulator) => Box::into_raw(Box::new(Chip8 {
+            emulator,
+            display: Display::default(),
+            keyboard: Keyboard::default(),
+            error: None,
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases an emulator, after which the handle must not be used again
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Seeds the random number generator so that runs can be repeated
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_seed(chip8: *mut Chip8, seed: u64) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    chip8.emulator.set_seed(seed);
+    Chip8Status::Ok
+}
+
+/// Uses the quirks of a platform named as on the command line, such as
+/// `"originalChip8"` or `"superchip"`
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_platform(chip8: *mut Chip8, name: *const c_char) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    if name.is_null() {
+        return chip8.fail(Chip8Status::InvalidArgument, "The platform name is null");
+    }
+
+    let platform = match CStr::from_ptr(name).to_string_lossy().parse::<Platform>() {
+        Ok(platform) => platform,
+        Err(message) => return chip8.fail(Chip8Status::InvalidArgument, message),
+    };
+
+    // Keep the default limit elsewhere, as the frontends do, so that runaway
+    // recursion is still reported as a stack overflow
+    chip8.emulator.set_quirks(platform.quirks());
+    chip8.emulator.set_stack_limit(Some(match platform {
+        Platform::OriginalChip8 | Platform::HybridVip => VIP_STACK_LIMIT,
+        _ => DEFAULT_STACK_LIMIT,
+    }));
+
+    Chip8Status::Ok
+}
+
+/// Chooses how instructions are run, either `"interpreter"` or
+/// `"recompiler"`
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_backend(chip8: *mut Chip8, name: *const c_char) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    if name.is_null() {
+        return chip8.fail(Chip8Status::InvalidArgument, "The backend name is null");
+    }
+
+    match CStr::from_ptr(name).to_string_lossy().parse::<Backend>() {
+        Ok(backend) => chip8.emulator.set_backend(backend),
+        Err(message) => return chip8.fail(Chip8Status::InvalidArgument, message),
+    }
+
+    Chip8Status::Ok
+}
+
+/// Runs the given number of instructions, stopping early on a fault
+///
+/// After a fault the message is available from `chip8_last_error` and the
+/// program counter stays on the instruction that failed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick(chip8: *mut Chip8, ticks: usize) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    match chip8
+        .emulator
+        .run(&mut chip8.display, &chip8.keyboard, ticks)
+    {
+        Ok(()) => Chip8Status::Ok,
+        Err(fault) => chip8.fail(Chip8Status::Fault, fault.to_string()),
+    }
+}
+
+/// Counts the delay and sound timers down once, which should happen sixty
+/// times a second
+#[no_mangle]
+pub unsafe extern "C" fn chip8_decrement_timers(chip8: *mut Chip8) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.emulator.decrement_timers();
+    }
+}
+
+/// Presses or releases one of the keys 0x0 to 0xF
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    match Key::try_from(key) {
+        Ok(key) if pressed => chip8.keyboard.press(&key),
+        Ok(key) => chip8.keyboard.release(&key),
+        Err(message) => return chip8.fail(Chip8Status::InvalidArgument, message),
+    }
+
+    Chip8Status::Ok
+}
+
+/// Sets every key at once, with bit n held when key n is pressed
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(chip8: *mut Chip8, state: u16) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.keyboard.set_state(state);
+    }
+}
+
+/// Returns the `CHIP8_HEIGHT` rows of the screen, or null for a null handle
+///
+/// Each row is a 64 bit integer with the leftmost pixel in its most
+/// significant bit. The pointer stays valid until the emulator is freed and
+/// its contents change as instructions run.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u64 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.display.rows().as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns whether the screen has changed since `chip8_mark_clean` was last
+/// called, so that hosts can skip redrawing
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_dirty(chip8: *const Chip8) -> bool {
+    chip8.as_ref().is_some_and(|chip8| chip8.display.is_dirty())
+}
+
+/// Records that the host has drawn the current screen
+#[no_mangle]
+pub unsafe extern "C" fn chip8_mark_clean(chip8: *mut Chip8) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.display.mark_clean();
+    }
+}
+
+/// Returns whether the buzzer should be sounding
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_sound_playing(chip8: *const Chip8) -> bool {
+    chip8
+        .as_ref()
+        .is_some_and(|chip8| chip8.emulator.is_sound_playing())
+}
+
+/// Saves the program and its screen into a buffer
+///
+/// The size of the state is always written to `length`. When `buffer` is
+/// null or smaller than that, nothing else is written and
+/// `CHIP8_STATUS_BUFFER_TOO_SMALL` is returned, so the call can be made once
+/// to find the size and again to fill the buffer. The bytes are only
+/// meaningful to `chip8_load_state`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    chip8: *mut Chip8,
+    buffer: *mut u8,
+    capacity: usize,
+    length: *mut usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    if length.is_null() {
+        return chip8.fail(Chip8Status::InvalidArgument, "The length pointer is null");
+    }
+
+    let state = chip8.emulator.save_state(&chip8.display);
+    let bytes = match serde_json::to_vec(&state) {
+        Ok(bytes) => bytes,
+        Err(e) => return chip8.fail(Chip8Status::InvalidArgument, e.to_string()),
+    };
+
+    *length = bytes.len();
+
+    if buffer.is_null() || capacity < bytes.len() {
+        return chip8.fail(
+            Chip8Status::BufferTooSmall,
+            format!("The state needs {} bytes", bytes.len()),
+        );
+    }
+
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len());
+    Chip8Status::Ok
+}
+
+/// Returns the program and its screen to a state from `chip8_save_state`
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Chip8,
+    buffer: *const u8,
+    length: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::InvalidArgument,
+    };
+
+    let bytes = match bytes(buffer, length) {
+        Some(bytes) => bytes,
+        None => return chip8.fail(Chip8Status::InvalidArgument, "The state buffer is null"),
+    };
+
+    let result = serde_json::from_slice::<SaveState>(bytes)
+        .map_err(|e| format!("Invalid state: {}", e))
+        .and_then(|state| {
+            chip8
+                .emulator
+                .load_state(&state, &mut chip8.display)
+                .map_err(|e| e.to_string())
+        });
+
+    match result {
+        Ok(()) => Chip8Status::Ok,
+        Err(message) => chip8.fail(Chip8Status::InvalidArgument, message),
+    }
+}
+
+/// Returns the message for the last call that failed, or null if none has
+///
+/// The string belongs to the emulator and stays valid until the next call
+/// that fails or until the emulator is freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(chip8: *const Chip8) -> *const c_char {
+    chip8
+        .as_ref()
+        .and_then(|chip8| chip8.error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Borrows a buffer from the caller, where an empty one may be null
+unsafe fn bytes<'a>(buffer: *const u8, length: usize) -> Option<&'a [u8]> {
+    if length == 0 {
+        Some(&[])
+    } else if buffer.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(buffer, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waits for a key and then keeps drawing and erasing its digit
+    const ROM: [u8; 10] = [0x60, 0x07, 0xF0, 0x0A, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x06];
+
+    #[test]
+    fn test_run_and_draw() {
+        unsafe {
+            let chip8 = chip8_new(ROM.as_ptr(), ROM.len());
+            assert!(!chip8.is_null());
+
+            assert_eq!(chip8_tick(chip8, 2), Chip8Status::Ok);
+            assert_eq!(chip8_set_key(chip8, 5, true), Chip8Status::Ok);
+            assert_eq!(chip8_tick(chip8, 3), Chip8Status::Ok);
+            assert!(chip8_is_dirty(chip8));
+
+            let rows = slice::from_raw_parts(chip8_framebuffer(chip8), CHIP8_HEIGHT);
+            assert_eq!(rows[0], 0x0780_0000_0000_0000);
+            assert_eq!(rows[1], 0x0400_0000_0000_0000);
+
+            chip8_mark_clean(chip8);
+            assert!(!chip8_is_dirty(chip8));
+            assert!(chip8_last_error(chip8).is_null());
+
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            assert!(chip8_new(ptr::null(), 1).is_null());
+            assert!(chip8_new([0; 4096].as_ptr(), 4096).is_null());
+            assert_eq!(chip8_tick(ptr::null_mut(), 1), Chip8Status::InvalidArgument);
+
+            let chip8 = chip8_new(ptr::null(), 0);
+
+            assert_eq!(chip8_set_key(chip8, 16, true), Chip8Status::InvalidArgument);
+            assert_eq!(
+                CStr::from_ptr(chip8_last_error(chip8)).to_str(),
+                Ok("Unsupported key 16")
+            );
+
+            assert_eq!(
+                chip8_set_platform(chip8, b"superchip\0".as_ptr().cast()),
+                Chip8Status::Ok
+            );
+            assert_eq!(
+                chip8_set_platform(chip8, b"nes\0".as_ptr().cast()),
+                Chip8Status::InvalidArgument
+            );
+
+            chip8_free(chip8);
+
+            // Calls itself until the stack is full
+            let rom = [0x22, 0x00];
+            let chip8 = chip8_new(rom.as_ptr(), rom.len());
+            chip8_set_platform(chip8, b"superchip\0".as_ptr().cast());
+
+            assert_eq!(chip8_tick(chip8, 100), Chip8Status::Fault);
+            assert_eq!(
+                CStr::from_ptr(chip8_last_error(chip8)).to_str(),
+                Ok("Stack overflow")
+            );
+
+            chip8_free(chip8);
+            let chip8 = chip8_new(ptr::null(), 0);
+
+            // Memory is empty so the first instruction is 0x0000
+            assert_eq!(chip8_tick(chip8, 1), Chip8Status::Fault);
+            assert!(!chip8_last_error(chip8).is_null());
+
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        unsafe {
+            let chip8 = chip8_new(ROM.as_ptr(), ROM.len());
+            assert_eq!(
+                chip8_set_backend(chip8, b"recompiler\0".as_ptr().cast()),
+                Chip8Status::Ok
+            );
+            chip8_set_keys(chip8, 1 << 5);
+            chip8_tick(chip8, 4);
+
+            let mut length = 0;
+            assert_eq!(
+                chip8_save_state(chip8, ptr::null_mut(), 0, &mut length),
+                Chip8Status::BufferTooSmall
+            );
+
+            let mut state = vec![0; length];
+            assert_eq!(
+                chip8_save_state(chip8, state.as_mut_ptr(), state.len(), &mut length),
+                Chip8Status::Ok
+            );
+
+            let saved = slice::from_raw_parts(chip8_framebuffer(chip8), CHIP8_HEIGHT).to_vec();
+            chip8_tick(chip8, 3);
+            assert_ne!(
+                slice::from_raw_parts(chip8_framebuffer(chip8), CHIP8_HEIGHT),
+                &saved[..]
+            );
+
+            assert_eq!(
+                chip8_load_state(chip8, state.as_ptr(), state.len()),
+                Chip8Status::Ok
+            );
+            assert_eq!(
+                slice::from_raw_parts(chip8_framebuffer(chip8), CHIP8_HEIGHT),
+                &saved[..]
+            );
+            assert_eq!(
+                chip8_load_state(chip8, state.as_ptr(), 3),
+                Chip8Status::InvalidArgument
+            );
+
+            chip8_free(chip8);
+        }
+    }
+}
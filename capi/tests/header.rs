@@ -0,0 +1,47 @@
+//! Checks that the checked in header matches the functions the library
+//! exports, since it is regenerated by hand with cbindgen
+
+use std::fs;
+
+/// Returns the names of the functions exported from the library source
+fn exported_functions() -> Vec<String> {
+    let source = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs")).unwrap();
+
+    source
+        .lines()
+        .filter(|line| line.starts_with("pub ") && line.contains("extern \"C\" fn "))
+        .map(|line| {
+            let name = line.split("fn ").nth(1).unwrap();
+            name[..name.find('(').unwrap()].to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_header_declares_every_function() {
+    let header =
+        fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/include/chip8.h")).unwrap();
+    let functions = exported_functions();
+
+    assert_eq!(functions.len(), 17);
+
+    for function in &functions {
+        assert!(
+            header.contains(&format!(" *{}(", function))
+                || header.contains(&format!(" {}(", function)),
+            "{} is missing from include/chip8.h",
+            function
+        );
+    }
+
+    let declared = header
+        .lines()
+        .filter(|line| line.contains(" chip8_") || line.contains("*chip8_"))
+        .filter(|line| line.contains('(') && !line.trim_start().starts_with('*'))
+        .count();
+    assert_eq!(
+        declared,
+        functions.len(),
+        "include/chip8.h declares extra functions"
+    );
+}
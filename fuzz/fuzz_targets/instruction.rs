@@ -0,0 +1,13 @@
+#![no_main]
+
+use std::convert::TryFrom;
+
+use chip_8::instruction::Instruction;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|opcode: u16| {
+    if let Ok(instruction) = Instruction::try_from(opcode) {
+        assert_eq!(u16::from(instruction), opcode);
+        let _ = instruction.to_string();
+    }
+});
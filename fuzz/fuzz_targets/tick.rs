@@ -0,0 +1,51 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip_8::{
+    display::Display,
+    emulator::{Backend, Emulator},
+    keyboard::Keyboard,
+    quirks::Platform,
+};
+use libfuzzer_sys::fuzz_target;
+
+const TICKS: usize = 1000;
+const TICKS_PER_FRAME: usize = 8;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    platform: u8,
+    recompiler: bool,
+    keys: u16,
+    seed: u64,
+    rom: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut emulator = match Emulator::new(&input.rom) {
+        Ok(emulator) => emulator,
+        Err(_) => return,
+    };
+    let platform = Platform::ALL[input.platform as usize % Platform::ALL.len()];
+    let mut display = Display::default();
+    let mut keyboard = Keyboard::default();
+
+    emulator.set_quirks(platform.quirks());
+    emulator.set_seed(input.seed);
+    keyboard.set_state(input.keys);
+
+    if input.recompiler {
+        emulator.set_backend(Backend::Recompiler);
+    }
+
+    for _ in 0..TICKS / TICKS_PER_FRAME {
+        emulator.decrement_timers();
+
+        if emulator
+            .run(&mut display, &keyboard, TICKS_PER_FRAME)
+            .is_err()
+        {
+            break;
+        }
+    }
+});
@@ -0,0 +1,28 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip_8::display::Display;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    x: usize,
+    y: usize,
+    clipped: bool,
+    sprite: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut display = Display::default();
+
+    // Drawing the same sprite twice always leaves the screen blank again
+    for _ in 0..2 {
+        if input.clipped {
+            display.xor_sprite_clipped(input.x, input.y, &input.sprite);
+        } else {
+            display.xor_sprite(input.x, input.y, &input.sprite);
+        }
+    }
+
+    assert!(display.rows().iter().all(|&row| row == 0));
+});
@@ -0,0 +1,511 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt::Write,
+    ops::Range,
+};
+
+use crate::{
+    disassembler::{disassemble_annotated, Annotation},
+    instruction::Instruction,
+};
+
+/// How control passes from one block to another
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edge {
+    /// Continues to the following instruction
+    Next,
+    /// Jumps to an address with `JP`
+    Jump,
+    /// Calls a subroutine which is assumed to return
+    Call,
+    /// Skips over the following instruction
+    Skip,
+}
+
+/// A straight run of instructions which is only entered at its start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<(usize, Edge)>,
+}
+
+/// The control flow of a rom found without running it
+///
+/// Instructions are followed from the load address through jumps, calls and
+/// both sides of every skip. Subroutines are assumed to return to the
+/// instruction after their call. The targets of `JP V0, nnn` depend on V0 so
+/// they are flagged instead of followed, and the code they reach may be
+/// reported as unreachable.
+pub struct Analysis {
+    load_address: usize,
+    length: usize,
+    code: BTreeMap<usize, Instruction>,
+    blocks: BTreeMap<usize, Block>,
+    subroutines: BTreeSet<usize>,
+    jump_targets: BTreeSet<usize>,
+    computed_jumps: BTreeSet<usize>,
+    sprites: Vec<Range<usize>>,
+}
+
+impl Analysis {
+    /// Analyses a rom loaded at the given address
+    pub fn new(rom: &[u8], load_address: usize) -> Self {
+        let mut code = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut subroutines = BTreeSet::new();
+        let mut jump_targets = BTreeSet::new();
+        let mut computed_jumps = BTreeSet::new();
+        let mut references = BTreeSet::new();
+        let mut pending = vec![load_address];
+
+        leaders.insert(load_address);
+
+        while let Some(address) = pending.pop() {
+            if code.contains_key(&address) {
+                continue;
+            }
+
+            let instruction = match decode(rom, load_address, address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+
+            code.insert(address, instruction);
+
+            match instruction {
+                Instruction::Jp { addr } => {
+                    jump_targets.insert(addr);
+                }
+                Instruction::Call { addr } => {
+                    subroutines.insert(addr);
+                }
+                Instruction::JpV { .. } => {
+                    computed_jumps.insert(address);
+                }
+                Instruction::LdI { addr } => {
+                    references.insert(addr);
+                }
+                _ => {}
+            }
+
+            let successors = successors(address, instruction);
+
+            if ends_block(instruction) {
+                leaders.extend(successors.iter().map(|&(target, _)| target));
+            }
+
+            pending.extend(successors.iter().map(|&(target, _)| target));
+        }
+
+        let mut analysis = Self {
+            load_address,
+            length: rom.len(),
+            code,
+            blocks: BTreeMap::new(),
+            subroutines,
+            jump_targets,
+            computed_jumps,
+            sprites: vec![],
+        };
+
+        analysis.blocks = analysis.build_blocks(&leaders);
+        analysis.sprites = analysis.find_sprites(&references);
+        analysis
+    }
+
+    /// Returns the basic blocks by their start address
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    /// Returns the addresses called as subroutines
+    pub fn subroutines(&self) -> impl Iterator<Item = usize> + '_ {
+        self.subroutines.iter().copied()
+    }
+
+    /// Returns the addresses of `JP V0, nnn` instructions whose targets
+    /// cannot be known without running the rom
+    pub fn computed_jumps(&self) -> impl Iterator<Item = usize> + '_ {
+        self.computed_jumps.iter().copied()
+    }
+
+    /// Returns the regions of the rom which are loaded into I but never
+    /// executed, from each address loaded up to the next code or reference
+    pub fn sprite_data(&self) -> &[Range<usize>] {
+        &self.sprites
+    }
+
+    /// Returns the regions of the rom which are neither reachable code nor
+    /// likely sprite data
+    pub fn unreachable(&self) -> Vec<Range<usize>> {
+        let mut regions: Vec<Range<usize>> = vec![];
+
+        for address in self.rom_range() {
+            if self.is_code(address) || self.is_sprite(address) {
+                continue;
+            }
+
+            match regions.last_mut() {
+                Some(region) if region.end == address => region.end += 1,
+                _ => regions.push(address..address + 1),
+            }
+        }
+
+        regions
+    }
+
+    /// Returns names for the subroutines, jump targets and sprites that start
+    /// a line of the disassembly, such as `sub_2A4`
+    pub fn labels(&self) -> BTreeMap<usize, String> {
+        let mut labels = BTreeMap::new();
+        let sprites = self.sprites.iter().map(|sprite| sprite.start);
+
+        for (prefix, addresses) in [
+            ("sprite", sprites.collect::<Vec<_>>()),
+            ("label", self.jump_targets.iter().copied().collect()),
+            ("sub", self.subroutines.iter().copied().collect()),
+        ]
+        .iter()
+        {
+            for &address in addresses {
+                if self.starts_line(address) {
+                    labels.insert(address, format!("{}_{:03X}", prefix, address));
+                }
+            }
+        }
+
+        labels
+    }
+
+    /// Lists the instructions in the rom with labels in place of addresses,
+    /// sprite data written with `DB` and comments on unreachable code and
+    /// computed jumps
+    pub fn annotate(&self, rom: &[u8]) -> String {
+        let labels = self.labels();
+
+        disassemble_annotated(rom, self.load_address, |address| {
+            let comment = if self.computed_jumps.contains(&address) {
+                Some("computed jump")
+            } else if !self.is_code(address) && !self.is_sprite(address) {
+                Some("unreachable")
+            } else {
+                None
+            };
+
+            Annotation {
+                label: labels.get(&address).cloned(),
+                data: self.is_sprite(address) && !self.is_code(address),
+                comment: comment.map(str::to_string),
+            }
+        })
+    }
+
+    /// Returns the control flow graph in the DOT format read by graphviz
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+
+            for (address, instruction) in self.code.range(block.start..block.end) {
+                write!(label, "0x{:03X}  {}\\l", address, instruction).unwrap();
+            }
+
+            let style = if self.computed_jumps.range(block.start..block.end).count() != 0 {
+                ", color=red"
+            } else if self.subroutines.contains(&block.start) {
+                ", style=bold"
+            } else {
+                ""
+            };
+
+            writeln!(
+                dot,
+                "    \"0x{:03X}\" [label=\"{}\"{}];",
+                block.start, label, style
+            )
+            .unwrap();
+
+            for (target, edge) in &block.successors {
+                let attributes = match edge {
+                    Edge::Next => "",
+                    Edge::Jump => " [label=\"jump\"]",
+                    Edge::Call => " [label=\"call\", style=dashed]",
+                    Edge::Skip => " [label=\"skip\"]",
+                };
+
+                writeln!(
+                    dot,
+                    "    \"0x{:03X}\" -> \"0x{:03X}\"{};",
+                    block.start, target, attributes
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// Summarises the subroutines, computed jumps, sprite data and
+    /// unreachable regions
+    pub fn report(&self) -> String {
+        let addresses = |addresses: &mut dyn Iterator<Item = usize>| {
+            let addresses: Vec<String> = addresses.map(|a| format!("0x{:03X}", a)).collect();
+
+            match addresses.is_empty() {
+                true => "none".to_string(),
+                false => addresses.join(", "),
+            }
+        };
+        let regions = |regions: &[Range<usize>]| {
+            let regions: Vec<String> = regions
+                .iter()
+                .map(|region| format!("0x{:03X}-0x{:03X}", region.start, region.end - 1))
+                .collect();
+
+            match regions.is_empty() {
+                true => "none".to_string(),
+                false => regions.join(", "),
+            }
+        };
+
+        let mut report = String::new();
+
+        writeln!(report, "Entry: 0x{:03X}", self.load_address).unwrap();
+        writeln!(
+            report,
+            "Code: {} instructions in {} blocks",
+            self.code.len(),
+            self.blocks.len()
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "Subroutines: {}",
+            addresses(&mut self.subroutines())
+        )
+        .unwrap();
+        writeln!(
+            report,
+            "Computed jumps: {}",
+            addresses(&mut self.computed_jumps())
+        )
+        .unwrap();
+        writeln!(report, "Sprite data: {}", regions(&self.sprites)).unwrap();
+        writeln!(report, "Unreachable: {}", regions(&self.unreachable())).unwrap();
+
+        report
+    }
+
+    /// Splits the reachable instructions into blocks at every leader and
+    /// after every instruction that does not simply continue
+    fn build_blocks(&self, leaders: &BTreeSet<usize>) -> BTreeMap<usize, Block> {
+        let mut blocks = BTreeMap::new();
+
+        for &start in leaders.iter().filter(|start| self.code.contains_key(start)) {
+            let mut address = start;
+
+            let successors = loop {
+                let instruction = self.code[&address];
+                let next = address + 2;
+
+                if ends_block(instruction) {
+                    break successors(address, instruction);
+                }
+
+                if leaders.contains(&next) || !self.code.contains_key(&next) {
+                    break vec![(next, Edge::Next)];
+                }
+
+                address = next;
+            };
+
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    end: address + 2,
+                    successors,
+                },
+            );
+        }
+
+        blocks
+    }
+
+    /// Returns the regions loaded into I that were not reached as code
+    fn find_sprites(&self, references: &BTreeSet<usize>) -> Vec<Range<usize>> {
+        let starts: Vec<usize> = references
+            .iter()
+            .copied()
+            .filter(|&address| self.rom_range().contains(&address) && !self.is_code(address))
+            .collect();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, &start)| {
+                let limit = starts
+                    .get(index + 1)
+                    .copied()
+                    .unwrap_or(self.rom_range().end);
+                let end = (start..limit)
+                    .find(|&address| self.is_code(address))
+                    .unwrap_or(limit);
+
+                start..end
+            })
+            .collect()
+    }
+
+    /// Returns the addresses the rom was loaded into
+    fn rom_range(&self) -> Range<usize> {
+        self.load_address..self.load_address + self.length
+    }
+
+    /// Returns whether the address holds either byte of reachable code
+    fn is_code(&self, address: usize) -> bool {
+        self.code.contains_key(&address) || (0 < address && self.code.contains_key(&(address - 1)))
+    }
+
+    /// Returns whether the address is inside likely sprite data
+    fn is_sprite(&self, address: usize) -> bool {
+        self.sprites.iter().any(|sprite| sprite.contains(&address))
+    }
+
+    /// Returns whether the disassembly has a line starting at the address
+    fn starts_line(&self, address: usize) -> bool {
+        self.rom_range().contains(&address) && (address - self.load_address).is_multiple_of(2)
+    }
+}
+
+/// Decodes the instruction at an address if it is inside the rom
+fn decode(rom: &[u8], load_address: usize, address: usize) -> Option<Instruction> {
+    let offset = address.checked_sub(load_address)?;
+    let bytes = rom.get(offset..offset + 2)?;
+
+    Instruction::try_from((bytes[0] as u16) << 8 | bytes[1] as u16).ok()
+}
+
+/// Returns whether the instruction might not continue to the next address
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Ret
+            | Instruction::Jp { .. }
+            | Instruction::Call { .. }
+            | Instruction::JpV { .. }
+            | Instruction::SeV { .. }
+            | Instruction::SneV { .. }
+            | Instruction::SeVV { .. }
+            | Instruction::SneVV { .. }
+            | Instruction::SkpV { .. }
+            | Instruction::SknpV { .. }
+    )
+}
+
+/// Returns the addresses that may run after the instruction
+fn successors(address: usize, instruction: Instruction) -> Vec<(usize, Edge)> {
+    match instruction {
+        Instruction::Jp { addr } => vec![(addr, Edge::Jump)],
+        Instruction::Call { addr } => vec![(addr, Edge::Call), (address + 2, Edge::Next)],
+        Instruction::Ret | Instruction::JpV { .. } => vec![],
+        Instruction::SeV { .. }
+        | Instruction::SneV { .. }
+        | Instruction::SeVV { .. }
+        | Instruction::SneVV { .. }
+        | Instruction::SkpV { .. }
+        | Instruction::SknpV { .. } => vec![(address + 2, Edge::Next), (address + 4, Edge::Skip)],
+        _ => vec![(address + 2, Edge::Next)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::assemble, memory::PROGRAM_OFFSET};
+
+    const SOURCE: &str = "
+                LD V0, 0
+        loop:   CALL draw
+                SE V0, 8
+                JP loop
+                JP V0, 0x300
+                CLS
+        draw:   LD I, box
+                DRW V0, V0, 3
+                ADD V0, 1
+                RET
+        box:    DB 0xF0, 0x90, 0xF0, 0x00
+    ";
+
+    fn analyse() -> (Vec<u8>, Analysis) {
+        let rom = assemble(SOURCE).unwrap();
+        let analysis = Analysis::new(&rom, PROGRAM_OFFSET);
+
+        (rom, analysis)
+    }
+
+    #[test]
+    fn test_report() {
+        let (_, analysis) = analyse();
+
+        assert_eq!(
+            analysis.report(),
+            "Entry: 0x200\n\
+             Code: 9 instructions in 6 blocks\n\
+             Subroutines: 0x20C\n\
+             Computed jumps: 0x208\n\
+             Sprite data: 0x214-0x217\n\
+             Unreachable: 0x20A-0x20B\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let (_, analysis) = analyse();
+        let blocks: Vec<&Block> = analysis.blocks().collect();
+
+        assert_eq!(
+            blocks[1],
+            &Block {
+                start: 0x202,
+                end: 0x204,
+                successors: vec![(0x20C, Edge::Call), (0x204, Edge::Next)],
+            }
+        );
+        assert_eq!(
+            blocks[2].successors,
+            vec![(0x206, Edge::Next), (0x208, Edge::Skip)]
+        );
+    }
+
+    #[test]
+    fn test_annotate_round_trip() {
+        let (rom, analysis) = analyse();
+        let listing = analysis.annotate(&rom);
+
+        assert!(listing.contains("label_202:\n    CALL sub_20C "));
+        assert!(listing.contains("    JP V0, 0x300        ; 0x208 B300 computed jump\n"));
+        assert!(listing.contains("    CLS                 ; 0x20A 00E0 unreachable\n"));
+        assert!(listing.contains("    LD I, sprite_214    ; 0x20C A214\n"));
+        assert!(listing.contains("sprite_214:\n    DB 0xF0, 0x90 "));
+        assert_eq!(assemble(&listing), Ok(rom));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let (_, analysis) = analyse();
+        let dot = analysis.to_dot();
+
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    \"0x202\" -> \"0x20C\" [label=\"call\", style=dashed];\n"));
+        assert!(dot.contains("    \"0x208\" [label=\"0x208  JP V0, 0x300\\l\", color=red];\n"));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use crate::{instruction::Instruction, memory::PROGRAM_OFFSET};
+
+/// An operand of an assembly instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Value(usize),
+}
+
+/// One line of source with its label and comment removed
+struct Line<'a> {
+    number: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+impl Line<'_> {
+    /// Returns the number of bytes the line assembles to
+    fn size(&self) -> usize {
+        match self.mnemonic.as_str() {
+            "DB" => self.operands.len(),
+            "DW" => self.operands.len() * 2,
+            _ => 2,
+        }
+    }
+}
+
+/// Assembles source written with the mnemonics from Cowgod's reference into a
+/// rom to be loaded at `PROGRAM_OFFSET`
+///
+/// Each line holds at most one instruction and may start with a `label:`
+/// which can be used in place of an address. Comments start with `;`, numbers
+/// are decimal or prefixed with `0x` and raw data is written with `DB` for
+/// bytes or `DW` for words.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+    let mut address = PROGRAM_OFFSET;
+
+    for (index, text) in source.lines().enumerate() {
+        let number = index + 1;
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+
+            if !is_label(label) {
+                return Err(format!("Line {}: Invalid label {}", number, label));
+            }
+
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(format!("Line {}: Duplicate label {}", number, label));
+            }
+
+            text = rest.trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+        let line = Line {
+            number,
+            mnemonic: mnemonic.to_uppercase(),
+            operands,
+        };
+
+        address += line.size();
+        lines.push(line);
+    }
+
+    let mut rom = vec![];
+
+    for line in &lines {
+        let operands = line
+            .operands
+            .iter()
+            .map(|operand| parse_operand(operand, &labels))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Line {}: {}", line.number, e))?;
+
+        match line.mnemonic.as_str() {
+            "DB" => {
+                for operand in operands {
+                    rom.push(byte(operand).map_err(|e| format!("Line {}: {}", line.number, e))?);
+                }
+            }
+            "DW" => {
+                for operand in operands {
+                    let word = word(operand).map_err(|e| format!("Line {}: {}", line.number, e))?;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let instruction = encode(mnemonic, &operands)
+                    .map_err(|e| format!("Line {}: {}", line.number, e))?;
+                rom.extend_from_slice(&u16::from(instruction).to_be_bytes());
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+/// Returns whether the name can be used as a label
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && parse_operand(name, &HashMap::new()).is_err()
+}
+
+/// Parses a register, number or label
+fn parse_operand(value: &str, labels: &HashMap<String, usize>) -> Result<Operand, String> {
+    let upper = value.to_uppercase();
+
+    match upper.as_str() {
+        "I" => return Ok(Operand::I),
+        "[I]" => return Ok(Operand::IndirectI),
+        "DT" => return Ok(Operand::Dt),
+        "ST" => return Ok(Operand::St),
+        "K" => return Ok(Operand::K),
+        "F" => return Ok(Operand::F),
+        "B" => return Ok(Operand::B),
+        _ => {}
+    }
+
+    if let Some(register) = upper
+        .strip_prefix('V')
+        .filter(|register| register.len() == 1)
+    {
+        if let Ok(register) = usize::from_str_radix(register, 16) {
+            return Ok(Operand::V(register));
+        }
+    }
+
+    let number = match upper.strip_prefix("0X") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None if upper.starts_with(|c: char| c.is_ascii_digit()) => upper.parse().ok(),
+        None => None,
+    };
+
+    match number.or_else(|| labels.get(value).copied()) {
+        Some(number) => Ok(Operand::Value(number)),
+        None => Err(format!("Unknown operand {}", value)),
+    }
+}
+
+/// Builds the instruction for a mnemonic and its operands
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<Instruction, String> {
+    use Operand::*;
+
+    let instruction = match (mnemonic, operands) {
+        ("CLS", []) => Instruction::Cls,
+        ("RET", []) => Instruction::Ret,
+        ("JP", &[Value(a)]) => Instruction::Jp { addr: addr(a)? },
+        ("JP", &[V(0), Value(a)]) => Instruction::JpV { addr: addr(a)? },
+        ("CALL", &[Value(a)]) => Instruction::Call { addr: addr(a)? },
+        ("SE", &[V(vx), Value(b)]) => Instruction::SeV {
+            vx,
+            byte: byte(Value(b))?,
+        },
+        ("SE", &[V(vx), V(vy)]) => Instruction::SeVV { vx, vy },
+        ("SNE", &[V(vx), Value(b)]) => Instruction::SneV {
+            vx,
+            byte: byte(Value(b))?,
+        },
+        ("SNE", &[V(vx), V(vy)]) => Instruction::SneVV { vx, vy },
+        ("LD", &[V(vx), Value(b)]) => Instruction::LdV {
+            vx,
+            byte: byte(Value(b))?,
+        },
+        ("LD", &[V(vx), V(vy)]) => Instruction::LdVV { vx, vy },
+        ("LD", &[I, Value(a)]) => Instruction::LdI { addr: addr(a)? },
+        ("LD", &[V(vx), Dt]) => Instruction::LdVDt { vx },
+        ("LD", &[V(vx), K]) => Instruction::LdVK { vx },
+        ("LD", &[Dt, V(vx)]) => Instruction::LdDtV { vx },
+        ("LD", &[St, V(vx)]) => Instruction::LdStV { vx },
+        ("LD", &[F, V(vx)]) => Instruction::LdFV { vx },
+        ("LD", &[B, V(vx)]) => Instruction::LdBV { vx },
+        ("LD", &[IndirectI, V(vx)]) => Instruction::LdIV { vx },
+        ("LD", &[V(vx), IndirectI]) => Instruction::LdVI { vx },
+        ("ADD", &[V(vx), Value(b)]) => Instruction::AddV {
+            vx,
+            byte: byte(Value(b))?,
+        },
+        ("ADD", &[V(vx), V(vy)]) => Instruction::AddVV { vx, vy },
+        ("ADD", &[I, V(vx)]) => Instruction::AddIV { vx },
+        ("OR", &[V(vx), V(vy)]) => Instruction::OrVV { vx, vy },
+        ("AND", &[V(vx), V(vy)]) => Instruction::AndVV { vx, vy },
+        ("XOR", &[V(vx), V(vy)]) => Instruction::XorVV { vx, vy },
+        ("SUB", &[V(vx), V(vy)]) => Instruction::SubVV { vx, vy },
+        ("SUBN", &[V(vx), V(vy)]) => Instruction::SubnVV { vx, vy },
+        ("SHR", &[V(vx)]) => Instruction::ShrVV { vx, vy: vx },
+        ("SHR", &[V(vx), V(vy)]) => Instruction::ShrVV { vx, vy },
+        ("SHL", &[V(vx)]) => Instruction::ShlVV { vx, vy: vx },
+        ("SHL", &[V(vx), V(vy)]) => Instruction::ShlVV { vx, vy },
+        ("RND", &[V(vx), Value(b)]) => Instruction::RndV {
+            vx,
+            byte: byte(Value(b))?,
+        },
+        ("DRW", &[V(vx), V(vy), Value(n)]) if n < 16 => Instruction::Drw { vx, vy, n },
+        ("SKP", &[V(vx)]) => Instruction::SkpV { vx },
+        ("SKNP", &[V(vx)]) => Instruction::SknpV { vx },
+        _ => return Err(format!("Invalid operands for {}", mnemonic)),
+    };
+
+    Ok(instruction)
+}
+
+/// Checks that an address fits in 12 bits
+fn addr(value: usize) -> Result<usize, String> {
+    if value <= 0xFFF {
+        Ok(value)
+    } else {
+        Err(format!("Address 0x{:X} is out of range", value))
+    }
+}
+
+/// Checks that an operand is a number which fits in a byte
+fn byte(operand: Operand) -> Result<u8, String> {
+    match operand {
+        Operand::Value(value) if value <= 0xFF => Ok(value as u8),
+        Operand::Value(value) => Err(format!("Byte 0x{:X} is out of range", value)),
+        _ => Err("Expected a byte".to_string()),
+    }
+}
+
+/// Checks that an operand is a number which fits in a word
+fn word(operand: Operand) -> Result<u16, String> {
+    match operand {
+        Operand::Value(value) if value <= 0xFFFF => Ok(value as u16),
+        Operand::Value(value) => Err(format!("Word 0x{:X} is out of range", value)),
+        _ => Err("Expected a word".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_with_labels() {
+        let source = "
+            start:  LD V0, 0x01   ; counter
+                    CALL sub
+                    JP start
+            sub:    ADD V0, 1
+                    RET
+            data:   DB 0xF0, 144
+        ";
+
+        let rom = assemble(source).unwrap();
+
+        assert_eq!(
+            rom,
+            vec![0x60, 0x01, 0x22, 0x06, 0x12, 0x00, 0x70, 0x01, 0x00, 0xEE, 0xF0, 0x90]
+        );
+    }
+
+    #[test]
+    fn test_assemble_special_registers() {
+        let rom = assemble("LD [I], VA\nLD VB, DT\nLD F, V2\nDRW V0, V1, 15").unwrap();
+
+        assert_eq!(rom, vec![0xFA, 0x55, 0xFB, 0x07, 0xF2, 0x29, 0xD0, 0x1F]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            assemble("CLS\nLD V0, 0x100"),
+            Err("Line 2: Byte 0x100 is out of range".to_string())
+        );
+        assert_eq!(
+            assemble("JP nowhere"),
+            Err("Line 1: Unknown operand nowhere".to_string())
+        );
+        assert_eq!(
+            assemble("OR V0, 1"),
+            Err("Line 1: Invalid operands for OR".to_string())
+        );
+    }
+}
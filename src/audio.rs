@@ -0,0 +1,157 @@
+use std::io::{self, Write};
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const FREQUENCY: f32 = 440.0;
+pub const VOLUME: f32 = 0.5;
+
+/// Generates the tone played by the Chip-8 buzzer
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl SquareWave {
+    /// Creates a square wave of the given frequency for the sample rate
+    pub fn new(frequency: f32, sample_rate: u32, volume: f32) -> Self {
+        Self {
+            phase_inc: frequency / sample_rate as f32,
+            phase: 0.0,
+            volume,
+        }
+    }
+
+    /// Fills the buffer with the next samples of the wave
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = if self.phase <= 0.5 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+impl Default for SquareWave {
+    fn default() -> Self {
+        Self::new(FREQUENCY, SAMPLE_RATE, VOLUME)
+    }
+}
+
+/// Synthesises the buzzer offline so that it can be saved as a WAV file
+///
+/// Instead of being driven by a real-time audio callback, the recorder is
+/// given the state of the sound timer once per emulated frame and appends
+/// either a frame of tone or a frame of silence.
+pub struct WavRecorder {
+    wave: SquareWave,
+    samples_per_frame: usize,
+    samples: Vec<f32>,
+}
+
+impl WavRecorder {
+    /// Creates an empty recording for an emulator running at the frame rate
+    pub fn new(frame_rate: u32) -> Self {
+        Self {
+            wave: SquareWave::default(),
+            samples_per_frame: (SAMPLE_RATE / frame_rate) as usize,
+            samples: vec![],
+        }
+    }
+
+    /// Changes the loudness of the tone recorded from now on
+    pub fn set_volume(&mut self, volume: f32) {
+        self.wave.volume = volume;
+    }
+
+    /// Appends one frame of audio depending on whether the buzzer is active
+    ///
+    /// The phase of the wave is kept across silent frames in the same way as
+    /// pausing and resuming an audio device.
+    pub fn record_frame(&mut self, is_sound_playing: bool) {
+        let start = self.samples.len();
+        self.samples.resize(start + self.samples_per_frame, 0.0);
+
+        if is_sound_playing {
+            self.wave.fill(&mut self.samples[start..]);
+        }
+    }
+
+    /// Returns every sample recorded so far
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Writes the recording as a mono 16-bit PCM WAV file
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let channels: u16 = 1;
+        let bits_per_sample: u16 = 16;
+        let block_align = channels * bits_per_sample / 8;
+        let byte_rate = SAMPLE_RATE * block_align as u32;
+        let data_size = (self.samples.len() * block_align as usize) as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&bits_per_sample.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+
+        for sample in &self.samples {
+            let value = (sample * i16::MAX as f32) as i16;
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_frame() {
+        let mut recorder = WavRecorder::new(60);
+
+        recorder.record_frame(false);
+        recorder.record_frame(true);
+
+        assert_eq!(recorder.samples().len(), 2 * 735);
+        assert!(recorder.samples()[..735].iter().all(|&x| x == 0.0));
+        assert!(recorder.samples()[735..].iter().all(|&x| x.abs() == VOLUME));
+
+        recorder.set_volume(0.25);
+        recorder.record_frame(true);
+        assert!(recorder.samples()[2 * 735..]
+            .iter()
+            .all(|&x| x.abs() == 0.25));
+    }
+
+    #[test]
+    fn test_write() {
+        let mut recorder = WavRecorder::new(60);
+        recorder.record_frame(true);
+        let mut bytes = vec![];
+
+        recorder.write(&mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 2 * 735);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &(36u32 + 2 * 735).to_le_bytes());
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[44..46], &16383i16.to_le_bytes());
+    }
+}
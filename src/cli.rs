@@ -0,0 +1,651 @@
+use std::{collections::HashMap, fmt, ops::RangeInclusive, path::PathBuf, str::FromStr};
+
+use chip_8::{
+    database::parse_color,
+    emulator::{Backend, StackDepth, WriteProtection},
+    font::{Font, FontSet},
+    quirks::{Platform, Quirks},
+};
+
+pub const USAGE: &str = "\
+Usage:
+    chip-8 [run] [options] <path_to_rom>
+    chip-8 disasm [--load-address <hex>] [--database <dir>] <path_to_rom>
+    chip-8 analyze [--load-address <hex>] [--database <dir>] <path_to_rom>
+    chip-8 cfg [--load-address <hex>] [--database <dir>] <path_to_rom>
+    chip-8 asm <path_to_source> <path_to_rom>
+    chip-8 info [--database <dir>] <path_to_rom>
+    chip-8 control [port]
+    chip-8 --help
+
+Options:
+    --scale <n>                 Initial size of each pixel in the window
+    --grid, --no-grid           Draw lines between the pixels or not
+    --scanlines, --no-scanlines Darken every other line of the window or not
+    --font <name|path>          Built-in font (octo, vip, dream6800, eti660,
+                                fish) or an 80 byte font file
+    --speed <n>                 Instructions run every frame
+    --quirks <preset|list>      Quirks of a platform or a comma separated list
+                                such as shift,jump
+    --platform <id>             Platform to use the quirks and speed of, such
+                                as originalChip8, chip48 or superchip
+    --seed <n>                  Seed for the random number generator
+    --backend <name>            How instructions are run (interpreter,
+                                recompiler)
+    --load-address <hex>        Address to load the rom at and start from,
+                                such as 600, instead of 200 or the database
+    --write-protection <mode>   What to do when the program writes below 200
+                                (off, log, fault)
+    --stack-depth <n>           Nested subroutine calls allowed before a stack
+                                overflow (12, 16 or unbounded)
+    --stack-in-memory, --no-stack-in-memory
+                                Keep return addresses in emulated memory
+                                like the COSMAC VIP or outside of it
+    --palette <colour,colour>   Background and foreground as #rrggbb
+    --mute, --no-mute           Never play the buzzer, or play it even if
+                                the config file mutes it
+    --volume <n>                Loudness of the buzzer from 0 to 1
+    --layout <name>             Keyboard layout (qwerty, azerty, qwertz,
+                                dvorak, colemak)
+    --headless                  Run without a window for --frames frames,
+                                saving images and audio to --output if given
+    --frames <n>                Number of frames to run headless
+    --output <dir>              Directory to save headless frames and audio
+    --debugger                  Start paused, F5 pauses or resumes and F10
+                                steps one instruction
+    --gdb <port>                Wait for a GDB client on localhost and let it
+                                control the emulator
+    --record <path>             Save the keys pressed on every frame
+    --playback <path>           Replay keys saved with --record
+    --trace <path>              Write every executed instruction to a file
+    --trace-addresses <a-b>     Only trace instructions at addresses from a to
+                                b in hex, such as 200-2FF
+    --trace-cycles <a-b>        Only trace cycles from a to b
+    --profile <format>          Print where the time went on exit as a
+                                report or as folded stacks for flamegraphs
+                                (report, folded)
+    --coverage-map <path>       Save a map of the addresses executed, read
+                                and written on exit
+    --coverage-asm <path>       Save a disassembly noting how each word was
+                                used on exit, with data written as DB
+    --database <dir>            Directory with the sha1-hashes.json and
+                                programs.json of the community chip-8-database
+                                to use instead of the bundled one
+    --config <path>             Config file to read instead of the one in the
+                                user's config directory
+    -h, --help                  Show this message
+";
+
+/// The physical keyboard layout used to find the keys for the Chip-8 keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl Layout {
+    /// Returns the names of the keys in the top left 4x4 block of the
+    /// keyboard, row by row
+    pub fn keys(self) -> [&'static str; 16] {
+        match self {
+            Layout::Qwerty => [
+                "1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V",
+            ],
+            Layout::Azerty => [
+                "1", "2", "3", "4", "A", "Z", "E", "R", "Q", "S", "D", "F", "W", "X", "C", "V",
+            ],
+            Layout::Qwertz => [
+                "1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Y", "X", "C", "V",
+            ],
+            Layout::Dvorak => [
+                "1", "2", "3", "4", "'", ",", ".", "P", "A", "O", "E", "U", ";", "Q", "J", "K",
+            ],
+            Layout::Colemak => [
+                "1", "2", "3", "4", "Q", "W", "F", "P", "A", "R", "S", "T", "Z", "X", "C", "V",
+            ],
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "qwertz" => Ok(Layout::Qwertz),
+            "dvorak" => Ok(Layout::Dvorak),
+            "colemak" => Ok(Layout::Colemak),
+            _ => Err(format!("Unknown layout {}", value)),
+        }
+    }
+}
+
+/// How the profile is printed when the emulator exits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Report,
+    Folded,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "report" => Ok(ProfileFormat::Report),
+            "folded" => Ok(ProfileFormat::Folded),
+            _ => Err(format!("Unknown profile format {}", value)),
+        }
+    }
+}
+
+/// Settings for running a rom chosen on the command line
+pub struct Options {
+    pub path: String,
+    pub scale: Option<u32>,
+    pub grid: Option<bool>,
+    pub scanlines: Option<bool>,
+    pub font: Option<Font>,
+    pub speed: Option<usize>,
+    pub quirks: Option<Quirks>,
+    pub platform: Option<Platform>,
+    pub seed: Option<u64>,
+    pub backend: Option<Backend>,
+    pub load_address: Option<usize>,
+    pub write_protection: Option<WriteProtection>,
+    pub stack_depth: Option<StackDepth>,
+    pub stack_in_memory: Option<bool>,
+    pub palette: Option<[[u8; 3]; 2]>,
+    pub mute: Option<bool>,
+    pub volume: Option<f32>,
+    pub layout: Option<Layout>,
+    pub bindings: HashMap<String, u8>,
+    pub frame_time: Option<u64>,
+    pub headless: bool,
+    pub frames: Option<usize>,
+    pub output: Option<PathBuf>,
+    pub debugger: bool,
+    pub gdb: Option<u16>,
+    pub record: Option<PathBuf>,
+    pub playback: Option<PathBuf>,
+    pub trace: Option<PathBuf>,
+    pub trace_addresses: Option<RangeInclusive<usize>>,
+    pub trace_cycles: Option<RangeInclusive<u64>>,
+    pub profile: Option<ProfileFormat>,
+    pub coverage_map: Option<PathBuf>,
+    pub coverage_asm: Option<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+}
+
+impl Options {
+    /// Returns the defaults for running the given rom
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            scale: None,
+            grid: None,
+            scanlines: None,
+            font: None,
+            speed: None,
+            quirks: None,
+            platform: None,
+            seed: None,
+            backend: None,
+            load_address: None,
+            write_protection: None,
+            stack_depth: None,
+            stack_in_memory: None,
+            palette: None,
+            mute: None,
+            volume: None,
+            layout: None,
+            bindings: HashMap::new(),
+            frame_time: None,
+            headless: false,
+            frames: None,
+            output: None,
+            debugger: false,
+            gdb: None,
+            record: None,
+            playback: None,
+            trace: None,
+            trace_addresses: None,
+            trace_cycles: None,
+            profile: None,
+            coverage_map: None,
+            coverage_asm: None,
+            database: None,
+            config: None,
+        }
+    }
+}
+
+/// A rom to analyse without running it
+pub struct Target {
+    pub path: String,
+    /// Where the rom is loaded, instead of what the database or 0x200 says
+    pub load_address: Option<usize>,
+    pub database: Option<PathBuf>,
+}
+
+/// What the program was asked to do
+pub enum Command {
+    Run(Box<Options>),
+    Disasm(Target),
+    Analyze(Target),
+    Cfg(Target),
+    Asm {
+        source: String,
+        output: String,
+    },
+    Info {
+        path: String,
+        database: Option<PathBuf>,
+    },
+    Control {
+        port: Option<u16>,
+    },
+    Help,
+}
+
+/// Reads a font either by the name of a built-in font or from a file
+pub fn load_font(value: &str) -> Result<Font, String> {
+    match value.parse::<FontSet>() {
+        Ok(set) => Ok(Font::new(set)),
+        Err(_) => {
+            let bytes = std::fs::read(value).map_err(|e| format!("Unable to read font: {}", e))?;
+            Font::from_bytes(&bytes)
+        }
+    }
+}
+
+/// Checks that a volume is between silent and full scale
+pub fn check_volume(volume: f32) -> Result<f32, String> {
+    if (0.0..=1.0).contains(&volume) {
+        Ok(volume)
+    } else {
+        Err(format!("Volume {} is not from 0 to 1", volume))
+    }
+}
+
+/// Parses a value of an option or explains what was expected
+fn parse_value<T: FromStr>(option: &str, value: Option<String>, expected: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("{} expects {}", option, expected))?;
+
+    value
+        .parse()
+        .map_err(|e| format!("{} expects {}: {}", option, expected, e))
+}
+
+/// Parses an address written in hex with or without a `0x` prefix
+fn parse_address(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("{} expects an address in hex such as 600", option))?;
+
+    usize::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|e| {
+        format!(
+            "{} expects an address in hex such as 600: {}: {}",
+            option, value, e
+        )
+    })
+}
+
+/// Returns the options a subcommand other than `run` makes use of
+fn options_used_by(command: &str) -> &'static [&'static str] {
+    match command {
+        "disasm" | "analyze" | "cfg" => &["--load-address", "--database"],
+        "info" => &["--database"],
+        _ => &[],
+    }
+}
+
+/// Parses `<background>,<foreground>` colours
+fn parse_palette(value: &str) -> Option<[[u8; 3]; 2]> {
+    let (background, foreground) = value.split_once(',')?;
+
+    Some([parse_color(background)?, parse_color(foreground)?])
+}
+
+/// Parses an inclusive range written as `<start>-<end>` in the given radix
+fn parse_range<T: Ord>(
+    option: &str,
+    value: Option<String>,
+    parse: fn(&str, u32) -> Result<T, std::num::ParseIntError>,
+    radix: u32,
+) -> Result<RangeInclusive<T>, String> {
+    let invalid = || {
+        format!(
+            "{} expects a range such as {}",
+            option,
+            if radix == 16 { "200-2FF" } else { "0-1000" }
+        )
+    };
+    let value = value.ok_or_else(invalid)?;
+    let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+    let start = parse(start, radix).map_err(|_| invalid())?;
+    let end = parse(end, radix).map_err(|_| invalid())?;
+
+    if end < start {
+        return Err(invalid());
+    }
+
+    Ok(start..=end)
+}
+
+/// Parses the arguments after the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+
+    let command = match args.peek().map(String::as_str) {
+        Some("run") | Some("disasm") | Some("analyze") | Some("cfg") | Some("asm")
+        | Some("info") | Some("control") => args.next(),
+        _ => None,
+    };
+
+    let mut positional = vec![];
+    let mut given = vec![];
+    let mut options = Options::new(String::new());
+
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') {
+            given.push(arg.clone());
+        }
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--scale" => {
+                let scale = parse_value(&arg, args.next(), "a positive integer")?;
+
+                if scale == 0 {
+                    return Err("--scale expects a positive integer".to_string());
+                }
+
+                options.scale = Some(scale);
+            }
+            "--grid" => options.grid = Some(true),
+            "--no-grid" => options.grid = Some(false),
+            "--scanlines" => options.scanlines = Some(true),
+            "--no-scanlines" => options.scanlines = Some(false),
+            "--font" => {
+                options.font = Some(load_font(
+                    &args.next().ok_or("--font expects a name or path")?,
+                )?);
+            }
+            "--speed" => options.speed = Some(parse_value(&arg, args.next(), "an integer")?),
+            "--quirks" => {
+                options.quirks = Some(parse_value(&arg, args.next(), "a preset or list")?);
+            }
+            "--platform" => {
+                options.platform = Some(parse_value(&arg, args.next(), "a platform id")?);
+            }
+            "--seed" => options.seed = Some(parse_value(&arg, args.next(), "an integer")?),
+            "--backend" => options.backend = Some(parse_value(&arg, args.next(), "a backend")?),
+            "--load-address" => options.load_address = Some(parse_address(&arg, args.next())?),
+            "--write-protection" => {
+                options.write_protection = Some(parse_value(&arg, args.next(), "a mode")?)
+            }
+            "--stack-depth" => {
+                options.stack_depth = Some(parse_value(&arg, args.next(), "a depth")?)
+            }
+            "--stack-in-memory" => options.stack_in_memory = Some(true),
+            "--no-stack-in-memory" => options.stack_in_memory = Some(false),
+            "--palette" => {
+                options.palette = Some(
+                    args.next()
+                        .as_deref()
+                        .and_then(parse_palette)
+                        .ok_or("--palette expects two colours such as #000000,#ffffff")?,
+                );
+            }
+            "--mute" => options.mute = Some(true),
+            "--no-mute" => options.mute = Some(false),
+            "--volume" => {
+                let volume = parse_value(&arg, args.next(), "a number from 0 to 1")?;
+                options.volume = Some(check_volume(volume)?);
+            }
+            "--layout" => options.layout = Some(parse_value(&arg, args.next(), "a layout")?),
+            "--headless" => options.headless = true,
+            "--frames" => options.frames = Some(parse_value(&arg, args.next(), "an integer")?),
+            "--output" => options.output = Some(parse_value(&arg, args.next(), "a directory")?),
+            "--debugger" => options.debugger = true,
+            "--gdb" => options.gdb = Some(parse_value(&arg, args.next(), "a port")?),
+            "--record" => options.record = Some(parse_value(&arg, args.next(), "a path")?),
+            "--playback" => options.playback = Some(parse_value(&arg, args.next(), "a path")?),
+            "--trace" => options.trace = Some(parse_value(&arg, args.next(), "a path")?),
+            "--trace-addresses" => {
+                options.trace_addresses =
+                    Some(parse_range(&arg, args.next(), usize::from_str_radix, 16)?);
+            }
+            "--trace-cycles" => {
+                options.trace_cycles =
+                    Some(parse_range(&arg, args.next(), u64::from_str_radix, 10)?);
+            }
+            "--profile" => options.profile = Some(parse_value(&arg, args.next(), "a format")?),
+            "--coverage-map" => {
+                options.coverage_map = Some(parse_value(&arg, args.next(), "a path")?)
+            }
+            "--coverage-asm" => {
+                options.coverage_asm = Some(parse_value(&arg, args.next(), "a path")?)
+            }
+            "--database" => options.database = Some(parse_value(&arg, args.next(), "a directory")?),
+            "--config" => options.config = Some(parse_value(&arg, args.next(), "a path")?),
+            _ if arg.starts_with('-') => {
+                return Err(format!("Unknown option {}\n\n{}", arg, USAGE))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if let Some(command) = command.as_deref().filter(|&command| command != "run") {
+        let used = options_used_by(command);
+
+        if let Some(option) = given.iter().find(|option| !used.contains(&option.as_str())) {
+            return Err(format!("{} cannot be used with {}", option, command));
+        }
+    }
+
+    let target = |path: &String| Target {
+        path: path.clone(),
+        load_address: options.load_address,
+        database: options.database.clone(),
+    };
+
+    let command = match (command.as_deref(), positional.as_slice()) {
+        (None, [path]) | (Some("run"), [path]) => {
+            options.path = path.clone();
+
+            if options.headless && options.frames.is_none() {
+                return Err("--headless expects --frames".to_string());
+            }
+
+            if options.headless && options.gdb.is_some() {
+                return Err("--gdb needs a window and cannot be used with --headless".to_string());
+            }
+
+            Command::Run(Box::new(options))
+        }
+        (Some("disasm"), [path]) => Command::Disasm(target(path)),
+        (Some("analyze"), [path]) => Command::Analyze(target(path)),
+        (Some("cfg"), [path]) => Command::Cfg(target(path)),
+        (Some("asm"), [source, output]) => Command::Asm {
+            source: source.clone(),
+            output: output.clone(),
+        },
+        (Some("info"), [path]) => Command::Info {
+            path: path.clone(),
+            database: options.database,
+        },
+        (Some("control"), []) => Command::Control { port: None },
+        (Some("control"), [port]) => Command::Control {
+            port: Some(parse_value("control", Some(port.clone()), "a port")?),
+        },
+        _ => return Err(USAGE.to_string()),
+    };
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_run() {
+        let command = parse(args(
+            "--speed 20 --quirks chip48 --seed 7 --palette #000000,#ffffff game.ch8",
+        ));
+
+        match command {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.path, "game.ch8");
+                assert_eq!(options.speed, Some(20));
+                assert_eq!(options.quirks, Some(Platform::Chip48.quirks()));
+                assert_eq!(options.seed, Some(7));
+                assert_eq!(options.palette, Some([[0, 0, 0], [0xFF, 0xFF, 0xFF]]));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        match parse(args(
+            "--backend recompiler --load-address 0x600 --write-protection fault game.ch8",
+        )) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.backend, Some(Backend::Recompiler));
+                assert_eq!(options.load_address, Some(0x600));
+                assert_eq!(options.write_protection, Some(WriteProtection::Fault));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        match parse(args("--stack-depth unbounded --stack-in-memory game.ch8")) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.stack_depth, Some(StackDepth::Unbounded));
+                assert_eq!(options.stack_in_memory, Some(true));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        match parse(args("--stack-depth 12 --no-stack-in-memory game.ch8")) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.stack_depth, Some(StackDepth::Limited(12)));
+                assert_eq!(options.stack_in_memory, Some(false));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        match parse(args("--no-grid --mute --volume 0.25 game.ch8")) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.grid, Some(false));
+                assert_eq!(options.scanlines, None);
+                assert_eq!(options.mute, Some(true));
+                assert_eq!(options.volume, Some(0.25));
+            }
+            _ => panic!("Expected the run command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trace_ranges() {
+        match parse(args(
+            "--trace-addresses 200-2fe --trace-cycles 10-20 game.ch8",
+        )) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.trace_addresses, Some(0x200..=0x2FE));
+                assert_eq!(options.trace_cycles, Some(10..=20));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        match parse(args("--profile folded game.ch8")) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.profile, Some(ProfileFormat::Folded));
+            }
+            _ => panic!("Expected the run command"),
+        }
+
+        assert!(parse(args("--trace-cycles 20-10 game.ch8")).is_err());
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert!(matches!(
+            parse(args("asm game.asm game.ch8")),
+            Ok(Command::Asm { source, output }) if source == "game.asm" && output == "game.ch8"
+        ));
+        assert!(matches!(
+            parse(args("info game.ch8")),
+            Ok(Command::Info { database: None, .. })
+        ));
+        assert!(matches!(
+            parse(args("info --database chip-8-database/database game.ch8")),
+            Ok(Command::Info { database: Some(database), .. })
+                if database == Path::new("chip-8-database/database")
+        ));
+        assert!(matches!(
+            parse(args("cfg game.ch8")),
+            Ok(Command::Cfg(target)) if target.path == "game.ch8" && target.load_address.is_none()
+        ));
+        assert!(matches!(
+            parse(args("disasm --load-address 600 --database db game.ch8")),
+            Ok(Command::Disasm(target))
+                if target.load_address == Some(0x600)
+                    && target.database.as_deref() == Some(Path::new("db"))
+        ));
+        assert!(matches!(
+            parse(args("control 4000")),
+            Ok(Command::Control { port: Some(4000) })
+        ));
+        assert!(matches!(parse(args("run --help")), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(args("")).err(), Some(USAGE.to_string()));
+        assert!(parse(args("--scale 0 game.ch8")).is_err());
+        assert!(parse(args("--volume 1.5 game.ch8")).is_err());
+        assert!(parse(args("--load-address g00 game.ch8")).is_err());
+        assert!(parse(args("--write-protection on game.ch8")).is_err());
+        assert!(parse(args("--backend jit game.ch8")).is_err());
+        assert!(parse(args("--stack-depth 0 game.ch8")).is_err());
+        assert!(parse(args("--stack-depth deep game.ch8")).is_err());
+        assert!(parse(args("--bogus game.ch8")).is_err());
+        assert!(parse(args("--headless game.ch8")).is_err());
+        assert!(parse(args("--headless --frames 1 --gdb 1234 game.ch8")).is_err());
+        assert!(parse(args("--gdb 70000 game.ch8")).is_err());
+        assert!(parse(args("disasm a.ch8 b.ch8")).is_err());
+        assert_eq!(
+            parse(args("disasm --speed 20 game.ch8")).err(),
+            Some("--speed cannot be used with disasm".to_string())
+        );
+        assert_eq!(
+            parse(args("info --load-address 600 game.ch8")).err(),
+            Some("--load-address cannot be used with info".to_string())
+        );
+        assert!(parse(args("analyze --platform chip48 game.ch8")).is_err());
+        assert!(parse(args("cfg --scale 2 game.ch8")).is_err());
+        assert!(parse(args("asm --database db game.asm game.ch8")).is_err());
+        assert!(parse(args("control --seed 1")).is_err());
+        assert_eq!(
+            parse(args("game.ch8 --load-address")).err(),
+            Some("--load-address expects an address in hex such as 600".to_string())
+        );
+        assert!(parse(args("--load-address 0x game.ch8"))
+            .err()
+            .unwrap()
+            .starts_with("--load-address expects an address in hex such as 600: 0x:"));
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chip_8::{
+    database::{hash, parse_color, RomInfo},
+    emulator::StackDepth,
+    font::Font,
+};
+use serde::Deserialize;
+
+use crate::cli::{check_volume, load_font, Options};
+
+/// Settings which can be given in the config file, where anything left out
+/// falls back to the command line or the built-in defaults
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    scale: Option<u32>,
+    grid: Option<bool>,
+    scanlines: Option<bool>,
+    font: Option<String>,
+    speed: Option<usize>,
+    quirks: Option<String>,
+    platform: Option<String>,
+    stack_depth: Option<StackDepth>,
+    stack_in_memory: Option<bool>,
+    palette: Option<[String; 2]>,
+    mute: Option<bool>,
+    volume: Option<f32>,
+    layout: Option<String>,
+    frame_time: Option<u64>,
+    keys: HashMap<String, u8>,
+    database: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Combines two sets of settings with the other taking priority
+    fn merge(mut self, other: &Settings) -> Self {
+        macro_rules! take {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field.clone();
+                })*
+            };
+        }
+
+        take!(
+            scale,
+            grid,
+            scanlines,
+            font,
+            speed,
+            quirks,
+            platform,
+            stack_depth,
+            stack_in_memory,
+            palette,
+            mute,
+            volume,
+            layout,
+            frame_time,
+            database
+        );
+
+        self.keys
+            .extend(other.keys.iter().map(|(name, &key)| (name.clone(), key)));
+        self
+    }
+
+    /// Returns the directory of the database to look roms up in
+    pub fn database(&self) -> Option<&Path> {
+        self.database.as_deref()
+    }
+
+    /// Fills in any options that were not given on the command line
+    pub fn apply(&self, options: &mut Options) -> Result<(), String> {
+        options.scale = options.scale.or(self.scale);
+        options.grid = options.grid.or(self.grid);
+        options.scanlines = options.scanlines.or(self.scanlines);
+        options.speed = options.speed.or(self.speed);
+        options.stack_depth = options.stack_depth.or(self.stack_depth);
+        options.stack_in_memory = options.stack_in_memory.or(self.stack_in_memory);
+        options.mute = options.mute.or(self.mute);
+        options.volume = options
+            .volume
+            .or(self.volume.map(check_volume).transpose()?);
+        options.frame_time = options.frame_time.or(self.frame_time);
+        options.database = options.database.clone().or(self.database.clone());
+
+        if let (None, Some(font)) = (&options.font, &self.font) {
+            options.font = Some(load_font(font)?);
+        }
+
+        if let (None, Some(quirks)) = (&options.quirks, &self.quirks) {
+            options.quirks = Some(quirks.parse()?);
+        }
+
+        if let (None, Some(platform)) = (&options.platform, &self.platform) {
+            options.platform = Some(platform.parse()?);
+        }
+
+        if let (None, Some([background, foreground])) = (&options.palette, &self.palette) {
+            let parse = |color: &str| parse_color(color).ok_or(format!("Invalid colour {}", color));
+            options.palette = Some([parse(background)?, parse(foreground)?]);
+        }
+
+        if let (None, Some(layout)) = (&options.layout, &self.layout) {
+            options.layout = Some(layout.parse()?);
+        }
+
+        for (name, &key) in &self.keys {
+            options.bindings.entry(name.clone()).or_insert(key);
+        }
+
+        Ok(())
+    }
+}
+
+/// The contents of the config file
+///
+/// ```toml
+/// [default]
+/// speed = 12
+/// palette = ["#000000", "#ffffff"]
+///
+/// [default.keys]
+/// Space = 0x5
+///
+/// [rom."pong.ch8"]
+/// speed = 8
+/// ```
+///
+/// Sections under `rom` are chosen by the file name or SHA-1 hash of the rom
+/// and override both the database and the defaults, which only fill in what
+/// the database does not recommend for the rom.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    default: Settings,
+    rom: HashMap<String, Settings>,
+}
+
+impl Config {
+    /// Parses the config file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| format!("Invalid config: {}", e))
+    }
+
+    /// Reads the config file given on the command line, or otherwise the one
+    /// in the user's config directory if there is one
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) if !required && !path.exists() => Ok(Self::default()),
+            Err(e) => Err(format!("Unable to read config {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Returns the `[default]` section
+    pub fn defaults(&self) -> &Settings {
+        &self.default
+    }
+
+    /// Returns the settings from the sections for a rom, with a section for
+    /// its hash taking priority over one for its file name
+    pub fn settings(&self, path: &str, rom: &[u8]) -> Settings {
+        let name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+
+        [name, Some(hash(rom))]
+            .iter()
+            .flatten()
+            .filter_map(|key| self.rom.get(key))
+            .fold(Settings::default(), |settings, rom| settings.merge(rom))
+    }
+}
+
+/// Fills in the settings the database recommends for a rom that were not
+/// given on the command line or in a section for the rom
+///
+/// The speed and quirks of the platform chosen so far are filled in too, so
+/// that the `[default]` section applied afterwards cannot override them.
+pub fn apply_database(options: &mut Options, info: Option<&RomInfo>) {
+    if let Some(info) = info {
+        if options.platform.is_none() {
+            options.speed = options.speed.or(info.tick_rate);
+            options.quirks = options.quirks.or(info.quirks);
+            options.platform = info.platform;
+        }
+
+        options.load_address = options.load_address.or(info.start_address);
+
+        if let (None, Some(set)) = (&options.font, info.font) {
+            options.font = Some(Font::new(set));
+        }
+
+        if let (None, [background, foreground, ..]) = (options.palette, info.colors.as_slice()) {
+            options.palette = Some([*background, *foreground]);
+        }
+
+        for (control, &key) in &info.keys {
+            let name = match control.as_str() {
+                "up" => "Up",
+                "down" => "Down",
+                "left" => "Left",
+                "right" => "Right",
+                "a" => "Space",
+                "b" => "Left Shift",
+                _ => continue,
+            };
+
+            options.bindings.entry(name.to_string()).or_insert(key);
+        }
+    }
+
+    if let Some(platform) = options.platform {
+        options.speed = options.speed.or(Some(platform.tick_rate()));
+        options.quirks = options.quirks.or(Some(platform.quirks()));
+    }
+}
+
+/// Returns `chip-8/config.toml` in `$XDG_CONFIG_HOME` or `~/.config`
+fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_home.join("chip-8").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chip_8::{assembler::assemble, database::Database, quirks::Platform};
+
+    use super::*;
+
+    const CONFIG: &str = r##"
+        [default]
+        speed = 12
+        scale = 10
+        palette = ["#000000", "#ffffff"]
+        scanlines = true
+        volume = 0.2
+        database = "chip-8-database/database"
+
+        [default.keys]
+        Space = 0x5
+
+        [rom."pong.ch8"]
+        speed = 8
+        grid = true
+        stack-depth = "unbounded"
+        stack-in-memory = true
+
+        [rom.a9993e364706816aba3e25717850c26c9cd0d89d]
+        speed = 30
+        stack-depth = 12
+    "##;
+
+    #[test]
+    fn test_settings_for_rom() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let settings = config.settings("roms/pong.ch8", &[]);
+        assert_eq!(settings.speed, Some(8));
+        assert_eq!(settings.scale, None);
+        assert_eq!(settings.grid, Some(true));
+
+        let settings = config.settings("roms/pong.ch8", &[]);
+        assert_eq!(settings.stack_depth, Some(StackDepth::Unbounded));
+        assert_eq!(settings.stack_in_memory, Some(true));
+
+        let settings = config.settings("roms/pong.ch8", b"abc");
+        assert_eq!(settings.speed, Some(30));
+        assert_eq!(settings.grid, Some(true));
+        assert_eq!(settings.stack_depth, Some(StackDepth::Limited(12)));
+
+        let settings = config.settings("roms/tetris.ch8", &[]);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(config.defaults().speed, Some(12));
+    }
+
+    #[test]
+    fn test_command_line_takes_priority() {
+        let config = Config::parse(CONFIG).unwrap();
+        let mut options = Options::new("pong.ch8".to_string());
+        options.speed = Some(20);
+        options.scanlines = Some(false);
+
+        config
+            .settings("pong.ch8", &[])
+            .apply(&mut options)
+            .unwrap();
+        apply_database(&mut options, None);
+        config.defaults().apply(&mut options).unwrap();
+
+        assert_eq!(options.speed, Some(20));
+        assert_eq!(options.scale, Some(10));
+        assert_eq!(options.grid, Some(true));
+        assert_eq!(options.scanlines, Some(false));
+        assert_eq!(options.volume, Some(0.2));
+        assert_eq!(options.palette, Some([[0, 0, 0], [0xFF, 0xFF, 0xFF]]));
+        assert_eq!(options.bindings.get("Space"), Some(&0x5));
+        assert_eq!(
+            options.database.as_deref(),
+            Some(Path::new("chip-8-database/database"))
+        );
+    }
+
+    #[test]
+    fn test_database_takes_priority_over_defaults() {
+        let config = Config::parse(
+            r##"
+            [default]
+            speed = 12
+            platform = "chip48"
+            palette = ["#000000", "#ffffff"]
+
+            [rom."tetris.ch8"]
+            speed = 20
+            "##,
+        )
+        .unwrap();
+        let known = assemble(include_str!("../tests/conformance/quirks.asm")).unwrap();
+        let info = Database::bundled().lookup(&known);
+        assert!(info.is_some());
+
+        let settle = |path: &str, rom: &[u8], info: Option<&RomInfo>, options: &mut Options| {
+            config.settings(path, rom).apply(options).unwrap();
+            apply_database(options, info);
+            config.defaults().apply(options).unwrap();
+        };
+
+        let mut options = Options::new("quirks.ch8".to_string());
+        settle("quirks.ch8", &known, info.as_ref(), &mut options);
+        assert_eq!(options.speed, Some(8));
+        assert_eq!(options.platform, Some(Platform::ModernChip8));
+        assert_eq!(options.quirks, Some(Platform::ModernChip8.quirks()));
+        assert_eq!(options.palette, Some([[0, 0, 0], [0xFF, 0xFF, 0xFF]]));
+
+        let mut options = Options::new("tetris.ch8".to_string());
+        settle("tetris.ch8", &known, info.as_ref(), &mut options);
+        assert_eq!(options.speed, Some(20));
+        assert_eq!(options.platform, Some(Platform::ModernChip8));
+
+        let mut options = Options::new("quirks.ch8".to_string());
+        options.platform = Some(Platform::Superchip);
+        settle("quirks.ch8", &known, info.as_ref(), &mut options);
+        assert_eq!(options.speed, Some(Platform::Superchip.tick_rate()));
+        assert_eq!(options.quirks, Some(Platform::Superchip.quirks()));
+
+        let mut options = Options::new("other.ch8".to_string());
+        settle("other.ch8", b"abc", None, &mut options);
+        assert_eq!(options.speed, Some(12));
+        assert_eq!(options.platform, Some(Platform::Chip48));
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(Config::parse("[default]\nspeeed = 12").is_err());
+        assert!(Config::parse("[default]\nstack-depth = 0").is_err());
+    }
+}
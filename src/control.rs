@@ -0,0 +1,439 @@
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    display::Display,
+    emulator::{Backend, Emulator, SaveState, StackDepth, StackStorage, VIP_STACK_LIMIT},
+    keyboard::{Key, Keyboard},
+    memory::{MEMORY_SIZE, PROGRAM_OFFSET},
+    quirks::Platform,
+};
+
+/// The instructions run each frame when neither a speed nor a platform is
+/// given, which matches the frontend
+const TICKS_PER_FRAME: usize = 8;
+
+/// A request read from one line of JSON, named by its `command` field
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Load(Load),
+    Press {
+        key: u8,
+    },
+    Release {
+        key: u8,
+    },
+    Keys {
+        state: u16,
+    },
+    Tick {
+        #[serde(default = "one")]
+        count: usize,
+    },
+    Frame {
+        #[serde(default = "one")]
+        count: usize,
+    },
+    Display,
+    Registers,
+    Memory {
+        address: usize,
+        length: usize,
+    },
+    Save,
+    Restore {
+        // Buffering the state for the tag loses its 128 bit integers so it
+        // is read separately
+        state: Value,
+    },
+    Quit,
+}
+
+/// The rom to start and how to run it
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Load {
+    rom: Option<Vec<u8>>,
+    path: Option<PathBuf>,
+    platform: Option<String>,
+    speed: Option<usize>,
+    seed: Option<u64>,
+    backend: Option<String>,
+    stack_depth: Option<StackDepth>,
+    #[serde(default)]
+    stack_in_memory: bool,
+}
+
+/// Returns the count of ticks or frames to run when none is given
+fn one() -> usize {
+    1
+}
+
+/// Drives the emulator for another process with one JSON request and reply
+/// on each line
+///
+/// Every reply has `"ok": true` with any values asked for, or `"ok": false`
+/// with an `error` message. The program keeps running after a fault is
+/// reported so that its state can still be read.
+///
+/// ```text
+/// {"command": "load", "path": "pong.ch8", "seed": 1}
+/// {"command": "press", "key": 5}
+/// {"command": "frame", "count": 60}
+/// {"command": "display"}
+/// ```
+pub struct Session {
+    emulator: Option<Emulator>,
+    display: Display,
+    keyboard: Keyboard,
+    ticks_per_frame: usize,
+    finished: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            emulator: None,
+            display: Display::default(),
+            keyboard: Keyboard::default(),
+            ticks_per_frame: TICKS_PER_FRAME,
+            finished: false,
+        }
+    }
+}
+
+impl Session {
+    /// Returns whether a `quit` request has been handled
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Answers requests from each line of the reader until it ends or a
+    /// `quit` request is handled
+    pub fn serve<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            writeln!(writer, "{}", self.handle(&line))?;
+            writer.flush()?;
+
+            if self.finished {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Answers a single request
+    pub fn handle(&mut self, line: &str) -> Value {
+        let result = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid request: {}", e))
+            .and_then(|request| self.evaluate(request));
+
+        match result {
+            Ok(Value::Object(mut values)) => {
+                values.insert("ok".to_string(), Value::Bool(true));
+                Value::Object(values)
+            }
+            Ok(_) => json!({ "ok": true }),
+            Err(message) => json!({ "ok": false, "error": message }),
+        }
+    }
+
+    /// Carries out a request and returns the values to reply with
+    fn evaluate(&mut self, request: Request) -> Result<Value, String> {
+        match request {
+            Request::Load(load) => self.load(load)?,
+            Request::Press { key } => self.keyboard.press(&Key::try_from(key)?),
+            Request::Release { key } => self.keyboard.release(&Key::try_from(key)?),
+            Request::Keys { state } => self.keyboard.set_state(state),
+            Request::Tick { count } => {
+                let emulator = self.emulator.as_mut().ok_or_else(not_loaded)?;
+                emulator.run(&mut self.display, &self.keyboard, count)?;
+            }
+            Request::Frame { count } => {
+                let emulator = self.emulator.as_mut().ok_or_else(not_loaded)?;
+
+                for _ in 0..count {
+                    emulator.decrement_timers();
+                    emulator.run(&mut self.display, &self.keyboard, self.ticks_per_frame)?;
+                }
+            }
+            Request::Display => {
+                let rows: Vec<String> = self
+                    .display
+                    .rows()
+                    .iter()
+                    .map(|row| format!("{:016x}", row))
+                    .collect();
+
+                return Ok(json!({ "rows": rows }));
+            }
+            Request::Registers => {
+                let emulator = self.emulator.as_ref().ok_or_else(not_loaded)?;
+
+                return Ok(json!({
+                    "v": emulator.registers(),
+                    "i": emulator.i(),
+                    "pc": emulator.program_counter(),
+                    "dt": emulator.delay_timer(),
+                    "st": emulator.sound_timer(),
+                    "sound": emulator.is_sound_playing(),
+                }));
+            }
+            Request::Memory { address, length } => {
+                let emulator = self.emulator.as_ref().ok_or_else(not_loaded)?;
+                let bytes = address
+                    .checked_add(length)
+                    .filter(|&end| end <= MEMORY_SIZE)
+                    .map(|end| &emulator.memory().bytes()[address..end])
+                    .ok_or_else(|| format!("Invalid memory range 0x{:X}+{}", address, length))?;
+
+                return Ok(json!({ "bytes": bytes }));
+            }
+            Request::Save => {
+                let emulator = self.emulator.as_ref().ok_or_else(not_loaded)?;
+                let state = emulator.save_state(&self.display);
+
+                return Ok(json!({ "state": state }));
+            }
+            Request::Restore { state } => {
+                let state: SaveState =
+                    serde_json::from_value(state).map_err(|e| format!("Invalid state: {}", e))?;
+                let emulator = self.emulator.as_mut().ok_or_else(not_loaded)?;
+                emulator.load_state(&state, &mut self.display)?;
+            }
+            Request::Quit => self.finished = true,
+        }
+
+        Ok(Value::Null)
+    }
+
+    /// Starts a rom from the beginning with a blank screen and no keys held
+    fn load(&mut self, load: Load) -> Result<(), String> {
+        let rom = match (load.rom, load.path) {
+            (Some(rom), None) => rom,
+            (None, Some(path)) => std::fs::read(&path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?,
+            _ => return Err("load expects either rom or path".to_string()),
+        };
+        let platform = load
+            .platform
+            .map(|name| name.parse::<Platform>())
+            .transpose()?;
+        let backend = load
+            .backend
+            .map(|name| name.parse::<Backend>())
+            .transpose()?;
+        let mut emulator = Emulator::with_load_address(&rom, PROGRAM_OFFSET)?;
+
+        if let Some(backend) = backend {
+            emulator.set_backend(backend);
+        }
+
+        if let Some(platform) = platform {
+            emulator.set_quirks(platform.quirks());
+        }
+
+        match (load.stack_depth, platform) {
+            (Some(depth), _) => emulator.set_stack_limit(depth.limit()),
+            (None, Some(Platform::OriginalChip8 | Platform::HybridVip)) => {
+                emulator.set_stack_limit(Some(VIP_STACK_LIMIT))
+            }
+            (None, _) => (),
+        }
+
+        if load.stack_in_memory {
+            emulator.set_stack_storage(StackStorage::Memory);
+        }
+
+        if let Some(seed) = load.seed {
+            emulator.set_seed(seed);
+        }
+
+        self.emulator = Some(emulator);
+        self.display = Display::default();
+        self.keyboard = Keyboard::default();
+        self.ticks_per_frame = load
+            .speed
+            .or(platform.map(Platform::tick_rate))
+            .unwrap_or(TICKS_PER_FRAME);
+
+        Ok(())
+    }
+}
+
+/// Returns the error for requests that need a rom before one is loaded
+fn not_loaded() -> String {
+    "No rom is loaded".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufReader, Read},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    /// Waits for a key and then keeps drawing and erasing its digit
+    const ROM: &str = "[96, 7, 240, 10, 240, 41, 208, 21, 18, 6]";
+
+    fn request(session: &mut Session, line: &str) -> Value {
+        session.handle(&line.replace("ROM", ROM))
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut session = Session::default();
+
+        assert_eq!(
+            request(&mut session, r#"{"command": "tick"}"#),
+            json!({ "ok": false, "error": "No rom is loaded" })
+        );
+        assert_eq!(
+            request(
+                &mut session,
+                r#"{"command": "load", "rom": ROM, "seed": 1}"#
+            ),
+            json!({ "ok": true })
+        );
+
+        request(&mut session, r#"{"command": "frame", "count": 2}"#);
+        assert_eq!(
+            request(&mut session, r#"{"command": "registers"}"#)["pc"],
+            0x202
+        );
+
+        request(&mut session, r#"{"command": "press", "key": 5}"#);
+        request(&mut session, r#"{"command": "frame"}"#);
+        request(&mut session, r#"{"command": "release", "key": 5}"#);
+
+        let registers = request(&mut session, r#"{"command": "registers"}"#);
+        assert_eq!(registers["pc"], 0x206);
+        assert_eq!(registers["v"][0], 5);
+        assert_eq!(registers["i"], 0x19);
+
+        let display = request(&mut session, r#"{"command": "display"}"#);
+        assert_eq!(display["rows"][0], "0780000000000000");
+        assert_eq!(display["rows"][1], "0400000000000000");
+
+        assert_eq!(
+            request(
+                &mut session,
+                r#"{"command": "memory", "address": 512, "length": 4}"#
+            ),
+            json!({ "ok": true, "bytes": [96, 7, 240, 10] })
+        );
+        assert_eq!(
+            request(
+                &mut session,
+                r#"{"command": "memory", "address": 4095, "length": 2}"#
+            )["ok"],
+            false
+        );
+        assert_eq!(
+            request(&mut session, r#"{"command": "press", "key": 16}"#)["error"],
+            "Unsupported key 16"
+        );
+        assert!(request(&mut session, r#"{"command": "jump"}"#)["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid request: unknown variant `jump`"));
+    }
+
+    #[test]
+    fn test_load_stack() {
+        // Calls itself forever
+        let rom = "[34, 0]";
+        let mut session = Session::default();
+
+        let line = r#"{"command": "load", "rom": ROM, "stack_depth": 3}"#;
+        session.handle(&line.replace("ROM", rom));
+        request(&mut session, r#"{"command": "tick", "count": 3}"#);
+        assert_eq!(
+            request(&mut session, r#"{"command": "tick"}"#)["error"],
+            "Stack overflow"
+        );
+
+        let line = r#"{"command": "load", "rom": ROM, "platform": "originalChip8",
+            "stack_depth": "unbounded", "stack_in_memory": true}"#;
+        session.handle(&line.replace("ROM", rom));
+        request(&mut session, r#"{"command": "tick", "count": 20}"#);
+        assert_eq!(
+            request(
+                &mut session,
+                r#"{"command": "memory", "address": 3744, "length": 4}"#
+            ),
+            json!({ "ok": true, "bytes": [2, 0, 2, 0] })
+        );
+
+        let line = r#"{"command": "load", "rom": ROM, "stack_depth": 0}"#;
+        assert_eq!(session.handle(&line.replace("ROM", rom))["ok"], false);
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let mut session = Session::default();
+
+        request(
+            &mut session,
+            r#"{"command": "load", "rom": ROM, "backend": "recompiler"}"#,
+        );
+        request(&mut session, r#"{"command": "keys", "state": 32}"#);
+        request(&mut session, r#"{"command": "frame"}"#);
+
+        let saved = request(&mut session, r#"{"command": "save"}"#);
+        let display = request(&mut session, r#"{"command": "display"}"#);
+
+        request(&mut session, r#"{"command": "tick", "count": 3}"#);
+        assert_ne!(request(&mut session, r#"{"command": "display"}"#), display);
+
+        let restore = json!({ "command": "restore", "state": saved["state"] });
+        assert_eq!(session.handle(&restore.to_string()), json!({ "ok": true }));
+        assert_eq!(request(&mut session, r#"{"command": "display"}"#), display);
+    }
+
+    #[test]
+    fn test_serve_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut session = Session::default();
+            session
+                .serve(BufReader::new(stream.try_clone().unwrap()), stream)
+                .unwrap();
+            session.is_finished()
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let requests = format!(
+            "{}\n\n{}\n{}\n",
+            r#"{"command": "load", "rom": ROM}"#.replace("ROM", ROM),
+            r#"{"command": "tick", "count": 1}"#,
+            r#"{"command": "quit"}"#
+        );
+        client.write_all(requests.as_bytes()).unwrap();
+
+        let mut replies = String::new();
+        client.read_to_string(&mut replies).unwrap();
+
+        assert_eq!(replies, "{\"ok\":true}\n".repeat(3));
+        assert!(server.join().unwrap());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{
+    disassembler::{disassemble_annotated, Annotation},
+    memory::MEMORY_SIZE,
+};
+
+const EXECUTED: u8 = 0b001;
+const READ: u8 = 0b010;
+const WRITTEN: u8 = 0b100;
+
+/// The number of addresses on each line of the coverage map
+const MAP_WIDTH: usize = 64;
+
+/// Records how the program used each address in memory
+///
+/// Addresses are marked when an instruction is executed from them, when they
+/// are read as data by sprites or `LD Vx, [I]`, and when the program writes to
+/// them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            flags: vec![0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Coverage {
+    /// Marks both bytes of an instruction as executed
+    pub fn mark_executed(&mut self, address: usize) {
+        self.mark(address..address + 2, EXECUTED);
+    }
+
+    /// Marks the addresses as read as data
+    pub fn mark_read(&mut self, addresses: Range<usize>) {
+        self.mark(addresses, READ);
+    }
+
+    /// Marks the address as written by the program
+    pub fn mark_written(&mut self, address: usize) {
+        self.mark(address..address + 1, WRITTEN);
+    }
+
+    /// Returns whether an instruction was executed from the address
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.has(address, EXECUTED)
+    }
+
+    /// Returns whether the address was read as data
+    pub fn is_read(&self, address: usize) -> bool {
+        self.has(address, READ)
+    }
+
+    /// Returns whether the program wrote to the address
+    pub fn is_written(&self, address: usize) -> bool {
+        self.has(address, WRITTEN)
+    }
+
+    /// Writes a map of the addresses with one character for each one, where
+    /// `x` was executed, `r` was read as data, `w` was only written and `.`
+    /// was never used
+    ///
+    /// ```text
+    /// 0x200 xxxxxxxxxxxxxxxxxxxxxxxxrrrrr...........................
+    /// ```
+    pub fn write_map<W: Write>(&self, mut writer: W, addresses: Range<usize>) -> io::Result<()> {
+        let addresses = addresses.start.min(MEMORY_SIZE)..addresses.end.min(MEMORY_SIZE);
+        let count = |flag| addresses.clone().filter(|&a| self.has(a, flag)).count();
+
+        writeln!(
+            writer,
+            "{} executed, {} read and {} written of {} bytes",
+            count(EXECUTED),
+            count(READ),
+            count(WRITTEN),
+            addresses.len()
+        )?;
+
+        for start in addresses.clone().step_by(MAP_WIDTH) {
+            let row: String = (start..(start + MAP_WIDTH).min(addresses.end))
+                .map(|address| self.symbol(address))
+                .collect();
+
+            writeln!(writer, "0x{:03X} {}", start, row)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lists the instructions in a rom with a comment saying how each word
+    /// was used, writing words that were only read as data with `DB`
+    pub fn annotate(&self, rom: &[u8], load_address: usize) -> String {
+        disassemble_annotated(rom, load_address, |address| {
+            let addresses = address..address + 2;
+            let any = |flag| addresses.clone().any(|a| self.has(a, flag));
+
+            let mut uses = vec![];
+
+            if any(EXECUTED) {
+                uses.push("executed");
+            }
+
+            if any(READ) {
+                uses.push("read");
+            }
+
+            if any(WRITTEN) {
+                uses.push("written");
+            }
+
+            Annotation {
+                data: any(READ) && !any(EXECUTED),
+                comment: if uses.is_empty() {
+                    Some("unreached".to_string())
+                } else {
+                    Some(uses.join(", "))
+                },
+                ..Annotation::default()
+            }
+        })
+    }
+
+    /// Returns the character for an address in the map
+    fn symbol(&self, address: usize) -> char {
+        if self.has(address, EXECUTED) {
+            'x'
+        } else if self.has(address, READ) {
+            'r'
+        } else if self.has(address, WRITTEN) {
+            'w'
+        } else {
+            '.'
+        }
+    }
+
+    /// Sets a flag on every address in the range inside memory
+    fn mark(&mut self, addresses: Range<usize>, flag: u8) {
+        let end = addresses.end.min(MEMORY_SIZE);
+
+        for address in addresses.start.min(end)..end {
+            self.flags[address] |= flag;
+        }
+    }
+
+    /// Returns whether the flag is set on an address
+    fn has(&self, address: usize, flag: u8) -> bool {
+        self.flags
+            .get(address)
+            .is_some_and(|&flags| flags & flag != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{display::Display, emulator::Emulator, keyboard::Keyboard};
+
+    /// Runs a rom which draws a sprite, stores V0 after it and never reaches
+    /// its last instruction
+    fn run() -> (Vec<u8>, Coverage) {
+        let rom = vec![
+            0xA2, 0x0A, 0xD0, 0x01, 0xF0, 0x55, 0x12, 0x06, 0x00, 0xE0, 0xFF, 0x00,
+        ];
+        let mut emulator = Emulator::new(&rom).unwrap();
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        emulator.enable_coverage();
+        emulator.run(&mut display, &keyboard, 10).unwrap();
+
+        (rom, emulator.coverage().unwrap().clone())
+    }
+
+    #[test]
+    fn test_write_map() {
+        let (_, coverage) = run();
+        let mut map = vec![];
+
+        coverage.write_map(&mut map, 0x200..0x210).unwrap();
+
+        assert_eq!(
+            String::from_utf8(map).unwrap(),
+            "8 executed, 1 read and 1 written of 16 bytes\n0x200 xxxxxxxx..r.....\n"
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let (rom, coverage) = run();
+
+        assert_eq!(
+            coverage.annotate(&rom, 0x200),
+            "    LD I, 0x20A         ; 0x200 A20A executed\n    \
+             DRW V0, V0, 1       ; 0x202 D001 executed\n    \
+             LD [I], V0          ; 0x204 F055 executed\n    \
+             JP 0x206            ; 0x206 1206 executed\n    \
+             CLS                 ; 0x208 00E0 unreached\n    \
+             DB 0xFF, 0x00       ; 0x20A FF00 read, written\n"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+
+use serde::Deserialize;
+
+use crate::{
+    font::FontSet,
+    quirks::{Platform, Quirks},
+};
+
+const HASHES: &str = include_str!("../database/sha1-hashes.json");
+const PROGRAMS: &str = include_str!("../database/programs.json");
+
+/// A program entry in the community chip-8-database `programs.json`
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+/// The settings for one release of a program, keyed by its SHA-1 hash
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    tickrate: Option<usize>,
+    start_address: Option<usize>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    font_style: Option<String>,
+    colors: Option<Colors>,
+}
+
+/// Quirks which differ from the defaults of a platform for a single rom
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    /// Replaces the quirks that have been overridden
+    fn apply(&self, quirks: Quirks) -> Quirks {
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory_increment_by_x: self
+                .memory_increment_by_x
+                .unwrap_or(quirks.memory_increment_by_x),
+            memory_leave_i_unchanged: self
+                .memory_leave_i_unchanged
+                .unwrap_or(quirks.memory_leave_i_unchanged),
+            wrap: self.wrap.unwrap_or(quirks.wrap),
+            jump: self.jump.unwrap_or(quirks.jump),
+            vblank: self.vblank.unwrap_or(quirks.vblank),
+            logic: self.logic.unwrap_or(quirks.logic),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// The recommended settings for a known rom
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The first platform listed for the rom which is supported
+    pub platform: Option<Platform>,
+    /// The quirks of the platform with any overrides for this rom
+    pub quirks: Option<Quirks>,
+    /// The number of instructions to evaluate each frame
+    pub tick_rate: Option<usize>,
+    pub start_address: Option<usize>,
+    /// Chip-8 keys for the named controls such as `up`, `down` or `a`
+    pub keys: HashMap<String, u8>,
+    pub font: Option<FontSet>,
+    /// Colours for each pixel value starting with the background
+    pub colors: Vec<[u8; 3]>,
+}
+
+/// Looks up roms by their hash in a database using the same format as the
+/// community chip-8-database
+///
+/// The database bundled into the emulator is read from the `database`
+/// directory at compile time and parsed the first time it is used. It holds
+/// the programs in `tests` so that they are recognised out of the box, while
+/// the full community database can be read from a copy on disk with `load`.
+pub struct Database {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+}
+
+impl Database {
+    /// Returns the database bundled into the emulator
+    pub fn bundled() -> &'static Self {
+        static BUNDLED: OnceLock<Database> = OnceLock::new();
+
+        BUNDLED
+            .get_or_init(|| Self::from_json(HASHES, PROGRAMS).expect("Bundled database is invalid"))
+    }
+
+    /// Reads `sha1-hashes.json` and `programs.json` from a directory, such as
+    /// the `database` directory of a checkout of the community database
+    pub fn load(directory: &Path) -> Result<Self, String> {
+        let read = |name: &str| {
+            let path = directory.join(name);
+            fs::read_to_string(&path)
+                .map_err(|e| format!("Unable to read database {}: {}", path.display(), e))
+        };
+
+        Self::from_json(&read("sha1-hashes.json")?, &read("programs.json")?)
+    }
+
+    /// Parses the contents of `sha1-hashes.json` and `programs.json`
+    pub fn from_json(hashes: &str, programs: &str) -> Result<Self, String> {
+        let hashes = serde_json::from_str(hashes).map_err(|e| format!("Invalid hashes: {}", e))?;
+        let programs =
+            serde_json::from_str(programs).map_err(|e| format!("Invalid programs: {}", e))?;
+
+        Ok(Self { hashes, programs })
+    }
+
+    /// Returns the recommended settings for the rom if it is known
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        let hash = hash(rom);
+        let program = self.programs.get(*self.hashes.get(&hash)?)?;
+        let rom = program.roms.get(&hash)?;
+
+        let platform = rom
+            .platforms
+            .iter()
+            .find_map(|platform| platform.parse::<Platform>().ok());
+        let quirks = platform.map(|platform| {
+            rom.quirky_platforms
+                .get(&platform.to_string())
+                .unwrap_or(&QuirkOverrides::default())
+                .apply(platform.quirks())
+        });
+        let colors = rom
+            .colors
+            .iter()
+            .flat_map(|colors| &colors.pixels)
+            .filter_map(|color| parse_color(color))
+            .collect();
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform,
+            quirks,
+            tick_rate: rom.tickrate,
+            start_address: rom.start_address,
+            keys: rom.keys.clone(),
+            font: rom.font_style.as_ref().and_then(|font| font.parse().ok()),
+            colors,
+        })
+    }
+}
+
+/// Returns the SHA-1 hash of the rom as lowercase hex
+pub fn hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// Parses a colour written as `#rrggbb`
+pub fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &[u8] = b"abc";
+    const ROM_HASH: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(ROM), ROM_HASH);
+    }
+
+    #[test]
+    fn test_bundled() {
+        let programs = [
+            (
+                include_str!("../tests/conformance/opcodes.asm"),
+                "Opcode test",
+            ),
+            (include_str!("../tests/conformance/flags.asm"), "Flags test"),
+            (
+                include_str!("../tests/conformance/quirks.asm"),
+                "Quirks test",
+            ),
+            (
+                include_str!("../tests/conformance/keypad.asm"),
+                "Keypad test",
+            ),
+            (include_str!("../tests/traces/digits.asm"), "Digits"),
+            (include_str!("../tests/traces/keys.asm"), "Keys"),
+        ];
+
+        for (source, title) in programs {
+            let rom = crate::assembler::assemble(source).unwrap();
+            let info = Database::bundled().lookup(&rom).unwrap();
+
+            assert_eq!(info.title, title);
+            assert_eq!(info.tick_rate, Some(8));
+        }
+
+        assert!(std::ptr::eq(Database::bundled(), Database::bundled()));
+    }
+
+    #[test]
+    fn test_load() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("database");
+        let rom = crate::assembler::assemble(include_str!("../tests/traces/digits.asm")).unwrap();
+
+        let database = Database::load(&directory).unwrap();
+        assert_eq!(database.lookup(&rom).unwrap().title, "Digits");
+
+        let error = Database::load(&directory.join("missing")).err().unwrap();
+        assert!(error.starts_with("Unable to read database"));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let hashes = format!(r#"{{ "{}": 1 }}"#, ROM_HASH);
+        let programs = format!(
+            r##"[
+                {{ "title": "Other", "roms": {{}} }},
+                {{
+                    "title": "Example",
+                    "authors": ["Someone"],
+                    "release": "2020",
+                    "roms": {{
+                        "{}": {{
+                            "file": "example.ch8",
+                            "platforms": ["megachip8", "originalChip8"],
+                            "quirkyPlatforms": {{ "originalChip8": {{ "shift": true }} }},
+                            "tickrate": 15,
+                            "keys": {{ "up": 5, "a": 6 }},
+                            "fontStyle": "vip",
+                            "colors": {{ "pixels": ["#000000", "#ff8000"] }}
+                        }}
+                    }}
+                }}
+            ]"##,
+            ROM_HASH
+        );
+        let database = Database::from_json(&hashes, &programs).unwrap();
+
+        let info = database.lookup(ROM).unwrap();
+
+        assert_eq!(info.title, "Example");
+        assert_eq!(info.authors, vec!["Someone"]);
+        assert_eq!(info.platform, Some(Platform::OriginalChip8));
+        assert_eq!(
+            info.quirks,
+            Some(Quirks {
+                shift: true,
+                ..Platform::OriginalChip8.quirks()
+            })
+        );
+        assert_eq!(info.tick_rate, Some(15));
+        assert_eq!(info.start_address, None);
+        assert_eq!(info.keys["up"], 5);
+        assert_eq!(info.font, Some(FontSet::Vip));
+        assert_eq!(info.colors, vec![[0x00, 0x00, 0x00], [0xFF, 0x80, 0x00]]);
+    }
+
+    #[test]
+    fn test_lookup_unknown() {
+        let database = Database::from_json("{}", "[]").unwrap();
+
+        assert_eq!(database.lookup(ROM), None);
+    }
+}
@@ -0,0 +1,117 @@
+use std::convert::TryFrom;
+
+use crate::instruction::Instruction;
+
+/// What is known about a word of a rom from running or analysing it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotation {
+    /// A name for the address written before the line and used in place of
+    /// the address in operands
+    pub label: Option<String>,
+    /// The word is data rather than an instruction and is written with `DB`
+    pub data: bool,
+    /// Text added to the comment after the address and opcode
+    pub comment: Option<String>,
+}
+
+/// Lists the instructions in a rom loaded at the given address
+///
+/// Every line can be read back by the assembler, with the address and opcode
+/// kept in a comment. Words which are not valid instructions are written as
+/// data with `DW` and a trailing odd byte with `DB`.
+pub fn disassemble(rom: &[u8], load_address: usize) -> String {
+    disassemble_annotated(rom, load_address, |_| Annotation::default())
+}
+
+/// Lists the instructions in a rom like `disassemble` using what is known
+/// about the word at each address
+pub fn disassemble_annotated<F>(rom: &[u8], load_address: usize, annotate: F) -> String
+where
+    F: Fn(usize) -> Annotation,
+{
+    let mut lines = vec![];
+
+    for (index, chunk) in rom.chunks(2).enumerate() {
+        let address = load_address + index * 2;
+        let annotation = annotate(address);
+
+        let (text, raw) = match *chunk {
+            [byte_1, byte_2] if annotation.data => (
+                format!("DB 0x{:02X}, 0x{:02X}", byte_1, byte_2),
+                format!("{:02X}{:02X}", byte_1, byte_2),
+            ),
+            [byte_1, byte_2] => {
+                let opcode = (byte_1 as u16) << 8 | byte_2 as u16;
+
+                let text = match Instruction::try_from(opcode) {
+                    Ok(instruction) => with_label(instruction, &annotate),
+                    Err(_) => format!("DW 0x{:04X}", opcode),
+                };
+
+                (text, format!("{:04X}", opcode))
+            }
+            [byte] => (format!("DB 0x{:02X}", byte), format!("{:02X}", byte)),
+            _ => unreachable!(),
+        };
+
+        let comment = match annotation.comment {
+            Some(comment) => format!("0x{:03X} {} {}", address, raw, comment),
+            None => format!("0x{:03X} {}", address, raw),
+        };
+
+        if let Some(label) = annotation.label {
+            lines.push(format!("{}:\n", label));
+        }
+
+        lines.push(format!("    {:<20}; {}\n", text, comment));
+    }
+
+    lines.concat()
+}
+
+/// Writes the instruction with the label of the address it refers to, if
+/// there is one
+fn with_label<F>(instruction: Instruction, annotate: &F) -> String
+where
+    F: Fn(usize) -> Annotation,
+{
+    let text = instruction.to_string();
+
+    let addr = match instruction {
+        Instruction::Jp { addr }
+        | Instruction::Call { addr }
+        | Instruction::LdI { addr }
+        | Instruction::JpV { addr } => addr,
+        _ => return text,
+    };
+
+    match annotate(addr).label {
+        Some(label) => text.replace(&format!("0x{:03X}", addr), &label),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::assemble, memory::PROGRAM_OFFSET};
+
+    #[test]
+    fn test_disassemble() {
+        let listing = disassemble(&[0x00, 0xE0, 0xF9, 0x99, 0xAB], PROGRAM_OFFSET);
+
+        assert_eq!(
+            listing,
+            "    CLS                 ; 0x200 00E0\n    \
+             DW 0xF999           ; 0x202 F999\n    \
+             DB 0xAB             ; 0x204 AB\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rom: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(assemble(&disassemble(&rom, PROGRAM_OFFSET)), Ok(rom));
+    }
+}
@@ -1,9 +1,17 @@
+use std::io::{self, Write};
+
 pub const HEIGHT: usize = 32;
 pub const WIDTH: usize = 64;
 
 /// Stores the current active state of every pixel on the Chip-8 display
+///
+/// Each row is packed into a single u64 with the leftmost pixel in the most
+/// significant bit, so that a sprite row can be drawn with one XOR. Rows that
+/// have changed since the last call to `mark_clean` are tracked so that
+/// frontends can skip redrawing an unchanged screen.
 pub struct Display {
-    pixels: [bool; WIDTH * HEIGHT],
+    rows: [u64; HEIGHT],
+    dirty_rows: u32,
 }
 
 impl Default for Display {
@@ -15,23 +23,58 @@ impl Default for Display {
 impl Display {
     /// Creates a new display with some pixels already active
     pub fn new(active: &[(usize, usize)]) -> Self {
-        let mut pixels = [false; WIDTH * HEIGHT];
+        let mut rows = [0; HEIGHT];
 
-        for (x, y) in active {
-            pixels[x + y * WIDTH] = true;
+        for &(x, y) in active {
+            rows[y] |= 1 << (WIDTH - 1 - x);
         }
 
-        Self { pixels }
+        Self {
+            rows,
+            dirty_rows: u32::MAX,
+        }
     }
 
     /// Resets the display to a blank screen
     pub fn clear(&mut self) {
-        self.pixels = [false; WIDTH * HEIGHT];
+        for (y, row) in self.rows.iter_mut().enumerate() {
+            if *row != 0 {
+                *row = 0;
+                self.dirty_rows |= 1 << y;
+            }
+        }
     }
 
     /// Returns whether the current pixel is active
     pub fn get_pixel(&self, x: usize, y: usize) -> bool {
-        self.pixels[x + y * WIDTH]
+        self.rows[y] >> (WIDTH - 1 - x) & 1 == 1
+    }
+
+    /// Returns every row of the screen from the top with the leftmost pixel
+    /// in the most significant bit
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    /// Replaces every row of the screen, such as when loading a saved state
+    pub fn set_rows(&mut self, rows: &[u64; HEIGHT]) {
+        self.rows = *rows;
+        self.dirty_rows = u32::MAX;
+    }
+
+    /// Returns whether any pixels have changed since the last `mark_clean`
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_rows != 0
+    }
+
+    /// Returns whether the given row has changed since the last `mark_clean`
+    pub fn is_row_dirty(&self, y: usize) -> bool {
+        self.dirty_rows >> y & 1 == 1
+    }
+
+    /// Forgets about any changes once the screen has been redrawn
+    pub fn mark_clean(&mut self) {
+        self.dirty_rows = 0;
     }
 
     /// Flips bits to draw a sprite on the screen
@@ -48,23 +91,270 @@ impl Display {
     pub fn xor_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
         let mut erased = false;
 
-        for (j, row) in sprite.iter().enumerate() {
-            let sprite = row.reverse_bits();
+        for (j, &byte) in sprite.iter().enumerate() {
+            let y = (y % HEIGHT + j) % HEIGHT;
+            let bits = ((byte as u64) << (WIDTH - 8)).rotate_right((x % WIDTH) as u32);
+
+            erased |= self.xor_row(y, bits);
+        }
+
+        erased
+    }
+
+    /// Flips bits to draw a sprite on the screen without wrapping
+    ///
+    /// This behaves like `xor_sprite` except that although the starting
+    /// coordinates wrap, any part of the sprite past the right or bottom edges
+    /// of the screen is not drawn.
+    pub fn xor_sprite_clipped(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let x = x % WIDTH;
+        let y = y % HEIGHT;
+        let mut erased = false;
 
-            for i in 0..8 {
-                let x = (x + i) % WIDTH;
-                let y = (y + j) % HEIGHT;
-                let bit = (sprite >> i) % 2 == 1;
+        for (j, &byte) in sprite.iter().take(HEIGHT - y).enumerate() {
+            let bits = ((byte as u64) << (WIDTH - 8)) >> x;
 
-                let before = self.pixels[x + y * WIDTH];
-                self.pixels[x + y * WIDTH] ^= bit;
+            erased |= self.xor_row(y + j, bits);
+        }
 
-                if before && bit {
-                    erased = true;
+        erased
+    }
+
+    /// Flips the bits of one row and returns whether any pixels were erased
+    fn xor_row(&mut self, y: usize, bits: u64) -> bool {
+        let erased = self.rows[y] & bits != 0;
+        self.rows[y] ^= bits;
+
+        if bits != 0 {
+            self.dirty_rows |= 1 << y;
+        }
+
+        erased
+    }
+
+    /// Reads a screen written by `to_text`
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = text.lines().collect();
+
+        if lines.len() != HEIGHT {
+            return Err(format!(
+                "Expected {} rows but found {}",
+                HEIGHT,
+                lines.len()
+            ));
+        }
+
+        let mut pixels = vec![];
+
+        for (number, line) in lines.iter().enumerate().map(|(i, line)| (i + 1, line)) {
+            let width = line.chars().count();
+
+            if width != WIDTH {
+                return Err(format!(
+                    "Expected {} pixels on line {} but found {}: {}",
+                    WIDTH, number, width, line
+                ));
+            }
+
+            for pixel in line.chars() {
+                match pixel {
+                    '#' => pixels.push(true),
+                    '.' => pixels.push(false),
+                    _ => {
+                        return Err(format!(
+                            "Unexpected pixel {} on line {}: {}",
+                            pixel, number, line
+                        ))
+                    }
                 }
             }
         }
 
-        erased
+        Self::from_pixels(&pixels)
+    }
+
+    /// Reads a plain PBM image of the same size as the screen
+    pub fn from_pbm(text: &str) -> Result<Self, String> {
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(str::split_whitespace);
+
+        if tokens.next() != Some("P1") {
+            return Err("Expected a plain PBM image".to_string());
+        }
+
+        let size = (tokens.next(), tokens.next());
+
+        if size != (Some(&WIDTH.to_string()[..]), Some(&HEIGHT.to_string()[..])) {
+            return Err(format!("Expected a {}x{} image", WIDTH, HEIGHT));
+        }
+
+        let pixels: Vec<bool> = tokens
+            .flat_map(str::chars)
+            .map(|pixel| match pixel {
+                '1' => Ok(true),
+                '0' => Ok(false),
+                _ => Err(format!("Unexpected pixel {}", pixel)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Self::from_pixels(&pixels)
+    }
+
+    /// Creates a display from every pixel row by row
+    fn from_pixels(pixels: &[bool]) -> Result<Self, String> {
+        if pixels.len() != WIDTH * HEIGHT {
+            return Err(format!(
+                "Expected {} pixels but found {}",
+                WIDTH * HEIGHT,
+                pixels.len()
+            ));
+        }
+
+        let active: Vec<(usize, usize)> = pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, &pixel)| pixel)
+            .map(|(index, _)| (index % WIDTH, index / WIDTH))
+            .collect();
+
+        Ok(Self::new(&active))
+    }
+
+    /// Returns the screen as text with a line for every row where `#` is an
+    /// active pixel and `.` an inactive one
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((WIDTH + 1) * HEIGHT);
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                text.push(if self.get_pixel(x, y) { '#' } else { '.' });
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Writes the screen as a plain PBM image with one character per pixel
+    pub fn write_pbm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "P1")?;
+        writeln!(writer, "{} {}", WIDTH, HEIGHT)?;
+
+        for y in 0..HEIGHT {
+            let row: Vec<&str> = (0..WIDTH)
+                .map(|x| if self.get_pixel(x, y) { "1" } else { "0" })
+                .collect();
+            writeln!(writer, "{}", row.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xor_sprite_wraps() {
+        let mut display = Display::default();
+
+        let erased = display.xor_sprite(WIDTH + 60, HEIGHT - 1, &[0b10000001, 0b11000000]);
+
+        assert!(!erased);
+        assert!(display.get_pixel(60, HEIGHT - 1));
+        assert!(display.get_pixel(3, HEIGHT - 1));
+        assert!(display.get_pixel(60, 0));
+        assert!(display.get_pixel(61, 0));
+        assert_eq!(display.rows()[HEIGHT - 1].count_ones(), 2);
+        assert_eq!(display.rows()[0].count_ones(), 2);
+    }
+
+    #[test]
+    fn test_xor_sprite_clipped() {
+        let mut display = Display::default();
+
+        display.xor_sprite_clipped(WIDTH + 60, HEIGHT - 1, &[0b10000001, 0b11000000]);
+
+        assert!(display.get_pixel(60, HEIGHT - 1));
+        assert!(!display.get_pixel(3, HEIGHT - 1));
+        assert_eq!(display.rows()[HEIGHT - 1].count_ones(), 1);
+        assert_eq!(display.rows()[0].count_ones(), 0);
+    }
+
+    #[test]
+    fn test_xor_sprite_erases() {
+        let mut display = Display::new(&[(5, 5)]);
+
+        assert!(!display.xor_sprite(0, 5, &[0b00001000]));
+        assert!(display.xor_sprite(0, 5, &[0b00000100]));
+        assert!(!display.get_pixel(5, 5));
+        assert!(display.get_pixel(4, 5));
+    }
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut display = Display::default();
+        assert!(display.is_dirty());
+
+        display.mark_clean();
+        display.xor_sprite(0, 3, &[0b00000000, 0b10000000]);
+
+        assert!(!display.is_row_dirty(3));
+        assert!(display.is_row_dirty(4));
+
+        display.mark_clean();
+        display.clear();
+
+        assert!(display.is_row_dirty(4));
+        assert!(!display.is_row_dirty(5));
+
+        display.mark_clean();
+        display.clear();
+
+        assert!(!display.is_dirty());
+    }
+
+    #[test]
+    fn test_text_and_pbm_round_trip() {
+        let display = Display::new(&[(0, 0), (63, 31), (10, 5)]);
+
+        let text = display.to_text();
+        assert!(text.starts_with("#....."));
+        assert_eq!(Display::from_text(&text).unwrap().rows(), display.rows());
+
+        let mut pbm = vec![];
+        display.write_pbm(&mut pbm).unwrap();
+        let pbm = String::from_utf8(pbm).unwrap();
+        assert_eq!(Display::from_pbm(&pbm).unwrap().rows(), display.rows());
+
+        assert!(Display::from_text("#.").is_err());
+    }
+
+    #[test]
+    fn test_from_text_checks_every_line() {
+        let text = Display::default().to_text();
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        let error = |lines: &[String]| Display::from_text(&lines.join("\n")).err().unwrap();
+
+        // Moving a pixel between lines keeps the total count the same
+        lines[2].pop();
+        lines[3].push('#');
+        assert_eq!(
+            error(&lines),
+            format!("Expected 64 pixels on line 3 but found 63: {}", lines[2])
+        );
+
+        lines[2].push('x');
+        lines[3].pop();
+        assert_eq!(
+            error(&lines),
+            format!("Unexpected pixel x on line 3: {}", lines[2])
+        );
+
+        assert_eq!(error(&lines[..31]), "Expected 32 rows but found 31");
     }
 }
@@ -1,14 +1,187 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, fmt, str::FromStr};
 
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{de, Deserialize, Deserializer};
 
+use self::recompiler::Recompiler;
 use crate::{
+    coverage::Coverage,
     display::Display,
+    font::Font,
     instruction::Instruction,
-    keyboard::Keyboard,
-    memory::{Memory, PROGRAM_OFFSET},
+    keyboard::{Key, Keyboard},
+    memory::{LoadError, Memory, PROGRAM_OFFSET, STACK_OFFSET, STACK_SIZE},
+    quirks::Quirks,
 };
 
+mod recompiler;
+mod state;
+
+pub use self::state::SaveState;
+
+/// The number of return addresses the original COSMAC VIP had room for
+pub const VIP_STACK_LIMIT: usize = 12;
+
+/// The number of return addresses most later interpreters allow
+pub const DEFAULT_STACK_LIMIT: usize = 16;
+
+/// An error which stops the emulator from evaluating the program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The instruction at the program counter could not be parsed
+    InvalidInstruction(String),
+    /// A subroutine was called when the stack was already full
+    StackOverflow,
+    /// A subroutine returned when the stack was empty
+    StackUnderflow,
+    /// The program wrote to the interpreter area of memory
+    ProtectedWrite(usize),
+    /// The program accessed an address past the end of memory
+    InvalidAddress(usize),
+    /// The program checked a key that is not on the hex keypad
+    InvalidKey(u8),
+    /// The program asked for the sprite of a value that is not a hex digit
+    InvalidDigit(u8),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::InvalidInstruction(message) => write!(f, "{}", message),
+            Fault::StackOverflow => write!(f, "Stack overflow"),
+            Fault::StackUnderflow => write!(f, "No subroutine to return from"),
+            Fault::ProtectedWrite(offset) => {
+                write!(f, "Write to protected address 0x{:03X}", offset)
+            }
+            Fault::InvalidAddress(offset) => write!(f, "Invalid address 0x{:X}", offset),
+            Fault::InvalidKey(value) => write!(f, "Unsupported key 0x{:X}", value),
+            Fault::InvalidDigit(value) => write!(f, "No sprite for digit 0x{:X}", value),
+        }
+    }
+}
+
+impl From<String> for Fault {
+    fn from(message: String) -> Self {
+        Fault::InvalidInstruction(message)
+    }
+}
+
+impl From<Fault> for String {
+    fn from(fault: Fault) -> Self {
+        fault.to_string()
+    }
+}
+
+/// Where the return addresses of called subroutines are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackStorage {
+    /// Outside of the emulated memory where programs cannot see them
+    Host,
+    /// In emulated memory from `STACK_OFFSET` like the COSMAC VIP
+    Memory,
+}
+
+/// How many nested subroutines a program may call, as written on the command
+/// line and in config files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackDepth {
+    /// Overflow after this many return addresses
+    Limited(usize),
+    /// Only limited by where the return addresses are kept
+    Unbounded,
+}
+
+impl StackDepth {
+    /// Returns the limit to give to `Emulator::set_stack_limit`
+    pub fn limit(self) -> Option<usize> {
+        match self {
+            StackDepth::Limited(limit) => Some(limit),
+            StackDepth::Unbounded => None,
+        }
+    }
+}
+
+impl FromStr for StackDepth {
+    type Err = String;
+
+    /// Parses a positive number of return addresses such as `12` or `16`, or
+    /// `unbounded`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "unbounded" => Ok(StackDepth::Unbounded),
+            _ => match value.parse() {
+                Ok(limit) if limit > 0 => Ok(StackDepth::Limited(limit)),
+                _ => Err(format!("Unknown stack depth {}", value)),
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StackDepth {
+    /// Reads either a number or a string such as `"unbounded"`
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Depth {
+            Limited(u64),
+            Named(String),
+        }
+
+        match Depth::deserialize(deserializer)? {
+            Depth::Limited(limit) => limit.to_string().parse(),
+            Depth::Named(name) => name.parse(),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+/// The strategy used to execute instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Decodes and evaluates one instruction at a time
+    Interpreter,
+    /// Translates basic blocks into chains of closures and reuses them
+    Recompiler,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "interpreter" => Ok(Backend::Interpreter),
+            "recompiler" => Ok(Backend::Recompiler),
+            _ => Err(format!("Unknown backend {}", value)),
+        }
+    }
+}
+
+/// What happens when a program writes to the interpreter area of memory
+/// below 0x200, or below the load address if that is lower
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteProtection {
+    /// The write is allowed like on the original interpreters
+    Off,
+    /// The write is allowed and reported on stderr
+    Log,
+    /// The write is refused and stops the emulator
+    Fault,
+}
+
+impl FromStr for WriteProtection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(WriteProtection::Off),
+            "log" => Ok(WriteProtection::Log),
+            "fault" => Ok(WriteProtection::Fault),
+            _ => Err(format!("Unknown write protection {}", value)),
+        }
+    }
+}
+
 pub struct Emulator {
     memory: Memory,
     registers: [u8; 16],
@@ -16,46 +189,232 @@ pub struct Emulator {
     delay_timer: u8,
     sound_timer: u8,
     program_counter: usize,
+    load_address: usize,
+    write_protection: WriteProtection,
     stack: Vec<usize>,
+    stack_pointer: usize,
+    stack_limit: Option<usize>,
+    stack_storage: StackStorage,
+    quirks: Quirks,
+    vblank: bool,
+    rng: ChaCha12Rng,
+    recompiler: Option<Recompiler>,
+    coverage: Option<Coverage>,
 }
 
 impl Emulator {
     /// Loads a rom into memory and initialise the emulator
-    pub fn new(rom: &[u8]) -> Self {
-        let memory = Memory::new(rom);
+    pub fn new(rom: &[u8]) -> Result<Self, LoadError> {
+        Self::with_load_address(rom, PROGRAM_OFFSET)
+    }
 
-        Self {
+    /// Loads a rom into memory at the given address, such as 0x600 for the
+    /// ETI-660, and starts the program from there
+    pub fn with_load_address(rom: &[u8], load_address: usize) -> Result<Self, LoadError> {
+        let memory = Memory::new(rom, load_address)?;
+
+        Ok(Self {
             memory,
             registers: [0; 16],
             i: 0,
             delay_timer: 0,
             sound_timer: 0,
-            program_counter: PROGRAM_OFFSET,
+            program_counter: load_address,
+            load_address,
+            write_protection: WriteProtection::Off,
             stack: vec![],
+            stack_pointer: 0,
+            stack_limit: Some(DEFAULT_STACK_LIMIT),
+            stack_storage: StackStorage::Host,
+            quirks: Quirks::default(),
+            vblank: true,
+            rng: ChaCha12Rng::from_entropy(),
+            recompiler: None,
+            coverage: None,
+        })
+    }
+
+    /// Sets which of the behaviours that differ between interpreters to use
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Seeds the random numbers for 0xCxkk so that runs can be repeated
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+    }
+
+    /// Replaces the sprites used for the hex digits
+    pub fn set_font(&mut self, font: &Font) -> Result<(), LoadError> {
+        self.memory.load_font(font)
+    }
+
+    /// Sets how writes to the interpreter area below the load address are
+    /// handled
+    pub fn set_write_protection(&mut self, protection: WriteProtection) {
+        self.write_protection = protection;
+    }
+
+    /// Sets how many nested subroutines can be called before a stack
+    /// overflow, or `None` to only be limited by the available storage
+    pub fn set_stack_limit(&mut self, limit: Option<usize>) {
+        self.stack_limit = limit;
+    }
+
+    /// Sets where return addresses are kept, which should be chosen before
+    /// any subroutines are called
+    pub fn set_stack_storage(&mut self, storage: StackStorage) {
+        self.stack_storage = storage;
+    }
+
+    /// Reuses decoded instructions instead of parsing them on every tick
+    pub fn enable_decode_cache(&mut self) {
+        self.memory.enable_decode_cache();
+    }
+
+    /// Starts recording which addresses are executed, read and written
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(Coverage::default());
+        }
+    }
+
+    /// Returns the addresses used so far if coverage is enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Switches the strategy used to execute instructions
+    pub fn set_backend(&mut self, backend: Backend) {
+        match backend {
+            Backend::Interpreter => {
+                self.memory.disable_write_log();
+                self.recompiler = None;
+            }
+            Backend::Recompiler if self.recompiler.is_none() => {
+                self.memory.enable_write_log();
+                self.recompiler = Some(Recompiler::default());
+            }
+            Backend::Recompiler => {}
         }
     }
 
     /// Ticks down 1/60th of a second on the delay and sound timers
+    ///
+    /// This also marks the vertical blank that sprites wait for when the
+    /// vblank quirk is enabled.
     pub fn decrement_timers(&mut self) {
+        self.vblank = true;
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 
+    /// Returns the general purpose registers V0 to VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// Returns the address register I
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// Returns the address of the next instruction
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Returns the value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns the emulated memory
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Returns the general purpose registers V0 to VF to be changed by a
+    /// debugger
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+
+    /// Replaces the address register I
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    /// Moves the program counter so that the next instruction is read from
+    /// the given address
+    pub fn set_program_counter(&mut self, address: usize) {
+        self.program_counter = address;
+    }
+
+    /// Replaces the value of the delay timer
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// Replaces the value of the sound timer
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Returns the emulated memory to be changed by a debugger, bypassing
+    /// write protection and coverage
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
     /// Returns true if the Chip-8 buzzer is active
     pub fn is_sound_playing(&self) -> bool {
         0 < self.sound_timer
     }
 
     /// Evaluates one CPU instruction and updates the program counter
-    pub fn tick(&mut self, display: &mut Display, keyboard: &Keyboard) -> Result<(), String> {
-        let bytes = self.memory.get_instruction(self.program_counter);
-        let instruction = Instruction::try_from(bytes)?;
+    pub fn tick(&mut self, display: &mut Display, keyboard: &Keyboard) -> Result<(), Fault> {
+        self.run(display, keyboard, 1)
+    }
+
+    /// Evaluates the given number of CPU instructions with the chosen backend
+    pub fn run(
+        &mut self,
+        display: &mut Display,
+        keyboard: &Keyboard,
+        ticks: usize,
+    ) -> Result<(), Fault> {
+        if let Some(mut recompiler) = self.recompiler.take() {
+            let result = recompiler.run(self, display, keyboard, ticks);
+            self.recompiler = Some(recompiler);
+            return result;
+        }
+
+        for _ in 0..ticks {
+            self.interpret(display, keyboard)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes and evaluates the instruction at the program counter
+    fn interpret(&mut self, display: &mut Display, keyboard: &Keyboard) -> Result<(), Fault> {
+        let instruction = self.memory.decode_instruction(self.program_counter)?;
+
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.mark_executed(self.program_counter);
+        }
 
         self.program_counter = match instruction {
             Instruction::Cls => self.cls(display),
-            Instruction::Ret => self.ret(),
+            Instruction::Ret => self.ret()?,
             Instruction::Jp { addr } => self.jp(addr),
-            Instruction::Call { addr } => self.call(addr),
+            Instruction::Call { addr } => self.call(addr)?,
             Instruction::SeV { vx, byte } => self.se_v(vx, byte),
             Instruction::SneV { vx, byte } => self.sne_v(vx, byte),
             Instruction::SeVV { vx, vy } => self.se_v_v(vx, vy),
@@ -67,25 +426,25 @@ impl Emulator {
             Instruction::XorVV { vx, vy } => self.xor_v_v(vx, vy),
             Instruction::AddVV { vx, vy } => self.add_v_v(vx, vy),
             Instruction::SubVV { vx, vy } => self.sub_v_v(vx, vy),
-            Instruction::ShrVV { vx, .. } => self.shr_v_v(vx),
+            Instruction::ShrVV { vx, vy } => self.shr_v_v(vx, vy),
             Instruction::SubnVV { vx, vy } => self.subn_v_v(vx, vy),
-            Instruction::ShlVV { vx, .. } => self.shl_v_v(vx),
+            Instruction::ShlVV { vx, vy } => self.shl_v_v(vx, vy),
             Instruction::SneVV { vx, vy } => self.sne_v_v(vx, vy),
             Instruction::LdI { addr } => self.ld_i(addr),
             Instruction::JpV { addr } => self.jp_v(addr),
             Instruction::RndV { vx, byte } => self.rnd_v(vx, byte),
-            Instruction::Drw { vx, vy, n } => self.drw(vx, vy, n, display),
-            Instruction::SkpV { vx } => self.skp_v(vx, keyboard),
-            Instruction::SknpV { vx } => self.sknp_v(vx, keyboard),
+            Instruction::Drw { vx, vy, n } => self.drw(vx, vy, n, display)?,
+            Instruction::SkpV { vx } => self.skp_v(vx, keyboard)?,
+            Instruction::SknpV { vx } => self.sknp_v(vx, keyboard)?,
             Instruction::LdVDt { vx } => self.ld_v_dt(vx),
             Instruction::LdVK { vx } => self.ld_v_k(vx, keyboard),
             Instruction::LdDtV { vx } => self.ld_dt_v(vx),
             Instruction::LdStV { vx } => self.ld_st_v(vx),
             Instruction::AddIV { vx } => self.add_i_v(vx),
-            Instruction::LdFV { vx } => self.ld_f_v(vx),
-            Instruction::LdBV { vx } => self.ld_b_v(vx),
-            Instruction::LdIV { vx } => self.ld_i_v(vx),
-            Instruction::LdVI { vx } => self.ld_v_i(vx),
+            Instruction::LdFV { vx } => self.ld_f_v(vx)?,
+            Instruction::LdBV { vx } => self.ld_b_v(vx)?,
+            Instruction::LdIV { vx } => self.ld_i_v(vx)?,
+            Instruction::LdVI { vx } => self.ld_v_i(vx)?,
         };
 
         Ok(())
@@ -97,9 +456,83 @@ impl Emulator {
         self.program_counter + 2
     }
 
+    /// Returns the number of subroutines that have not yet returned
+    pub fn stack_depth(&self) -> usize {
+        match self.stack_storage {
+            StackStorage::Host => self.stack.len(),
+            StackStorage::Memory => self.stack_pointer,
+        }
+    }
+
+    /// Replaces a byte of memory on behalf of the program
+    fn write(&mut self, offset: usize, byte: u8) -> Result<(), Fault> {
+        if offset < PROGRAM_OFFSET.min(self.load_address) {
+            match self.write_protection {
+                WriteProtection::Off => {}
+                WriteProtection::Log => eprintln!(
+                    "{} from 0x{:03X}",
+                    Fault::ProtectedWrite(offset),
+                    self.program_counter
+                ),
+                WriteProtection::Fault => return Err(Fault::ProtectedWrite(offset)),
+            }
+        }
+
+        self.memory.set_byte(offset, byte)?;
+
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.mark_written(offset);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of return addresses the stack can hold
+    fn stack_capacity(&self) -> usize {
+        let capacity = match self.stack_storage {
+            StackStorage::Host => usize::MAX,
+            StackStorage::Memory => STACK_SIZE / 2,
+        };
+
+        self.stack_limit.unwrap_or(capacity).min(capacity)
+    }
+
+    /// Saves a return address on the stack
+    fn push(&mut self, addr: usize) -> Result<(), Fault> {
+        if self.stack_capacity() <= self.stack_depth() {
+            return Err(Fault::StackOverflow);
+        }
+
+        match self.stack_storage {
+            StackStorage::Host => self.stack.push(addr),
+            StackStorage::Memory => {
+                let offset = STACK_OFFSET + self.stack_pointer * 2;
+                self.memory.set_byte(offset, (addr >> 8) as u8)?;
+                self.memory.set_byte(offset + 1, addr as u8)?;
+                self.stack_pointer += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes the most recent return address from the stack
+    fn pop(&mut self) -> Result<usize, Fault> {
+        match self.stack_storage {
+            StackStorage::Host => self.stack.pop().ok_or(Fault::StackUnderflow),
+            StackStorage::Memory if self.stack_pointer == 0 => Err(Fault::StackUnderflow),
+            StackStorage::Memory => {
+                self.stack_pointer -= 1;
+                let offset = STACK_OFFSET + self.stack_pointer * 2;
+                let addr = self.memory.get_instruction(offset)? as usize;
+                Ok(addr)
+            }
+        }
+    }
+
     /// Returns from a subroutine
-    fn ret(&mut self) -> usize {
-        self.stack.pop().expect("No subroutine to return from") + 2
+    fn ret(&mut self) -> Result<usize, Fault> {
+        Ok(self.pop()? + 2)
     }
 
     /// Jumps the program counter to nnn (0x1nnn)
@@ -108,9 +541,9 @@ impl Emulator {
     }
 
     /// Calls subroutine at nnn (0x2nnn)
-    fn call(&mut self, addr: usize) -> usize {
-        self.stack.push(self.program_counter);
-        addr
+    fn call(&mut self, addr: usize) -> Result<usize, Fault> {
+        self.push(self.program_counter)?;
+        Ok(addr)
     }
 
     /// Skips an instruction if Vx == kk (0x3xkk)
@@ -161,18 +594,33 @@ impl Emulator {
     /// Stores bitwise OR of Vx and Vy to Vx (0x8xy1)
     fn or_v_v(&mut self, vx: usize, vy: usize) -> usize {
         self.registers[vx] |= self.registers[vy];
+
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+
         self.program_counter + 2
     }
 
     /// Stores bitwise AND of Vx and Vy in Vx (0x8xy2)
     fn and_v_v(&mut self, vx: usize, vy: usize) -> usize {
         self.registers[vx] &= self.registers[vy];
+
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+
         self.program_counter + 2
     }
 
     /// Stores bitwise XOR of Vx and Vy in Vx (0x8xy3)
     fn xor_v_v(&mut self, vx: usize, vy: usize) -> usize {
         self.registers[vx] ^= self.registers[vy];
+
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+
         self.program_counter + 2
     }
 
@@ -180,8 +628,8 @@ impl Emulator {
     fn add_v_v(&mut self, vx: usize, vy: usize) -> usize {
         let (value, carry) = self.registers[vx].overflowing_add(self.registers[vy]);
 
-        self.registers[0xF] = carry as u8;
         self.registers[vx] = value;
+        self.registers[0xF] = carry as u8;
         self.program_counter + 2
     }
 
@@ -189,15 +637,17 @@ impl Emulator {
     fn sub_v_v(&mut self, vx: usize, vy: usize) -> usize {
         let (value, carry) = self.registers[vx].overflowing_sub(self.registers[vy]);
 
-        self.registers[0xF] = !carry as u8;
         self.registers[vx] = value;
+        self.registers[0xF] = !carry as u8;
         self.program_counter + 2
     }
 
     /// Shifts Vx to the right with carry (0x8xy6)
-    fn shr_v_v(&mut self, vx: usize) -> usize {
-        self.registers[0xF] = self.registers[vx] % 2;
-        self.registers[vx] >>= 1;
+    fn shr_v_v(&mut self, vx: usize, vy: usize) -> usize {
+        let value = self.registers[if self.quirks.shift { vx } else { vy }];
+
+        self.registers[vx] = value >> 1;
+        self.registers[0xF] = value % 2;
         self.program_counter + 2
     }
 
@@ -205,15 +655,17 @@ impl Emulator {
     fn subn_v_v(&mut self, vx: usize, vy: usize) -> usize {
         let (value, carry) = self.registers[vy].overflowing_sub(self.registers[vx]);
 
-        self.registers[0xF] = !carry as u8;
         self.registers[vx] = value;
+        self.registers[0xF] = !carry as u8;
         self.program_counter + 2
     }
 
     /// Shifts Vx to the left with carry (0x8xyE)
-    fn shl_v_v(&mut self, vx: usize) -> usize {
-        self.registers[0xF] = (0b10000000 <= self.registers[vx]) as u8;
-        self.registers[vx] <<= 1;
+    fn shl_v_v(&mut self, vx: usize, vy: usize) -> usize {
+        let value = self.registers[if self.quirks.shift { vx } else { vy }];
+
+        self.registers[vx] = value << 1;
+        self.registers[0xF] = (0b10000000 <= value) as u8;
         self.program_counter + 2
     }
 
@@ -234,43 +686,75 @@ impl Emulator {
 
     /// Jumps program counter to nnn + V0 (0xBnnn)
     fn jp_v(&self, addr: usize) -> usize {
-        addr + self.registers[0x0] as usize
+        let vx = if self.quirks.jump { addr >> 8 } else { 0x0 };
+
+        addr + self.registers[vx] as usize
     }
 
     /// Randomly generates a random number to store in Vx (0xCxkk)
     fn rnd_v(&mut self, vx: usize, byte: u8) -> usize {
-        self.registers[vx] = rand::thread_rng().gen::<u8>() & byte;
+        self.registers[vx] = self.rng.gen::<u8>() & byte;
         self.program_counter + 2
     }
 
     /// Draws n-byte sprite from Vi at Vx, Vy (0xDxyn)
-    fn drw(&mut self, vx: usize, vy: usize, n: usize, display: &mut Display) -> usize {
+    fn drw(
+        &mut self,
+        vx: usize,
+        vy: usize,
+        n: usize,
+        display: &mut Display,
+    ) -> Result<usize, Fault> {
+        if self.quirks.vblank {
+            if !self.vblank {
+                return Ok(self.program_counter);
+            }
+
+            self.vblank = false;
+        }
+
         let offset = self.i as usize;
         let x = self.registers[vx] as usize;
         let y = self.registers[vy] as usize;
 
-        let sprite = self.memory.get_sprite(offset, n);
-        let erased = display.xor_sprite(x, y, sprite);
+        let sprite = self.memory.get_sprite(offset, n)?;
+
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.mark_read(offset..offset + n);
+        }
+
+        let erased = if self.quirks.wrap {
+            display.xor_sprite(x, y, sprite)
+        } else {
+            display.xor_sprite_clipped(x, y, sprite)
+        };
 
         self.registers[0xF] = erased as u8;
-        self.program_counter + 2
+        Ok(self.program_counter + 2)
+    }
+
+    /// Returns the key named by the value of Vx
+    fn key(&self, vx: usize) -> Result<Key, Fault> {
+        let value = self.registers[vx];
+
+        Key::try_from(value).map_err(|_| Fault::InvalidKey(value))
     }
 
     /// Skips the next instruction if Vx is pressed (0xEx9E)
-    fn skp_v(&self, vx: usize, keyboard: &Keyboard) -> usize {
-        if keyboard.is_pressed(&self.registers[vx].into()) {
-            self.program_counter + 4
+    fn skp_v(&self, vx: usize, keyboard: &Keyboard) -> Result<usize, Fault> {
+        if keyboard.is_pressed(&self.key(vx)?) {
+            Ok(self.program_counter + 4)
         } else {
-            self.program_counter + 2
+            Ok(self.program_counter + 2)
         }
     }
 
     /// Skips the next instruction if Vx is not pressed (0xExA1)
-    fn sknp_v(&self, vx: usize, keyboard: &Keyboard) -> usize {
-        if keyboard.is_pressed(&self.registers[vx].into()) {
-            self.program_counter + 2
+    fn sknp_v(&self, vx: usize, keyboard: &Keyboard) -> Result<usize, Fault> {
+        if keyboard.is_pressed(&self.key(vx)?) {
+            Ok(self.program_counter + 2)
         } else {
-            self.program_counter + 4
+            Ok(self.program_counter + 4)
         }
     }
 
@@ -311,634 +795,1063 @@ impl Emulator {
     }
 
     /// Sets Vi to the location of sprite Vx (0xFx29)
-    fn ld_f_v(&mut self, vx: usize) -> usize {
+    fn ld_f_v(&mut self, vx: usize) -> Result<usize, Fault> {
         let value = self.registers[vx];
+        let offset = self
+            .memory
+            .calculate_digit_offset(value)
+            .ok_or(Fault::InvalidDigit(value))?;
 
-        self.i = Memory::calculate_digit_offset(value) as u16;
-        self.program_counter + 2
+        self.i = offset as u16;
+        Ok(self.program_counter + 2)
     }
 
     /// Store BCD representation of Vx in memory (0xFx33)
-    fn ld_b_v(&mut self, vx: usize) -> usize {
+    fn ld_b_v(&mut self, vx: usize) -> Result<usize, Fault> {
         let x = self.registers[vx];
 
         for index in 0..3 {
-            let offset = (self.i + index) as usize;
+            let offset = self.i as usize + index;
             let digit = (x / 10u8.pow(2 - index as u32)) % 10;
-            self.memory.set_byte(offset, digit);
+            self.write(offset, digit)?;
         }
 
-        self.program_counter + 2
+        Ok(self.program_counter + 2)
     }
 
     /// Loads [V0, Vx] to memory starting at Vi (0xFx55)
-    fn ld_i_v(&mut self, vx: usize) -> usize {
+    fn ld_i_v(&mut self, vx: usize) -> Result<usize, Fault> {
         for index in 0..=vx {
-            self.memory
-                .set_byte(self.i as usize + index, self.registers[index])
+            self.write(self.i as usize + index, self.registers[index])?;
         }
 
-        self.program_counter + 2
+        self.increment_i_after_transfer(vx);
+        Ok(self.program_counter + 2)
     }
 
     /// Loads memory starting at Vi to [V0, Vx] (0xFx65)
-    fn ld_v_i(&mut self, vx: usize) -> usize {
+    fn ld_v_i(&mut self, vx: usize) -> Result<usize, Fault> {
         for index in 0..=vx {
-            self.registers[index] = self.memory.get_byte(self.i as usize + index);
+            self.registers[index] = self.memory.get_byte(self.i as usize + index)?;
         }
 
-        self.program_counter + 2
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.mark_read(self.i as usize..self.i as usize + vx + 1);
+        }
+
+        self.increment_i_after_transfer(vx);
+        Ok(self.program_counter + 2)
+    }
+
+    /// Moves Vi past the registers stored or loaded depending on the quirks
+    fn increment_i_after_transfer(&mut self, vx: usize) {
+        if !self.quirks.memory_leave_i_unchanged {
+            let amount = if self.quirks.memory_increment_by_x {
+                vx
+            } else {
+                vx + 1
+            };
+
+            self.i = self.i.wrapping_add(amount as u16);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::keyboard::Key;
+    use crate::{keyboard::Key, quirks::Platform, snapshot::assert_snapshot};
+
+    /// The backends which every test that runs instructions is repeated on
+    const BACKENDS: [Backend; 2] = [Backend::Interpreter, Backend::Recompiler];
+
+    /// Creates an emulator with the rom at 0x200 running on the backend
+    fn emulator(backend: Backend, rom: &[u8]) -> Result<Emulator, LoadError> {
+        emulator_at(backend, rom, PROGRAM_OFFSET)
+    }
+
+    /// Creates an emulator with the rom at the address running on the backend
+    fn emulator_at(
+        backend: Backend,
+        rom: &[u8],
+        load_address: usize,
+    ) -> Result<Emulator, LoadError> {
+        let mut emulator = Emulator::with_load_address(rom, load_address)?;
+        emulator.set_backend(backend);
+        Ok(emulator)
+    }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_cls() {
-        let mut emulator = Emulator::new(&[0x00, 0xE0]);
-        let mut display = Display::new(&[(0, 0)]);
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x00, 0xE0]).unwrap();
+            let mut display = Display::new(&[(0, 0)]);
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(display.get_pixel(0, 0), false);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(display.get_pixel(0, 0), false);
+        }
     }
 
     #[test]
     fn test_ret() {
-        let mut emulator = Emulator::new(&[0x00, 0xEE]);
-        emulator.stack.push(0x400);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x00, 0xEE]).unwrap();
+            emulator.stack.push(0x400);
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x402);
+            assert_eq!(emulator.stack, Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn test_ret_empty_stack() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x00, 0xEE]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            let result = emulator.tick(&mut display, &keyboard);
 
-        assert_eq!(emulator.program_counter, 0x402);
-        assert_eq!(emulator.stack, vec![]);
+            assert_eq!(result, Err(Fault::StackUnderflow));
+            assert_eq!(emulator.program_counter, 0x200);
+        }
     }
 
     #[test]
     fn test_jp() {
-        let mut emulator = Emulator::new(&[0x12, 0x34]);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x12, 0x34]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x234);
-        assert_eq!(emulator.stack, vec![]);
+            assert_eq!(emulator.program_counter, 0x234);
+            assert_eq!(emulator.stack, Vec::<usize>::new());
+        }
     }
 
     #[test]
     fn test_call() {
-        let mut emulator = Emulator::new(&[0x23, 0x45]);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x23, 0x45]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x345);
+            assert_eq!(emulator.stack, vec![0x200]);
+        }
+    }
+
+    #[test]
+    fn test_call_stack_overflow() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x22, 0x00]).unwrap();
+            emulator.set_stack_limit(Some(VIP_STACK_LIMIT));
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.run(&mut display, &keyboard, 12).unwrap();
+            let result = emulator.tick(&mut display, &keyboard);
+
+            assert_eq!(result, Err(Fault::StackOverflow));
+            assert_eq!(emulator.stack.len(), 12);
+        }
+    }
+
+    #[test]
+    fn test_call_unbounded_stack() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x22, 0x00]).unwrap();
+            emulator.set_stack_limit(None);
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.run(&mut display, &keyboard, 1000).unwrap();
+
+            assert_eq!(emulator.stack.len(), 1000);
+        }
+    }
+
+    #[test]
+    fn test_call_and_ret_memory_stack() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE]).unwrap();
+            emulator.set_stack_storage(StackStorage::Memory);
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.stack, Vec::<usize>::new());
+            assert_eq!(emulator.memory.get_byte(STACK_OFFSET), Ok(0x02));
+            assert_eq!(emulator.memory.get_byte(STACK_OFFSET + 1), Ok(0x00));
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.memory.set_byte(STACK_OFFSET + 1, 0x10).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x345);
-        assert_eq!(emulator.stack, vec![0x200]);
+            assert_eq!(emulator.program_counter, 0x212);
+            assert_eq!(emulator.stack_pointer, 0);
+        }
     }
 
     #[test]
     fn test_se_v_equal() {
-        let mut emulator = Emulator::new(&[0x34, 0x56]);
-        emulator.registers[0x4] = 0x56;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x34, 0x56]).unwrap();
+            emulator.registers[0x4] = 0x56;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_se_v_not_equal() {
-        let mut emulator = Emulator::new(&[0x34, 0x56]);
-        emulator.registers[0x4] = 0x65;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x34, 0x56]).unwrap();
+            emulator.registers[0x4] = 0x65;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_sne_v_equal() {
-        let mut emulator = Emulator::new(&[0x45, 0x67]);
-        emulator.registers[0x5] = 0x67;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x45, 0x67]).unwrap();
+            emulator.registers[0x5] = 0x67;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_sne_v_not_equal() {
-        let mut emulator = Emulator::new(&[0x45, 0x67]);
-        emulator.registers[0x5] = 0x76;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x45, 0x67]).unwrap();
+            emulator.registers[0x5] = 0x76;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_se_v_v_equal() {
-        let mut emulator = Emulator::new(&[0x56, 0x70]);
-        emulator.registers[0x6] = 0x78;
-        emulator.registers[0x7] = 0x78;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x56, 0x70]).unwrap();
+            emulator.registers[0x6] = 0x78;
+            emulator.registers[0x7] = 0x78;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_se_v_v_not_equal() {
-        let mut emulator = Emulator::new(&[0x56, 0x70]);
-        emulator.registers[0x6] = 0x78;
-        emulator.registers[0x7] = 0x89;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x56, 0x70]).unwrap();
+            emulator.registers[0x6] = 0x78;
+            emulator.registers[0x7] = 0x89;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_ld_v() {
-        let mut emulator = Emulator::new(&[0x67, 0x89]);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x67, 0x89]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x7], 0x89);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x7], 0x89);
+        }
     }
 
     #[test]
     fn test_add_v() {
-        let mut emulator = Emulator::new(&[0x78, 0x9A, 0x78, 0x9A]);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x78, 0x9A, 0x78, 0x9A]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x8], 0x9A);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x8], 0x9A);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x8], 0x34);
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x8], 0x34);
+        }
     }
 
     #[test]
     fn test_ld_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA0]);
-        emulator.registers[0x9] = 0x20;
-        emulator.registers[0xA] = 0x40;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA0]).unwrap();
+            emulator.registers[0x9] = 0x20;
+            emulator.registers[0xA] = 0x40;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0x40);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0x40);
+        }
     }
 
     #[test]
     fn test_or_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA1]);
-        emulator.registers[0x9] = 0b11110000;
-        emulator.registers[0xA] = 0b11001100;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA1]).unwrap();
+            emulator.registers[0x9] = 0b11110000;
+            emulator.registers[0xA] = 0b11001100;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0b11111100);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0b11111100);
+        }
     }
 
     #[test]
     fn test_and_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA2]);
-        emulator.registers[0x9] = 0b11110000;
-        emulator.registers[0xA] = 0b11001100;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA2]).unwrap();
+            emulator.registers[0x9] = 0b11110000;
+            emulator.registers[0xA] = 0b11001100;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0b11000000);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0b11000000);
+        }
     }
 
     #[test]
     fn test_xor_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA3]);
-        emulator.registers[0x9] = 0b11110000;
-        emulator.registers[0xA] = 0b11001100;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA3]).unwrap();
+            emulator.registers[0x9] = 0b11110000;
+            emulator.registers[0xA] = 0b11001100;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0b00111100);
+        }
+    }
+
+    #[test]
+    fn test_or_v_v_logic_quirk() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA1]).unwrap();
+            emulator.set_quirks(Platform::OriginalChip8.quirks());
+            emulator.registers[0xF] = 0x1;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0b00111100);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
     }
 
     #[test]
     fn test_add_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA4, 0x89, 0xA4]);
-        emulator.registers[0x9] = 0x78;
-        emulator.registers[0xA] = 0x78;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA4, 0x89, 0xA4]).unwrap();
+            emulator.registers[0x9] = 0x78;
+            emulator.registers[0xA] = 0x78;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0xF0);
+            assert_eq!(emulator.registers[0xF], 0);
+
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x9], 0x68);
+            assert_eq!(emulator.registers[0xF], 1);
+        }
+    }
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0xF0);
-        assert_eq!(emulator.registers[0xF], 0);
+    #[test]
+    fn test_add_v_v_flag_register() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x8F, 0xA4]).unwrap();
+            emulator.registers[0xF] = 0x10;
+            emulator.registers[0xA] = 0x20;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x9], 0x68);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
     }
 
     #[test]
     fn test_sub_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA5, 0x89, 0xA5]);
-        emulator.registers[0x9] = 0x78;
-        emulator.registers[0xA] = 0x78;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA5, 0x89, 0xA5]).unwrap();
+            emulator.registers[0x9] = 0x78;
+            emulator.registers[0xA] = 0x78;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0x0);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0x0);
+            assert_eq!(emulator.registers[0xF], 1);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x9], 0x88);
-        assert_eq!(emulator.registers[0xF], 0);
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x9], 0x88);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
     }
 
     #[test]
     fn test_shr_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA6, 0x89, 0xA6]);
-        emulator.registers[0x9] = 0b00000101;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA6, 0x89, 0xA6]).unwrap();
+            emulator.registers[0x9] = 0b00000101;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0b00000010);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0b00000010);
+            assert_eq!(emulator.registers[0xF], 1);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x9], 0b00000001);
-        assert_eq!(emulator.registers[0xF], 0);
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x9], 0b00000001);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn test_shr_v_v_shift_quirk_off() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA6]).unwrap();
+            emulator.set_quirks(Platform::OriginalChip8.quirks());
+            emulator.registers[0x9] = 0b11111111;
+            emulator.registers[0xA] = 0b00000101;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.registers[0x9], 0b00000010);
+            assert_eq!(emulator.registers[0xF], 1);
+        }
     }
 
     #[test]
     fn test_subn_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xA7, 0x89, 0xA7]);
-        emulator.registers[0x9] = 0x78;
-        emulator.registers[0xA] = 0x78;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xA7, 0x89, 0xA7]).unwrap();
+            emulator.registers[0x9] = 0x78;
+            emulator.registers[0xA] = 0x78;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0x0);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0x0);
+            assert_eq!(emulator.registers[0xF], 1);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x9], 0x78);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x9], 0x78);
+            assert_eq!(emulator.registers[0xF], 1);
+        }
     }
 
     #[test]
     fn test_shl_v_v() {
-        let mut emulator = Emulator::new(&[0x89, 0xAE, 0x89, 0xAE]);
-        emulator.registers[0x9] = 0b10100000;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x89, 0xAE, 0x89, 0xAE]).unwrap();
+            emulator.registers[0x9] = 0b10100000;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x9], 0b01000000);
-        assert_eq!(emulator.registers[0xF], 1);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x9], 0b01000000);
+            assert_eq!(emulator.registers[0xF], 1);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
-        assert_eq!(emulator.registers[0x9], 0b10000000);
-        assert_eq!(emulator.registers[0xF], 0);
+            assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.registers[0x9], 0b10000000);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
     }
 
     #[test]
     fn test_sne_v_v_not_equal() {
-        let mut emulator = Emulator::new(&[0x9A, 0xB0]);
-        emulator.registers[0xA] = 0xC;
-        emulator.registers[0xB] = 0xD;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x9A, 0xB0]).unwrap();
+            emulator.registers[0xA] = 0xC;
+            emulator.registers[0xB] = 0xD;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_sne_v_v_equal() {
-        let mut emulator = Emulator::new(&[0x9A, 0xB0]);
-        emulator.registers[0xA] = 0xC;
-        emulator.registers[0xB] = 0xC;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x9A, 0xB0]).unwrap();
+            emulator.registers[0xA] = 0xC;
+            emulator.registers[0xB] = 0xC;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_ld_i() {
-        let mut emulator = Emulator::new(&[0xAB, 0xCD]);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xAB, 0xCD]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.i, 0xBCD);
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.i, 0xBCD);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_jp_v() {
-        let mut emulator = Emulator::new(&[0xBC, 0xDE]);
-        emulator.registers[0x0] = 0x1;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xBC, 0xDE]).unwrap();
+            emulator.registers[0x0] = 0x1;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0xCDF);
-        assert_eq!(emulator.stack, vec![]);
+            assert_eq!(emulator.program_counter, 0xCDF);
+            assert_eq!(emulator.stack, Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn test_jp_v_jump_quirk() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xBC, 0xDE]).unwrap();
+            emulator.set_quirks(Platform::Superchip.quirks());
+            emulator.registers[0x0] = 0x1;
+            emulator.registers[0xC] = 0x2;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0xCE0);
+        }
+    }
+
+    #[test]
+    fn test_drw_vblank_quirk() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xD0, 0x01, 0xD0, 0x01]).unwrap();
+            emulator.set_quirks(Platform::OriginalChip8.quirks());
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.run(&mut display, &keyboard, 3).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x202);
+
+            emulator.decrement_timers();
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x204);
+        }
+    }
+
+    #[test]
+    fn test_drw_wrap_quirk_off() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xDA, 0xB1]).unwrap();
+            emulator.set_quirks(Platform::ModernChip8.quirks());
+            emulator.i = 0x400;
+            emulator.memory.set_byte(0x400, 0b11110000).unwrap();
+            emulator.registers[0xA] = 0x3E;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert!(display.get_pixel(0x3F, 0x0));
+            assert!(!display.get_pixel(0x0, 0x0));
+        }
     }
 
     #[test]
     fn test_drw() {
-        let mut emulator = Emulator::new(&[0xDA, 0xB2]);
-        emulator.i = 0x400;
-        emulator.memory.set_byte(0x400, 0b11110000);
-        emulator.memory.set_byte(0x401, 0b11001100);
-        emulator.registers[0xA] = 0x3E;
-        emulator.registers[0xB] = 0x2;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
-
-        emulator.tick(&mut display, &keyboard).unwrap();
-
-        assert_eq!(display.get_pixel(0x3E, 0x2), true);
-        assert_eq!(display.get_pixel(0x3F, 0x2), true);
-        assert_eq!(display.get_pixel(0x0, 0x2), true);
-        assert_eq!(display.get_pixel(0x1, 0x2), true);
-        assert_eq!(display.get_pixel(0x2, 0x2), false);
-        assert_eq!(display.get_pixel(0x3, 0x2), false);
-        assert_eq!(display.get_pixel(0x4, 0x2), false);
-        assert_eq!(display.get_pixel(0x5, 0x2), false);
-
-        assert_eq!(display.get_pixel(0x3E, 0x3), true);
-        assert_eq!(display.get_pixel(0x3F, 0x3), true);
-        assert_eq!(display.get_pixel(0x0, 0x3), false);
-        assert_eq!(display.get_pixel(0x1, 0x3), false);
-        assert_eq!(display.get_pixel(0x2, 0x3), true);
-        assert_eq!(display.get_pixel(0x3, 0x3), true);
-        assert_eq!(display.get_pixel(0x4, 0x3), false);
-        assert_eq!(display.get_pixel(0x5, 0x3), false);
-
-        assert_eq!(emulator.program_counter, 0x202);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xDA, 0xB2]).unwrap();
+            emulator.i = 0x400;
+            emulator.memory.set_byte(0x400, 0b11110000).unwrap();
+            emulator.memory.set_byte(0x401, 0b11001100).unwrap();
+            emulator.registers[0xA] = 0x3E;
+            emulator.registers[0xB] = 0x2;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_snapshot(
+                &display,
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/drw.txt"),
+            );
+
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_skp_v_pressed() {
-        let mut emulator = Emulator::new(&[0xE0, 0x9E]);
-        emulator.registers[0x0] = 0x5;
-        let mut display = Display::default();
-        let mut keyboard = Keyboard::default();
-        keyboard.press(&Key::Num5);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xE0, 0x9E]).unwrap();
+            emulator.registers[0x0] = 0x5;
+            let mut display = Display::default();
+            let mut keyboard = Keyboard::default();
+            keyboard.press(&Key::Num5);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_skp_v_not_pressed() {
-        let mut emulator = Emulator::new(&[0xE0, 0x9E]);
-        emulator.registers[0x0] = 0x5;
-        let mut display = Display::default();
-        let mut keyboard = Keyboard::default();
-        keyboard.release(&Key::Num5);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xE0, 0x9E]).unwrap();
+            emulator.registers[0x0] = 0x5;
+            let mut display = Display::default();
+            let mut keyboard = Keyboard::default();
+            keyboard.release(&Key::Num5);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_sknp_v_pressed() {
-        let mut emulator = Emulator::new(&[0xE0, 0xA1]);
-        emulator.registers[0x0] = 0x5;
-        let mut display = Display::default();
-        let mut keyboard = Keyboard::default();
-        keyboard.press(&Key::Num5);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xE0, 0xA1]).unwrap();
+            emulator.registers[0x0] = 0x5;
+            let mut display = Display::default();
+            let mut keyboard = Keyboard::default();
+            keyboard.press(&Key::Num5);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_sknp_v_not_pressed() {
-        let mut emulator = Emulator::new(&[0xE0, 0xA1]);
-        emulator.registers[0x0] = 0x5;
-        let mut display = Display::default();
-        let mut keyboard = Keyboard::default();
-        keyboard.release(&Key::Num5);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xE0, 0xA1]).unwrap();
+            emulator.registers[0x0] = 0x5;
+            let mut display = Display::default();
+            let mut keyboard = Keyboard::default();
+            keyboard.release(&Key::Num5);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x204);
+            assert_eq!(emulator.program_counter, 0x204);
+        }
     }
 
     #[test]
     fn test_ld_v_dt() {
-        let mut emulator = Emulator::new(&[0xF1, 0x07]);
-        emulator.delay_timer = 0x55;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF1, 0x07]).unwrap();
+            emulator.delay_timer = 0x55;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x1], 0x55);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x1], 0x55);
+        }
     }
 
     #[test]
     fn test_ld_v_k() {
-        let mut emulator = Emulator::new(&[0xF2, 0x0A]);
-        let mut display = Display::default();
-        let mut keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF2, 0x0A]).unwrap();
+            let mut display = Display::default();
+            let mut keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x200);
-        assert_eq!(emulator.registers[0x2], 0x0);
+            assert_eq!(emulator.program_counter, 0x200);
+            assert_eq!(emulator.registers[0x2], 0x0);
 
-        keyboard.press(&Key::Num2);
+            keyboard.press(&Key::Num2);
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0x2], 0x2);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0x2], 0x2);
+        }
     }
 
     #[test]
     fn test_ld_dt_v() {
-        let mut emulator = Emulator::new(&[0xF3, 0x15]);
-        emulator.registers[0x3] = 3;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF3, 0x15]).unwrap();
+            emulator.registers[0x3] = 3;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.delay_timer, 0x3);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.delay_timer, 0x3);
+        }
     }
 
     #[test]
     fn test_ld_st_v() {
-        let mut emulator = Emulator::new(&[0xF4, 0x18]);
-        emulator.registers[0x4] = 4;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF4, 0x18]).unwrap();
+            emulator.registers[0x4] = 4;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.sound_timer, 0x4);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.sound_timer, 0x4);
+        }
     }
 
     #[test]
     fn test_add_i_v() {
-        let mut emulator = Emulator::new(&[0xF5, 0x1E]);
-        emulator.i = 0x9A;
-        emulator.registers[0x5] = 0x9A;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
-
-        emulator.tick(&mut display, &keyboard).unwrap();
-
-        assert_eq!(emulator.i, 0x134);
-        assert_eq!(emulator.program_counter, 0x202);
-        assert_eq!(emulator.registers[0xF], 0);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF5, 0x1E]).unwrap();
+            emulator.i = 0x9A;
+            emulator.registers[0x5] = 0x9A;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.i, 0x134);
+            assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.registers[0xF], 0);
+        }
     }
 
     #[test]
     fn test_ld_f_v() {
-        let mut emulator = Emulator::new(&[0xF6, 0x29]);
-        emulator.registers[0x6] = 0xA;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF6, 0x29]).unwrap();
+            emulator.registers[0x6] = 0xA;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.tick(&mut display, &keyboard).unwrap();
 
-        assert_eq!(emulator.i, 0xA * 5);
-        assert_eq!(emulator.program_counter, 0x202);
+            assert_eq!(emulator.i, 0xA * 5);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
     fn test_ld_b_v() {
-        let mut emulator = Emulator::new(&[0xF7, 0x33]);
-        emulator.i = 0x400;
-        emulator.registers[0x7] = 0x7B;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF7, 0x33]).unwrap();
+            emulator.i = 0x400;
+            emulator.registers[0x7] = 0x7B;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.memory.get_byte(0x400), Ok(0x1));
+            assert_eq!(emulator.memory.get_byte(0x401), Ok(0x2));
+            assert_eq!(emulator.memory.get_byte(0x402), Ok(0x3));
+            assert_eq!(emulator.program_counter, 0x202);
+        }
+    }
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+    #[test]
+    fn test_ld_i_v() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF8, 0x55]).unwrap();
+            emulator.i = 0x400;
+            emulator.registers[0x0] = 0x1;
+            emulator.registers[0x4] = 0x5;
+            emulator.registers[0x8] = 0x9;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.memory.get_byte(0x400), Ok(0x1));
+            assert_eq!(emulator.memory.get_byte(0x404), Ok(0x5));
+            assert_eq!(emulator.memory.get_byte(0x408), Ok(0x9));
+            assert_eq!(emulator.program_counter, 0x202);
+        }
+    }
 
-        assert_eq!(emulator.memory.get_byte(0x400), 0x1);
-        assert_eq!(emulator.memory.get_byte(0x401), 0x2);
-        assert_eq!(emulator.memory.get_byte(0x402), 0x3);
-        assert_eq!(emulator.program_counter, 0x202);
+    #[test]
+    fn test_decode_cache_invalidated_by_write() {
+        for backend in BACKENDS {
+            let mut emulator =
+                emulator(backend, &[0x60, 0x61, 0xA2, 0x00, 0xF0, 0x55, 0x12, 0x00]).unwrap();
+            emulator.enable_decode_cache();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            for _ in 0..5 {
+                emulator.tick(&mut display, &keyboard).unwrap();
+            }
+
+            assert_eq!(emulator.registers[0x1], 0x61);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
     }
 
     #[test]
-    fn test_ld_i_v() {
-        let mut emulator = Emulator::new(&[0xF8, 0x55]);
-        emulator.i = 0x400;
-        emulator.registers[0x0] = 0x1;
-        emulator.registers[0x4] = 0x5;
-        emulator.registers[0x8] = 0x9;
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
+    fn test_ld_i_v_memory_quirks() {
+        for backend in BACKENDS {
+            for &(platform, i) in &[
+                (Platform::OriginalChip8, 0x409),
+                (Platform::Chip48, 0x408),
+                (Platform::Superchip, 0x400),
+            ] {
+                let mut emulator = emulator(backend, &[0xF8, 0x55]).unwrap();
+                emulator.set_quirks(platform.quirks());
+                emulator.i = 0x400;
+                let mut display = Display::default();
+                let keyboard = Keyboard::default();
+
+                emulator.tick(&mut display, &keyboard).unwrap();
+
+                assert_eq!(emulator.i, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ld_i_v_write_protection() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF8, 0x55]).unwrap();
+            emulator.set_write_protection(WriteProtection::Fault);
+            emulator.i = 0x1FC;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            let result = emulator.tick(&mut display, &keyboard);
+
+            assert_eq!(result, Err(Fault::ProtectedWrite(0x1FC)));
+            assert_eq!(emulator.program_counter, 0x200);
+        }
+    }
+
+    #[test]
+    fn test_ld_i_v_write_protection_with_load_address() {
+        for backend in BACKENDS {
+            let mut emulator = emulator_at(backend, &[0xF0, 0x55], 0x600).unwrap();
+            emulator.set_write_protection(WriteProtection::Fault);
+            emulator.i = 0x300;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+            emulator.program_counter = 0x600;
+            emulator.i = 0x1FF;
+
+            let result = emulator.tick(&mut display, &keyboard);
+
+            assert_eq!(result, Err(Fault::ProtectedWrite(0x1FF)));
+        }
+    }
 
-        emulator.tick(&mut display, &keyboard).unwrap();
+    #[test]
+    fn test_with_load_address() {
+        for backend in BACKENDS {
+            let mut emulator = emulator_at(backend, &[0x12, 0x34], 0x600).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
 
-        assert_eq!(emulator.memory.get_byte(0x400), 0x1);
-        assert_eq!(emulator.memory.get_byte(0x404), 0x5);
-        assert_eq!(emulator.memory.get_byte(0x408), 0x9);
-        assert_eq!(emulator.program_counter, 0x202);
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.program_counter, 0x234);
+        }
     }
 
     #[test]
     fn test_ld_v_i() {
-        let mut emulator = Emulator::new(&[0xF8, 0x65]);
-        emulator.i = 0x400;
-        emulator.memory.set_byte(0x400, 0x1);
-        emulator.memory.set_byte(0x404, 0x5);
-        emulator.memory.set_byte(0x408, 0x9);
-        let mut display = Display::default();
-        let keyboard = Keyboard::default();
-
-        emulator.tick(&mut display, &keyboard).unwrap();
-
-        assert_eq!(emulator.registers[0x0], 0x1);
-        assert_eq!(emulator.registers[0x4], 0x5);
-        assert_eq!(emulator.registers[0x8], 0x9);
-        assert_eq!(emulator.program_counter, 0x202);
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF8, 0x65]).unwrap();
+            emulator.i = 0x400;
+            emulator.memory.set_byte(0x400, 0x1).unwrap();
+            emulator.memory.set_byte(0x404, 0x5).unwrap();
+            emulator.memory.set_byte(0x408, 0x9).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            emulator.tick(&mut display, &keyboard).unwrap();
+
+            assert_eq!(emulator.registers[0x0], 0x1);
+            assert_eq!(emulator.registers[0x4], 0x5);
+            assert_eq!(emulator.registers[0x8], 0x9);
+            assert_eq!(emulator.program_counter, 0x202);
+        }
+    }
+
+    #[test]
+    fn test_rnd_v_seed() {
+        for backend in BACKENDS {
+            let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            let mut first = emulator(backend, &rom).unwrap();
+            first.set_seed(42);
+            first.run(&mut display, &keyboard, 2).unwrap();
+
+            let mut second = emulator(backend, &rom).unwrap();
+            second.set_seed(42);
+            second.run(&mut display, &keyboard, 2).unwrap();
+
+            assert_eq!(first.registers(), second.registers());
+        }
+    }
+
+    #[test]
+    fn test_invalid_operands() {
+        for backend in BACKENDS {
+            let cases: [(&[u8], Fault); 6] = [
+                (&[0x6A, 0x10, 0xEA, 0x9E], Fault::InvalidKey(0x10)),
+                (&[0x6A, 0xFF, 0xEA, 0xA1], Fault::InvalidKey(0xFF)),
+                (&[0x6A, 0x10, 0xFA, 0x29], Fault::InvalidDigit(0x10)),
+                (&[0xAF, 0xFF, 0xD0, 0x05], Fault::InvalidAddress(0x1000)),
+                (&[0xAF, 0xFE, 0xF2, 0x55], Fault::InvalidAddress(0x1000)),
+                (&[0xAF, 0xFF, 0xF1, 0x65], Fault::InvalidAddress(0x1000)),
+            ];
+
+            for (rom, fault) in cases.iter() {
+                let mut emulator = emulator(backend, rom).unwrap();
+                let mut display = Display::default();
+                let keyboard = Keyboard::default();
+
+                assert_eq!(emulator.run(&mut display, &keyboard, 2), Err(fault.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_program_counter_past_end_of_memory() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0x1F, 0xFF]).unwrap();
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            let result = emulator.run(&mut display, &keyboard, 2);
+
+            assert_eq!(result, Err(Fault::InvalidAddress(0x1000)));
+        }
+    }
+
+    #[test]
+    fn test_ld_b_v_wrapped_i() {
+        for backend in BACKENDS {
+            let mut emulator = emulator(backend, &[0xF0, 0x33]).unwrap();
+            emulator.i = 0xFFFF;
+            let mut display = Display::default();
+            let keyboard = Keyboard::default();
+
+            let result = emulator.tick(&mut display, &keyboard);
+
+            assert_eq!(result, Err(Fault::InvalidAddress(0xFFFF)));
+        }
     }
 }
@@ -0,0 +1,352 @@
+use super::{Emulator, Fault};
+use crate::{display::Display, instruction::Instruction, keyboard::Keyboard, memory::MEMORY_SIZE};
+
+const MAX_BLOCK_LENGTH: usize = 64;
+
+/// A translated instruction which returns the next program counter
+type Op = Box<dyn Fn(&mut Emulator, &mut Display, &Keyboard) -> Result<usize, Fault> + Send>;
+
+/// A straight run of translated instructions
+///
+/// Every op except the last always continues to the following instruction so
+/// the block can be evaluated without decoding or checking the program
+/// counter in between.
+struct Block {
+    ops: Vec<Op>,
+    end: usize,
+}
+
+/// Translates basic blocks of instructions into closures and caches them by
+/// their start address
+pub(super) struct Recompiler {
+    blocks: Vec<Option<Block>>,
+    coverage: Vec<u16>,
+}
+
+impl Default for Recompiler {
+    fn default() -> Self {
+        Self {
+            blocks: (0..MEMORY_SIZE).map(|_| None).collect(),
+            coverage: vec![0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Recompiler {
+    /// Evaluates the given number of instructions, translating new blocks as
+    /// they are reached
+    ///
+    /// Blocks that overlap any memory written in the meantime are discarded so
+    /// that self-modifying programs are translated again.
+    pub(super) fn run(
+        &mut self,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+        mut ticks: usize,
+    ) -> Result<(), Fault> {
+        while 0 < ticks {
+            // Writes from outside the emulator, such as a debugger or a loaded
+            // state, may have replaced code since the last run
+            for offset in emulator.memory.drain_writes() {
+                self.invalidate(offset);
+            }
+
+            let start = emulator.program_counter;
+
+            if self.blocks.get(start).is_some_and(Option::is_none) {
+                let block = translate(emulator, start)?;
+                self.insert(start, block);
+            }
+
+            let block = match self.blocks.get(start) {
+                Some(Some(block)) => block,
+                _ => return Err(Fault::InvalidAddress(start)),
+            };
+            let count = block.ops.len().min(ticks);
+            let mut result = Ok(());
+
+            for op in &block.ops[..count] {
+                if let Some(coverage) = emulator.coverage.as_mut() {
+                    coverage.mark_executed(emulator.program_counter);
+                }
+
+                match op(emulator, display, keyboard) {
+                    Ok(program_counter) => emulator.program_counter = program_counter,
+                    Err(fault) => {
+                        result = Err(fault);
+                        break;
+                    }
+                }
+            }
+
+            ticks -= count;
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores a translated block and marks the memory it was built from
+    fn insert(&mut self, start: usize, block: Block) {
+        for count in &mut self.coverage[start..block.end] {
+            *count += 1;
+        }
+
+        self.blocks[start] = Some(block);
+    }
+
+    /// Discards every block that was translated from the given offset
+    fn invalidate(&mut self, offset: usize) {
+        if self.coverage[offset] == 0 {
+            return;
+        }
+
+        // Blocks are at most MAX_BLOCK_LENGTH instructions long, so only
+        // those starting shortly before the offset can cover it
+        for start in offset.saturating_sub(MAX_BLOCK_LENGTH * 2 - 1)..=offset {
+            if self.blocks[start]
+                .as_ref()
+                .is_some_and(|block| offset < block.end)
+            {
+                if let Some(block) = self.blocks[start].take() {
+                    for count in &mut self.coverage[start..block.end] {
+                        *count -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Decodes instructions from the start address until one that can change
+/// the flow of the program or write to memory
+fn translate(emulator: &mut Emulator, start: usize) -> Result<Block, Fault> {
+    let mut ops = vec![];
+    let mut address = start;
+
+    loop {
+        let instruction = match emulator.memory.decode_instruction(address) {
+            Ok(instruction) => instruction,
+            // Leave invalid instructions to be reported when they are reached
+            Err(_) if !ops.is_empty() => break,
+            Err(fault) => return Err(fault),
+        };
+
+        ops.push(compile(instruction));
+        address += 2;
+
+        if ends_block(instruction) || ops.len() == MAX_BLOCK_LENGTH || MEMORY_SIZE <= address + 1 {
+            break;
+        }
+    }
+
+    Ok(Block { ops, end: address })
+}
+
+/// Returns whether the instruction might not continue to the next address,
+/// including draws waiting for the vertical blank, or might overwrite the
+/// instructions that follow it
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Ret
+            | Instruction::Jp { .. }
+            | Instruction::Call { .. }
+            | Instruction::SeV { .. }
+            | Instruction::SneV { .. }
+            | Instruction::SeVV { .. }
+            | Instruction::SneVV { .. }
+            | Instruction::JpV { .. }
+            | Instruction::SkpV { .. }
+            | Instruction::SknpV { .. }
+            | Instruction::Drw { .. }
+            | Instruction::LdVK { .. }
+            | Instruction::LdBV { .. }
+            | Instruction::LdIV { .. }
+    )
+}
+
+/// Boxes an infallible closure with the signature shared by every op
+fn op<F>(f: F) -> Op
+where
+    F: Fn(&mut Emulator, &mut Display, &Keyboard) -> usize + Send + 'static,
+{
+    Box::new(move |e, d, k| Ok(f(e, d, k)))
+}
+
+/// Boxes a closure with the signature shared by every op
+fn try_op<F>(f: F) -> Op
+where
+    F: Fn(&mut Emulator, &mut Display, &Keyboard) -> Result<usize, Fault> + Send + 'static,
+{
+    Box::new(f)
+}
+
+/// Builds a closure with the operands of the instruction already bound
+fn compile(instruction: Instruction) -> Op {
+    match instruction {
+        Instruction::Cls => op(|e, d, _| e.cls(d)),
+        Instruction::Ret => try_op(|e, _, _| e.ret()),
+        Instruction::Jp { addr } => op(move |e, _, _| e.jp(addr)),
+        Instruction::Call { addr } => try_op(move |e, _, _| e.call(addr)),
+        Instruction::SeV { vx, byte } => op(move |e, _, _| e.se_v(vx, byte)),
+        Instruction::SneV { vx, byte } => op(move |e, _, _| e.sne_v(vx, byte)),
+        Instruction::SeVV { vx, vy } => op(move |e, _, _| e.se_v_v(vx, vy)),
+        Instruction::LdV { vx, byte } => op(move |e, _, _| e.ld_v(vx, byte)),
+        Instruction::AddV { vx, byte } => op(move |e, _, _| e.add_v(vx, byte)),
+        Instruction::LdVV { vx, vy } => op(move |e, _, _| e.ld_v_v(vx, vy)),
+        Instruction::OrVV { vx, vy } => op(move |e, _, _| e.or_v_v(vx, vy)),
+        Instruction::AndVV { vx, vy } => op(move |e, _, _| e.and_v_v(vx, vy)),
+        Instruction::XorVV { vx, vy } => op(move |e, _, _| e.xor_v_v(vx, vy)),
+        Instruction::AddVV { vx, vy } => op(move |e, _, _| e.add_v_v(vx, vy)),
+        Instruction::SubVV { vx, vy } => op(move |e, _, _| e.sub_v_v(vx, vy)),
+        Instruction::ShrVV { vx, vy } => op(move |e, _, _| e.shr_v_v(vx, vy)),
+        Instruction::SubnVV { vx, vy } => op(move |e, _, _| e.subn_v_v(vx, vy)),
+        Instruction::ShlVV { vx, vy } => op(move |e, _, _| e.shl_v_v(vx, vy)),
+        Instruction::SneVV { vx, vy } => op(move |e, _, _| e.sne_v_v(vx, vy)),
+        Instruction::LdI { addr } => op(move |e, _, _| e.ld_i(addr)),
+        Instruction::JpV { addr } => op(move |e, _, _| e.jp_v(addr)),
+        Instruction::RndV { vx, byte } => op(move |e, _, _| e.rnd_v(vx, byte)),
+        Instruction::Drw { vx, vy, n } => try_op(move |e, d, _| e.drw(vx, vy, n, d)),
+        Instruction::SkpV { vx } => try_op(move |e, _, k| e.skp_v(vx, k)),
+        Instruction::SknpV { vx } => try_op(move |e, _, k| e.sknp_v(vx, k)),
+        Instruction::LdVDt { vx } => op(move |e, _, _| e.ld_v_dt(vx)),
+        Instruction::LdVK { vx } => op(move |e, _, k| e.ld_v_k(vx, k)),
+        Instruction::LdDtV { vx } => op(move |e, _, _| e.ld_dt_v(vx)),
+        Instruction::LdStV { vx } => op(move |e, _, _| e.ld_st_v(vx)),
+        Instruction::AddIV { vx } => op(move |e, _, _| e.add_i_v(vx)),
+        Instruction::LdFV { vx } => try_op(move |e, _, _| e.ld_f_v(vx)),
+        Instruction::LdBV { vx } => try_op(move |e, _, _| e.ld_b_v(vx)),
+        Instruction::LdIV { vx } => try_op(move |e, _, _| e.ld_i_v(vx)),
+        Instruction::LdVI { vx } => try_op(move |e, _, _| e.ld_v_i(vx)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Backend;
+
+    /// Runs the rom one tick at a time on the interpreter and in uneven
+    /// batches on the recompiler, checking the state matches throughout
+    fn assert_same_as_interpreter(rom: &[u8], ticks: usize) {
+        let mut interpreter = Emulator::new(rom).unwrap();
+        interpreter.enable_coverage();
+        let mut interpreter_display = Display::default();
+        let mut recompiler = Emulator::new(rom).unwrap();
+        recompiler.enable_coverage();
+        recompiler.set_backend(Backend::Recompiler);
+        let mut recompiler_display = Display::default();
+        let keyboard = Keyboard::default();
+
+        for batch in (1..=7).cycle().take(ticks) {
+            for _ in 0..batch {
+                interpreter
+                    .tick(&mut interpreter_display, &keyboard)
+                    .unwrap();
+            }
+            recompiler
+                .run(&mut recompiler_display, &keyboard, batch)
+                .unwrap();
+
+            assert_eq!(interpreter.program_counter, recompiler.program_counter);
+            assert_eq!(interpreter.registers, recompiler.registers);
+            assert_eq!(interpreter.i, recompiler.i);
+            assert_eq!(interpreter.stack, recompiler.stack);
+            assert_eq!(interpreter_display.rows(), recompiler_display.rows());
+            assert_eq!(interpreter.coverage(), recompiler.coverage());
+
+            for offset in 0..MEMORY_SIZE {
+                assert_eq!(
+                    interpreter.memory.get_byte(offset),
+                    recompiler.memory.get_byte(offset)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_loop_with_draws() {
+        assert_same_as_interpreter(
+            &[
+                0x60, 0x01, 0x71, 0x01, 0x80, 0x14, 0x82, 0x06, 0xA3, 0x00, 0xD1, 0x25, 0x31, 0x00,
+                0x12, 0x02, 0x12, 0x00,
+            ],
+            100,
+        );
+    }
+
+    #[test]
+    fn test_subroutines_and_bcd() {
+        assert_same_as_interpreter(
+            &[
+                0x22, 0x08, 0x70, 0x07, 0xF0, 0x29, 0x12, 0x00, 0xA3, 0x00, 0xF0, 0x33, 0xF2, 0x65,
+                0x00, 0xEE,
+            ],
+            100,
+        );
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        assert_same_as_interpreter(
+            &[
+                0x60, 0x61, 0x71, 0x01, 0x62, 0x0F, 0x82, 0x12, 0x63, 0x60, 0x83, 0x21, 0xA2, 0x00,
+                0x80, 0x30, 0xF0, 0x55, 0x12, 0x00,
+            ],
+            100,
+        );
+    }
+
+    #[test]
+    fn test_memory_patched_between_runs() {
+        let mut emulator = Emulator::new(&[0x60, 0x01, 0x12, 0x00]).unwrap();
+        emulator.set_backend(Backend::Recompiler);
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        emulator.run(&mut display, &keyboard, 2).unwrap();
+        assert_eq!(emulator.registers[0x0], 0x1);
+
+        emulator.memory_mut().set_byte(0x201, 0x02).unwrap();
+        emulator.run(&mut display, &keyboard, 1).unwrap();
+        assert_eq!(emulator.registers[0x0], 0x2);
+    }
+
+    #[test]
+    fn test_switching_to_interpreter_clears_write_log() {
+        // Stores V0 over itself in a loop
+        let mut emulator = Emulator::new(&[0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00]).unwrap();
+        emulator.set_backend(Backend::Recompiler);
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        emulator.run(&mut display, &keyboard, 2).unwrap();
+        emulator.memory_mut().set_byte(0x300, 0x01).unwrap();
+        emulator.set_backend(Backend::Interpreter);
+        assert_eq!(emulator.memory.drain_writes().count(), 0);
+
+        emulator.run(&mut display, &keyboard, 30).unwrap();
+        assert_eq!(emulator.memory.drain_writes().count(), 0);
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        let mut emulator = Emulator::new(&[0x60, 0x01, 0xF0, 0xFF]).unwrap();
+        emulator.set_backend(Backend::Recompiler);
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        let result = emulator.run(&mut display, &keyboard, 2);
+
+        assert_eq!(
+            result,
+            Err(Fault::InvalidInstruction(
+                "Unknown instruction 0xF0FF".to_string()
+            ))
+        );
+        assert_eq!(emulator.program_counter, 0x202);
+        assert_eq!(emulator.registers[0x0], 0x1);
+    }
+}
@@ -0,0 +1,162 @@
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{Emulator, StackStorage};
+use crate::{
+    display::{Display, HEIGHT},
+    memory::{LoadError, STACK_SIZE},
+};
+
+/// Everything about a running program that can change, along with its
+/// screen, so that it can be returned to later
+///
+/// Settings such as the quirks, load address and backend are not saved and
+/// stay as they are when a state is loaded. States can be stored with serde,
+/// which includes the random number generator so that a run continues the
+/// same way after loading.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveState {
+    memory: Vec<u8>,
+    font_offset: usize,
+    registers: [u8; 16],
+    i: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    program_counter: usize,
+    stack: Vec<usize>,
+    stack_pointer: usize,
+    vblank: bool,
+    rng: ChaCha12Rng,
+    display: [u64; HEIGHT],
+}
+
+impl Emulator {
+    /// Saves the state of the program and the screen it has drawn
+    pub fn save_state(&self, display: &Display) -> SaveState {
+        let mut rows = [0; HEIGHT];
+        rows.copy_from_slice(display.rows());
+
+        SaveState {
+            memory: self.memory.bytes().to_vec(),
+            font_offset: self.memory.font_offset(),
+            registers: self.registers,
+            i: self.i,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            program_counter: self.program_counter,
+            stack: self.stack.clone(),
+            stack_pointer: self.stack_pointer,
+            vblank: self.vblank,
+            rng: self.rng.clone(),
+            display: rows,
+        }
+    }
+
+    /// Returns the program and the screen to a saved state
+    pub fn load_state(
+        &mut self,
+        state: &SaveState,
+        display: &mut Display,
+    ) -> Result<(), LoadError> {
+        let stack_depth = match self.stack_storage {
+            StackStorage::Host => state.stack.len(),
+            StackStorage::Memory => state.stack_pointer,
+        };
+
+        if self.stack_capacity() < stack_depth || STACK_SIZE / 2 < state.stack_pointer {
+            return Err(LoadError::InvalidState);
+        }
+
+        self.memory.restore(&state.memory, state.font_offset)?;
+        self.registers = state.registers;
+        self.i = state.i;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.program_counter = state.program_counter;
+        self.stack = state.stack.clone();
+        self.stack_pointer = state.stack_pointer;
+        self.vblank = state.vblank;
+        self.rng = state.rng.clone();
+        display.set_rows(&state.display);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::DEFAULT_STACK_LIMIT, keyboard::Keyboard};
+
+    #[test]
+    fn test_save_and_load_state() {
+        // Draws random sprites from a subroutine in a loop
+        let rom = [
+            0x22, 0x04, 0x12, 0x00, 0xC0, 0x3F, 0xC1, 0x1F, 0xA2, 0x0E, 0xD0, 0x15, 0x00, 0xEE,
+            0xF0, 0x90, 0xF0, 0x90, 0xF0,
+        ];
+        let mut emulator = Emulator::new(&rom).unwrap();
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        emulator.set_seed(7);
+        emulator.run(&mut display, &keyboard, 9).unwrap();
+
+        let state = emulator.save_state(&display);
+        let json = serde_json::to_string(&state).unwrap();
+
+        emulator.run(&mut display, &keyboard, 60).unwrap();
+        let expected = emulator.save_state(&display);
+
+        let mut restored = Emulator::new(&[]).unwrap();
+        let mut restored_display = Display::default();
+        restored
+            .load_state(&serde_json::from_str(&json).unwrap(), &mut restored_display)
+            .unwrap();
+        assert_eq!(restored.save_state(&restored_display), state);
+
+        restored.run(&mut restored_display, &keyboard, 60).unwrap();
+        assert_eq!(restored.save_state(&restored_display), expected);
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        let mut emulator = Emulator::new(&[]).unwrap();
+        let mut display = Display::default();
+        let mut state = emulator.save_state(&display);
+
+        state.memory.pop();
+
+        assert_eq!(
+            emulator.load_state(&state, &mut display),
+            Err(LoadError::InvalidState)
+        );
+    }
+
+    #[test]
+    fn test_load_deep_stack() {
+        // Calls itself forever
+        let mut emulator = Emulator::new(&[0x22, 0x00]).unwrap();
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        emulator.set_stack_limit(None);
+        emulator.run(&mut display, &keyboard, 100).unwrap();
+
+        let mut state = emulator.save_state(&display);
+        assert!(emulator.load_state(&state, &mut display).is_ok());
+
+        emulator.set_stack_limit(Some(DEFAULT_STACK_LIMIT));
+        assert_eq!(
+            emulator.load_state(&state, &mut display),
+            Err(LoadError::InvalidState)
+        );
+
+        emulator.set_stack_limit(None);
+        state.stack_pointer = STACK_SIZE;
+        assert_eq!(
+            emulator.load_state(&state, &mut display),
+            Err(LoadError::InvalidState)
+        );
+    }
+}
@@ -0,0 +1,709 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, ErrorKind, Read, Write},
+    ops::Range,
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    display::Display,
+    emulator::{Emulator, Fault},
+    keyboard::Keyboard,
+    memory::MEMORY_SIZE,
+};
+
+/// The signal reported when the client interrupts a running program
+const SIGINT: u8 = 2;
+
+/// The signal reported when the program faults on an instruction
+const SIGILL: u8 = 4;
+
+/// The signal reported for breakpoints and steps
+const SIGTRAP: u8 = 5;
+
+/// The signal reported when the program faults on an address
+const SIGSEGV: u8 = 11;
+
+/// The size of the register block sent for the `g` packet
+const REGISTERS_SIZE: usize = 22;
+
+/// The largest packet the client may send, which is advertised to it
+const PACKET_SIZE: usize = 0x4000;
+
+/// Whether the client is controlling the emulator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// The program is paused for the client to inspect
+    Stopped,
+    /// The program runs until a breakpoint, fault or interrupt
+    Running,
+    /// The client detached or disconnected and the program runs freely
+    Detached,
+    /// The client asked for the emulator to exit
+    Killed,
+}
+
+/// Serves the GDB remote serial protocol to a debugger frontend
+///
+/// Registers, memory, breakpoints, single steps and continuing are
+/// supported, and the register layout is described to the client with a
+/// target description. The registers are numbered from V0 to VF, then I, the
+/// program counter and the delay and sound timers, and the 16 bit registers
+/// are sent big-endian like the opcodes.
+///
+/// The stream should be non-blocking so that `poll` returns when the client
+/// has nothing to say.
+pub struct GdbStub<S> {
+    stream: S,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    breakpoints: BTreeSet<usize>,
+    state: State,
+    signal: u8,
+    acknowledge: bool,
+}
+
+impl<S: Read + Write> GdbStub<S> {
+    /// Starts a session with the program stopped before its first
+    /// instruction
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            input: vec![],
+            output: vec![],
+            breakpoints: BTreeSet::new(),
+            state: State::Stopped,
+            signal: SIGTRAP,
+            acknowledge: true,
+        }
+    }
+
+    /// Returns whether the program is stopped, running or no longer
+    /// controlled by the client
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Reads what the client has sent and answers every complete packet
+    ///
+    /// Single steps are evaluated here, while continuing is left to `run`.
+    pub fn poll(
+        &mut self,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+    ) -> io::Result<()> {
+        if let State::Detached | State::Killed = self.state {
+            return Ok(());
+        }
+
+        let mut buffer = [0; 4096];
+
+        match self.stream.read(&mut buffer) {
+            Ok(0) => {
+                self.state = State::Detached;
+                return Ok(());
+            }
+            Ok(length) => self.input.extend_from_slice(&buffer[..length]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+
+        while let Some(packet) = self.next_packet()? {
+            self.handle(&packet, emulator, display, keyboard)?;
+
+            if let State::Detached | State::Killed = self.state {
+                break;
+            }
+        }
+
+        self.flush()
+    }
+
+    /// Evaluates up to the given number of instructions while the program
+    /// is running, stopping at breakpoints and faults
+    pub fn run(
+        &mut self,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+        ticks: usize,
+    ) -> io::Result<()> {
+        for _ in 0..ticks {
+            if self.state != State::Running {
+                break;
+            }
+
+            let program_counter = emulator.program_counter();
+
+            match emulator.tick(display, keyboard) {
+                // Instructions waiting for the vertical blank or looping on
+                // themselves only stop the first time
+                Ok(()) if emulator.program_counter() != program_counter => {
+                    if self.breakpoints.contains(&emulator.program_counter()) {
+                        self.stop(SIGTRAP)?;
+                    }
+                }
+                Ok(()) => {}
+                Err(fault) => self.fault(&fault)?,
+            }
+        }
+
+        self.flush()
+    }
+
+    /// Takes the next packet from the input, acknowledging it, and handles
+    /// interrupts sent between packets
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let start = match self.input.iter().position(|&b| b == b'$' || b == 0x03) {
+                Some(start) => start,
+                None => {
+                    // Drop acknowledgements and anything else between packets
+                    self.input.clear();
+                    return Ok(None);
+                }
+            };
+
+            if self.input[start] == 0x03 {
+                self.input.drain(..=start);
+
+                if self.state == State::Running {
+                    self.stop(SIGINT)?;
+                }
+
+                continue;
+            }
+
+            let end = match self.input[start..].iter().position(|&b| b == b'#') {
+                Some(end) if start + end + 2 < self.input.len() => start + end,
+                _ => {
+                    self.input.drain(..start);
+                    return Ok(None);
+                }
+            };
+
+            let data = self.input[start + 1..end].to_vec();
+            let checksum = std::str::from_utf8(&self.input[end + 1..end + 3])
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+            self.input.drain(..end + 3);
+
+            let valid = checksum == Some(sum(&data));
+
+            if self.acknowledge {
+                self.output.push(if valid { b'+' } else { b'-' });
+            }
+
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    /// Answers a packet from the client
+    fn handle(
+        &mut self,
+        packet: &str,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+    ) -> io::Result<()> {
+        let command = packet.get(..1).unwrap_or_default();
+        let arguments = packet.get(1..).unwrap_or_default();
+
+        let reply = match command {
+            "?" => format!("S{:02X}", self.signal),
+            "g" => encode(&read_registers(emulator)),
+            "G" => {
+                let values = decode(arguments).filter(|values| values.len() == REGISTERS_SIZE);
+                result(values.map(|values| write_registers(emulator, &values)))
+            }
+            "p" => {
+                let range = parse_hex(arguments).and_then(register_range);
+                range.map_or(error(), |range| encode(&read_registers(emulator)[range]))
+            }
+            "P" => result(self.write_register(emulator, arguments)),
+            "m" => self.read_memory(emulator, arguments),
+            "M" => result(self.write_memory(emulator, arguments)),
+            "Z" | "z" => self.breakpoint(command == "Z", arguments),
+            "c" | "s" => {
+                if let Some(address) = parse_hex(arguments) {
+                    emulator.set_program_counter(address);
+                }
+
+                if command == "c" {
+                    self.state = State::Running;
+                    return Ok(());
+                }
+
+                match emulator.tick(display, keyboard) {
+                    Ok(()) => self.stop(SIGTRAP)?,
+                    Err(fault) => self.fault(&fault)?,
+                }
+
+                return Ok(());
+            }
+            "D" => {
+                self.send("OK")?;
+                self.state = State::Detached;
+                return Ok(());
+            }
+            "k" => {
+                self.state = State::Killed;
+                return Ok(());
+            }
+            "H" => "OK".to_string(),
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        };
+
+        self.send(&reply)?;
+
+        if packet == "QStartNoAckMode" {
+            self.acknowledge = false;
+        }
+
+        Ok(())
+    }
+
+    /// Answers the general queries used while connecting
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={:X};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            );
+        }
+
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let description = target_description();
+
+            return match parse_pair(range, ',') {
+                Some((offset, length)) if offset.checked_add(length).is_none() => error(),
+                Some((offset, length)) if offset < description.len() => {
+                    let end = (offset + length).min(description.len());
+                    let marker = if end == description.len() { 'l' } else { 'm' };
+                    format!("{}{}", marker, &description[offset..end])
+                }
+                Some(_) => "l".to_string(),
+                None => error(),
+            };
+        }
+
+        match packet {
+            "QStartNoAckMode" => "OK".to_string(),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Replaces one register from a `P` packet such as `11=0204`
+    fn write_register(&self, emulator: &mut Emulator, arguments: &str) -> Option<()> {
+        let (number, value) = arguments.split_once('=')?;
+        let range = register_range(parse_hex(number)?)?;
+        let value = decode(value).filter(|value| value.len() == range.len())?;
+
+        let mut registers = read_registers(emulator);
+        registers[range].copy_from_slice(&value);
+        write_registers(emulator, &registers);
+
+        Some(())
+    }
+
+    /// Reads memory for an `m` packet, stopping at the end of memory
+    fn read_memory(&self, emulator: &Emulator, arguments: &str) -> String {
+        match parse_pair(arguments, ',') {
+            Some((address, length))
+                if address < MEMORY_SIZE && address.checked_add(length).is_some() =>
+            {
+                let bytes: Vec<u8> = (address..(address + length).min(MEMORY_SIZE))
+                    .filter_map(|address| emulator.memory().get_byte(address).ok())
+                    .collect();
+                encode(&bytes)
+            }
+            _ => error(),
+        }
+    }
+
+    /// Writes memory for an `M` packet such as `300,2:00E0`
+    fn write_memory(&self, emulator: &mut Emulator, arguments: &str) -> Option<()> {
+        let (range, data) = arguments.split_once(':')?;
+        let (address, length) = parse_pair(range, ',')?;
+        let bytes = decode(data).filter(|bytes| bytes.len() == length)?;
+
+        address
+            .checked_add(length)
+            .filter(|&end| end <= MEMORY_SIZE)?;
+
+        for (offset, &byte) in bytes.iter().enumerate() {
+            emulator
+                .memory_mut()
+                .set_byte(address + offset, byte)
+                .ok()?;
+        }
+
+        Some(())
+    }
+
+    /// Inserts or removes a breakpoint for a `Z` or `z` packet, treating
+    /// hardware breakpoints the same as software ones
+    fn breakpoint(&mut self, insert: bool, arguments: &str) -> String {
+        let mut fields = arguments.split(',');
+
+        let address = match (fields.next(), fields.next().and_then(parse_hex)) {
+            (Some("0") | Some("1"), Some(address)) => address,
+            (Some(_), Some(_)) => return String::new(),
+            _ => return error(),
+        };
+
+        if insert {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+
+        "OK".to_string()
+    }
+
+    /// Stops the program and tells the client why
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.state = State::Stopped;
+        self.signal = signal;
+        self.send(&format!("S{:02X}", signal))
+    }
+
+    /// Stops the program on a fault, printing its message in the client
+    fn fault(&mut self, fault: &Fault) -> io::Result<()> {
+        let signal = match fault {
+            Fault::InvalidInstruction(_) | Fault::InvalidKey(_) | Fault::InvalidDigit(_) => SIGILL,
+            Fault::StackOverflow
+            | Fault::StackUnderflow
+            | Fault::ProtectedWrite(_)
+            | Fault::InvalidAddress(_) => SIGSEGV,
+        };
+
+        self.send(&format!("O{}", encode(format!("{}\n", fault).as_bytes())))?;
+        self.stop(signal)
+    }
+
+    /// Queues a packet with its checksum to be sent
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(self.output, "${}#{:02x}", data, sum(data.as_bytes()))
+    }
+
+    /// Writes everything queued in one go, waiting while a non-blocking
+    /// stream is full
+    fn flush(&mut self) -> io::Result<()> {
+        let mut bytes = &self.output[..];
+
+        while !bytes.is_empty() {
+            match self.stream.write(bytes) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(length) => bytes = &bytes[length..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.output.clear();
+        self.stream.flush()
+    }
+}
+
+/// Describes the registers so that clients can name and size them
+fn target_description() -> String {
+    let mut registers: Vec<String> = (0..16)
+        .map(|n| format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>", n))
+        .collect();
+    registers.push("<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>".to_string());
+    registers.push("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>".to_string());
+    registers.push("<reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>".to_string());
+    registers.push("<reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>".to_string());
+
+    format!(
+        "<?xml version=\"1.0\"?>\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.cpu\">{}</feature></target>",
+        registers.concat()
+    )
+}
+
+/// Returns the bytes of every register in the order they are numbered
+fn read_registers(emulator: &Emulator) -> Vec<u8> {
+    let mut registers = emulator.registers().to_vec();
+    registers.extend_from_slice(&emulator.i().to_be_bytes());
+    registers.extend_from_slice(&(emulator.program_counter() as u16).to_be_bytes());
+    registers.push(emulator.delay_timer());
+    registers.push(emulator.sound_timer());
+    registers
+}
+
+/// Replaces every register from bytes in the order they are numbered
+fn write_registers(emulator: &mut Emulator, registers: &[u8]) {
+    emulator.registers_mut().copy_from_slice(&registers[..16]);
+    emulator.set_i(u16::from_be_bytes([registers[16], registers[17]]));
+    emulator.set_program_counter(u16::from_be_bytes([registers[18], registers[19]]) as usize);
+    emulator.set_delay_timer(registers[20]);
+    emulator.set_sound_timer(registers[21]);
+}
+
+/// Returns where a numbered register is in the register block
+fn register_range(number: usize) -> Option<Range<usize>> {
+    match number {
+        0..=15 => Some(number..number + 1),
+        16 => Some(16..18),
+        17 => Some(18..20),
+        18 => Some(20..21),
+        19 => Some(21..22),
+        _ => None,
+    }
+}
+
+/// Returns the reply for a packet with `OK` if it succeeded
+fn result(outcome: Option<()>) -> String {
+    outcome.map_or(error(), |_| "OK".to_string())
+}
+
+/// Returns the reply for a malformed or failed request
+fn error() -> String {
+    "E01".to_string()
+}
+
+/// Adds up the bytes of a packet for its checksum
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Writes bytes as pairs of hex digits
+fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Reads pairs of hex digits as bytes
+fn decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Reads a hex number
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// Reads two hex numbers split by a separator such as `200,10`
+fn parse_pair(text: &str, separator: char) -> Option<(usize, usize)> {
+    let (first, second) = text.split_once(separator)?;
+    Some((parse_hex(first)?, parse_hex(second)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+
+    /// Sent after the replies to each request in place of a notification
+    const END: u8 = b'%';
+
+    /// A program which counts in V0 through a subroutine
+    const ROM: [u8; 10] = [0x60, 0x05, 0x22, 0x06, 0x12, 0x02, 0x70, 0x01, 0x00, 0xEE];
+
+    /// Connects a client to a stub over loopback
+    fn connect() -> (TcpStream, GdbStub<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        server.set_nonblocking(true).unwrap();
+        server.set_nodelay(true).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        (client, GdbStub::new(server))
+    }
+
+    /// Sends a packet from the client and returns the packets sent back
+    fn request(
+        client: &mut TcpStream,
+        stub: &mut GdbStub<TcpStream>,
+        emulator: &mut Emulator,
+        packet: &str,
+    ) -> Vec<String> {
+        let packet = format!("${}#{:02x}", packet, sum(packet.as_bytes()));
+        client.write_all(packet.as_bytes()).unwrap();
+        poll(client, stub, emulator)
+    }
+
+    /// Lets the stub answer once the client's bytes have arrived and reads its
+    /// replies without acknowledgements
+    fn poll(
+        client: &mut TcpStream,
+        stub: &mut GdbStub<TcpStream>,
+        emulator: &mut Emulator,
+    ) -> Vec<String> {
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        // Blocks until there is more to read, as long as part of a packet is
+        // still waiting for the rest
+        loop {
+            stub.stream.set_nonblocking(false).unwrap();
+            stub.stream.peek(&mut [0]).unwrap();
+            stub.stream.set_nonblocking(true).unwrap();
+            stub.poll(emulator, &mut display, &keyboard).unwrap();
+
+            if stub.input.is_empty() {
+                break;
+            }
+        }
+
+        stub.run(emulator, &mut display, &keyboard, 100).unwrap();
+
+        // The stub never sends a notification, so one marks the end of the
+        // replies to this request
+        stub.output.push(END);
+        stub.flush().unwrap();
+
+        read_replies(client)
+    }
+
+    /// Reads the packets sent up to the end marker
+    fn read_replies(client: &mut TcpStream) -> Vec<String> {
+        let mut text = vec![];
+        let mut buffer = [0; 4096];
+
+        while text.last() != Some(&END) {
+            let length = client.read(&mut buffer).unwrap();
+            assert_ne!(length, 0, "The stub closed the connection");
+            text.extend_from_slice(&buffer[..length]);
+        }
+
+        text.pop();
+
+        String::from_utf8_lossy(&text)
+            .split('$')
+            .skip(1)
+            .map(|packet| {
+                let (data, checksum) = packet.split_once('#').unwrap();
+                assert_eq!(&checksum[..2], format!("{:02x}", sum(data.as_bytes())));
+                data.to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (mut client, mut stub) = connect();
+        let mut emulator = Emulator::new(&ROM).unwrap();
+        let mut send = |packet: &str, emulator: &mut Emulator| {
+            request(&mut client, &mut stub, emulator, packet)
+        };
+
+        assert!(send("qSupported:swbreak+", &mut emulator)[0].contains("qXfer:features:read+"));
+        assert_eq!(send("QStartNoAckMode", &mut emulator), ["OK"]);
+        assert_eq!(send("?", &mut emulator), ["S05"]);
+        assert_eq!(
+            send("g", &mut emulator),
+            [format!("{}000002000000", "00".repeat(16))]
+        );
+
+        assert_eq!(send("P3=2a", &mut emulator), ["OK"]);
+        assert_eq!(send("P10=0123", &mut emulator), ["OK"]);
+        assert_eq!(send("p3", &mut emulator), ["2a"]);
+        assert_eq!(emulator.registers()[3], 0x2A);
+        assert_eq!(emulator.i(), 0x123);
+        assert_eq!(send("P20=00", &mut emulator), ["E01"]);
+
+        assert_eq!(send("m200,4", &mut emulator), ["60052206"]);
+        assert_eq!(send("mffe,4", &mut emulator), ["0000"]);
+        assert_eq!(send("M300,2:00e0", &mut emulator), ["OK"]);
+        assert_eq!(emulator.memory().get_instruction(0x300), Ok(0x00E0));
+        assert_eq!(send("Mfff,2:0000", &mut emulator), ["E01"]);
+        assert_eq!(send("m200,ffffffffffffffff", &mut emulator), ["E01"]);
+        assert_eq!(send("Mffffffffffffffff,1:00", &mut emulator), ["E01"]);
+        assert_eq!(
+            send(
+                "qXfer:features:read:target.xml:5,ffffffffffffffff",
+                &mut emulator
+            ),
+            ["E01"]
+        );
+
+        let description = send("qXfer:features:read:target.xml:0,2000", &mut emulator);
+        assert!(description[0].starts_with("l<?xml"));
+        assert!(description[0].contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+    }
+
+    #[test]
+    fn test_breakpoints_and_steps() {
+        let (mut client, mut stub) = connect();
+        let mut emulator = Emulator::new(&ROM).unwrap();
+
+        assert_eq!(request(&mut client, &mut stub, &mut emulator, "s"), ["S05"]);
+        assert_eq!(emulator.program_counter(), 0x202);
+
+        assert_eq!(
+            request(&mut client, &mut stub, &mut emulator, "Z0,206,2"),
+            ["OK"]
+        );
+        assert_eq!(request(&mut client, &mut stub, &mut emulator, "c"), ["S05"]);
+        assert_eq!(emulator.program_counter(), 0x206);
+        assert_eq!(stub.state(), State::Stopped);
+
+        // Continuing from a breakpoint runs the instruction under it
+        assert_eq!(request(&mut client, &mut stub, &mut emulator, "c"), ["S05"]);
+        assert_eq!(emulator.registers()[0], 0x06);
+
+        // Faults stop the program with their message
+        assert_eq!(
+            request(&mut client, &mut stub, &mut emulator, "M204,2:00ee"),
+            ["OK"]
+        );
+        assert_eq!(
+            request(&mut client, &mut stub, &mut emulator, "c"),
+            [
+                format!("O{}", encode(b"No subroutine to return from\n")),
+                "S0B".to_string()
+            ]
+        );
+        assert_eq!(request(&mut client, &mut stub, &mut emulator, "?"), ["S0B"]);
+
+        assert_eq!(
+            request(&mut client, &mut stub, &mut emulator, "z0,206,2"),
+            ["OK"]
+        );
+        assert_eq!(request(&mut client, &mut stub, &mut emulator, "D"), ["OK"]);
+        assert_eq!(stub.state(), State::Detached);
+    }
+
+    #[test]
+    fn test_interrupt_and_kill() {
+        let (mut client, mut stub) = connect();
+        let mut emulator = Emulator::new(&[0x12, 0x00]).unwrap();
+
+        assert!(request(&mut client, &mut stub, &mut emulator, "c").is_empty());
+        assert_eq!(stub.state(), State::Running);
+
+        client.write_all(&[0x03]).unwrap();
+        assert_eq!(poll(&mut client, &mut stub, &mut emulator), ["S02"]);
+
+        assert!(request(&mut client, &mut stub, &mut emulator, "k").is_empty());
+        assert_eq!(stub.state(), State::Killed);
+    }
+}
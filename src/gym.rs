@@ -0,0 +1,610 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    display::{Display, HEIGHT, WIDTH},
+    emulator::{Backend, Emulator, Fault},
+    keyboard::Keyboard,
+    memory::{LoadError, PROGRAM_OFFSET},
+    quirks::Quirks,
+};
+
+/// The number of bytes in an observation of the screen
+pub const OBSERVATION_SIZE: usize = WIDTH * HEIGHT / 8;
+
+/// The screen with eight pixels in each byte, row by row from the top left
+/// and with the leftmost pixel in the most significant bit
+pub type Observation = [u8; OBSERVATION_SIZE];
+
+/// The most digits read by a `Bcd` extractor, which always fit in a `u32`
+pub const MAX_BCD_DIGITS: usize = 9;
+
+/// Reads a number from the emulator, such as a score or a count of lives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extractor {
+    /// The byte at an address
+    Byte(usize),
+    /// The big-endian word at an address
+    Word(usize),
+    /// Decimal digits stored one to a byte from the most significant, such
+    /// as the three written by `LD B, Vx`, of which at most the first
+    /// `MAX_BCD_DIGITS` are read
+    Bcd { address: usize, digits: usize },
+    /// One of the registers V0 to VF
+    Register(u8),
+}
+
+impl Extractor {
+    /// Returns the value, reading addresses past the end of memory as 0
+    ///
+    /// Digits of a `Bcd` value above 9 are added in as they are, wrapping
+    /// around if the total does not fit.
+    pub fn read(&self, emulator: &Emulator) -> u32 {
+        let byte = |address: Option<usize>| {
+            address.map_or(0, |address| {
+                emulator.memory().get_byte(address).unwrap_or_default() as u32
+            })
+        };
+
+        match *self {
+            Extractor::Byte(address) => byte(Some(address)),
+            Extractor::Word(address) => byte(Some(address)) << 8 | byte(address.checked_add(1)),
+            Extractor::Bcd { address, digits } => (0..digits.min(MAX_BCD_DIGITS))
+                .map(|digit| byte(address.checked_add(digit)))
+                .fold(0, |value: u32, digit| {
+                    value.wrapping_mul(10).wrapping_add(digit)
+                }),
+            Extractor::Register(vx) => emulator.registers()[vx as usize & 0xF] as u32,
+        }
+    }
+}
+
+/// A test of a value which ends an episode when it passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// The value is the number, such as a level that ends the game
+    Equals(Extractor, u32),
+    /// The value is less than the number, such as lives running out
+    Below(Extractor, u32),
+    /// The value is more than the number
+    Above(Extractor, u32),
+}
+
+impl Condition {
+    /// Returns whether the condition holds for the emulator
+    pub fn is_met(&self, emulator: &Emulator) -> bool {
+        match *self {
+            Condition::Equals(extractor, value) => extractor.read(emulator) == value,
+            Condition::Below(extractor, value) => extractor.read(emulator) < value,
+            Condition::Above(extractor, value) => extractor.read(emulator) > value,
+        }
+    }
+}
+
+/// How each environment runs its rom and scores its agent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The quirks of the platform the rom was written for
+    pub quirks: Quirks,
+    /// The address the rom is loaded at
+    pub load_address: usize,
+    /// How instructions are run, which does not change the outcome
+    pub backend: Backend,
+    /// Instructions run every frame
+    pub ticks_per_frame: usize,
+    /// Frames run with the same keys held on every step
+    pub frames_per_step: usize,
+    /// The value whose increase since the last step is the reward
+    pub reward: Option<Extractor>,
+    /// Conditions which end the episode when any of them are met
+    pub termination: Vec<Condition>,
+    /// The number of steps after which the episode ends
+    pub max_steps: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            load_address: PROGRAM_OFFSET,
+            backend: Backend::Interpreter,
+            ticks_per_frame: 8,
+            frames_per_step: 1,
+            reward: None,
+            termination: vec![],
+            max_steps: None,
+        }
+    }
+}
+
+/// The outcome of one step of an environment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    /// The fault which stopped the program, which also ends the episode
+    pub fault: Option<Fault>,
+}
+
+/// A Chip-8 rom as an environment for reinforcement learning
+///
+/// Actions are the keys to hold down as a mask with bit n set for key n,
+/// like `Keyboard::state`. Episodes are fully determined by the rom, the
+/// seed and the actions, so they can be replayed exactly.
+pub struct Env {
+    config: Config,
+    emulator: Emulator,
+    display: Display,
+    keyboard: Keyboard,
+    score: u32,
+    steps: usize,
+    done: bool,
+}
+
+impl Env {
+    /// Creates an environment which is done until it is reset with a rom
+    pub fn new(config: Config) -> Self {
+        let emulator = Emulator::new(&[]).expect("An empty rom always fits");
+
+        Self {
+            config,
+            emulator,
+            display: Display::default(),
+            keyboard: Keyboard::default(),
+            score: 0,
+            steps: 0,
+            done: true,
+        }
+    }
+
+    /// Returns the emulator, such as to read values for logging
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Returns whether the episode has ended
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Starts a new episode of the rom with the random numbers seeded
+    pub fn reset(&mut self, rom: &[u8], seed: u64) -> Result<Observation, LoadError> {
+        let mut emulator = Emulator::with_load_address(rom, self.config.load_address)?;
+        emulator.set_quirks(self.config.quirks);
+        emulator.set_backend(self.config.backend);
+        emulator.set_seed(seed);
+
+        self.emulator = emulator;
+        self.display = Display::default();
+        self.keyboard = Keyboard::default();
+        self.score = self.read_score();
+        self.steps = 0;
+        self.done = false;
+
+        Ok(self.observation())
+    }
+
+    /// Holds down the keys in the action for the frames of one step
+    ///
+    /// Stepping an episode which is done changes nothing and gives no reward.
+    pub fn step(&mut self, action: u16) -> Step {
+        if self.done {
+            return self.outcome(0.0, None);
+        }
+
+        self.keyboard.set_state(action);
+
+        let mut fault = None;
+
+        for _ in 0..self.config.frames_per_step {
+            self.emulator.decrement_timers();
+
+            let result = self.emulator.run(
+                &mut self.display,
+                &self.keyboard,
+                self.config.ticks_per_frame,
+            );
+
+            if let Err(error) = result {
+                fault = Some(error);
+                break;
+            }
+        }
+
+        let score = self.read_score();
+        let reward = score as f64 - self.score as f64;
+        self.score = score;
+        self.steps += 1;
+
+        self.done = fault.is_some()
+            || self.config.max_steps.is_some_and(|max| max <= self.steps)
+            || self
+                .config
+                .termination
+                .iter()
+                .any(|condition| condition.is_met(&self.emulator));
+
+        self.outcome(reward, fault)
+    }
+
+    /// Returns the screen packed into bytes
+    pub fn observation(&self) -> Observation {
+        let mut observation = [0; OBSERVATION_SIZE];
+
+        for (bytes, row) in observation
+            .chunks_exact_mut(WIDTH / 8)
+            .zip(self.display.rows())
+        {
+            bytes.copy_from_slice(&row.to_be_bytes());
+        }
+
+        observation
+    }
+
+    /// Returns the value rewarded, or 0 if there is no reward
+    fn read_score(&self) -> u32 {
+        self.config
+            .reward
+            .map_or(0, |extractor| extractor.read(&self.emulator))
+    }
+
+    /// Describes the state after a step
+    fn outcome(&self, reward: f64, fault: Option<Fault>) -> Step {
+        Step {
+            observation: self.observation(),
+            reward,
+            done: self.done,
+            fault,
+        }
+    }
+}
+
+/// Many environments stepped together on a pool of worker threads
+///
+/// The environments are split into one chunk for each worker, and the
+/// workers live as long as the batch so that a step only has to hand them
+/// their chunk. Each environment only depends on its own seed and actions,
+/// so the results are the same for any number of threads.
+pub struct BatchEnv {
+    chunks: Vec<Vec<Env>>,
+    workers: Vec<Worker>,
+}
+
+/// A thread which steps the chunk of environments it is sent
+struct Worker {
+    jobs: Sender<(Vec<Env>, Vec<u16>)>,
+    results: Receiver<(Vec<Env>, Vec<Step>)>,
+    handle: JoinHandle<()>,
+}
+
+impl Worker {
+    fn spawn() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<(Vec<Env>, Vec<u16>)>();
+        let (result_sender, results) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            for (mut envs, actions) in job_receiver {
+                let steps = step_all(&mut envs, &actions);
+
+                if result_sender.send((envs, steps)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            jobs,
+            results,
+            handle,
+        }
+    }
+}
+
+impl BatchEnv {
+    /// Creates a number of environments with the same config, using a thread
+    /// for each available core
+    pub fn new(config: Config, size: usize) -> Self {
+        let mut batch = Self {
+            chunks: vec![(0..size).map(|_| Env::new(config.clone())).collect()],
+            workers: vec![],
+        };
+
+        batch.set_threads(thread::available_parallelism().map_or(1, |threads| threads.get()));
+        batch
+    }
+
+    /// Sets the most threads to step the environments on, starting a new
+    /// pool of workers
+    pub fn set_threads(&mut self, threads: usize) {
+        self.stop_workers();
+
+        let envs: Vec<Env> = self.chunks.drain(..).flatten().collect();
+        let chunk_size = envs.len().div_ceil(threads.max(1)).max(1);
+        let mut envs = envs.into_iter().peekable();
+
+        while envs.peek().is_some() {
+            self.chunks.push(envs.by_ref().take(chunk_size).collect());
+        }
+
+        // A single chunk is stepped on the calling thread
+        if 1 < self.chunks.len() {
+            self.workers = self.chunks.iter().map(|_| Worker::spawn()).collect();
+        }
+    }
+
+    /// Returns the number of environments
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(Vec::len).sum()
+    }
+
+    /// Returns whether there are no environments
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the environments in order
+    pub fn envs(&self) -> impl Iterator<Item = &Env> {
+        self.chunks.iter().flatten()
+    }
+
+    /// Returns the environments in order, such as to reset those that are
+    /// done
+    pub fn envs_mut(&mut self) -> impl Iterator<Item = &mut Env> {
+        self.chunks.iter_mut().flatten()
+    }
+
+    /// Returns an environment, panicking if the index is out of range
+    pub fn env(&self, index: usize) -> &Env {
+        self.envs().nth(index).expect("No env at the index")
+    }
+
+    /// Returns an environment, panicking if the index is out of range
+    pub fn env_mut(&mut self, index: usize) -> &mut Env {
+        self.envs_mut().nth(index).expect("No env at the index")
+    }
+
+    /// Starts a new episode of the rom in every environment, panicking
+    /// unless there is one seed for each
+    pub fn reset(&mut self, rom: &[u8], seeds: &[u64]) -> Result<Vec<Observation>, LoadError> {
+        assert_eq!(seeds.len(), self.len(), "Expected a seed for each env");
+
+        self.envs_mut()
+            .zip(seeds)
+            .map(|(env, &seed)| env.reset(rom, seed))
+            .collect()
+    }
+
+    /// Steps every environment, panicking unless there is one action for
+    /// each
+    pub fn step(&mut self, actions: &[u16]) -> Vec<Step> {
+        assert_eq!(actions.len(), self.len(), "Expected an action for each env");
+
+        if self.workers.is_empty() {
+            return self
+                .chunks
+                .iter_mut()
+                .flat_map(|envs| step_all(envs, actions))
+                .collect();
+        }
+
+        let mut offset = 0;
+
+        for (envs, worker) in self.chunks.iter_mut().zip(&self.workers) {
+            let actions = actions[offset..offset + envs.len()].to_vec();
+            offset += envs.len();
+
+            worker
+                .jobs
+                .send((std::mem::take(envs), actions))
+                .expect("An env panicked");
+        }
+
+        let mut steps = Vec::with_capacity(actions.len());
+
+        for (envs, worker) in self.chunks.iter_mut().zip(&self.workers) {
+            let (chunk, chunk_steps) = worker.results.recv().expect("An env panicked");
+            *envs = chunk;
+            steps.extend(chunk_steps);
+        }
+
+        steps
+    }
+
+    /// Closes the jobs of every worker and waits for them to finish
+    fn stop_workers(&mut self) {
+        for worker in self.workers.drain(..) {
+            drop(worker.jobs);
+            let _ = worker.handle.join();
+        }
+    }
+}
+
+impl Drop for BatchEnv {
+    fn drop(&mut self) {
+        self.stop_workers();
+    }
+}
+
+/// Steps each environment with its action
+fn step_all(envs: &mut [Env], actions: &[u16]) -> Vec<Step> {
+    envs.iter_mut()
+        .zip(actions)
+        .map(|(env, &action)| env.step(action))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    /// Scores a point on every loop while key 0 is held, drawing a dot in a
+    /// random place each time, and stops after five points
+    const SOURCE: &str = "
+                LD V2, 0
+        loop:   SKNP V2
+                ADD V0, 1
+                LD I, score
+                LD B, V0
+                RND V3, 0x3F
+                RND V4, 0x1F
+                LD I, dot
+                DRW V3, V4, 1
+                SE V0, 5
+                JP loop
+        halt:   JP halt
+        dot:    DB 0x80, 0x00
+        score:  DB 0x00, 0x00, 0x00, 0x00
+    ";
+
+    fn config(rom: &[u8]) -> Config {
+        let score = PROGRAM_OFFSET + rom.len() - 4;
+
+        Config {
+            reward: Some(Extractor::Bcd {
+                address: score,
+                digits: 3,
+            }),
+            termination: vec![Condition::Equals(Extractor::Register(0), 5)],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_extractors() {
+        let rom = [0x12, 0x34, 0x01, 0x02, 0x09];
+        let emulator = Emulator::new(&rom).unwrap();
+        let bcd = Extractor::Bcd {
+            address: 0x202,
+            digits: 3,
+        };
+
+        assert_eq!(Extractor::Byte(0x201).read(&emulator), 0x34);
+        assert_eq!(Extractor::Word(0x200).read(&emulator), 0x1234);
+        assert_eq!(bcd.read(&emulator), 129);
+        assert_eq!(Extractor::Byte(0xFFFF).read(&emulator), 0);
+        assert_eq!(Extractor::Word(usize::MAX).read(&emulator), 0);
+        assert_eq!(
+            Extractor::Bcd {
+                address: usize::MAX,
+                digits: 2
+            }
+            .read(&emulator),
+            0
+        );
+
+        // Only the first nine digits are read and larger digits wrap around
+        let mut emulator = Emulator::new(&[0x09; 12]).unwrap();
+        let wide = Extractor::Bcd {
+            address: 0x200,
+            digits: 12,
+        };
+        assert_eq!(wide.read(&emulator), 999_999_999);
+        emulator.memory_mut().set_byte(0x200, 0xFF).unwrap();
+        assert_eq!(
+            wide.read(&emulator),
+            255_u32.wrapping_mul(100_000_000) + 99_999_999
+        );
+
+        let emulator = Emulator::new(&rom).unwrap();
+        assert!(Condition::Above(bcd, 128).is_met(&emulator));
+        assert!(!Condition::Below(bcd, 129).is_met(&emulator));
+    }
+
+    #[test]
+    fn test_rewards_and_termination() {
+        let rom = assemble(SOURCE).unwrap();
+        let mut env = Env::new(config(&rom));
+
+        assert!(env.is_done());
+        assert_eq!(env.reset(&rom, 1).unwrap(), [0; OBSERVATION_SIZE]);
+
+        let idle = env.step(0);
+        assert_eq!(idle.reward, 0.0);
+        assert!(!idle.done);
+        assert_ne!(idle.observation, [0; OBSERVATION_SIZE]);
+
+        let mut total = 0.0;
+        let mut steps = 0;
+
+        while !env.is_done() {
+            total += env.step(0b1).reward;
+            steps += 1;
+        }
+
+        assert_eq!(total, 5.0);
+        assert!(steps < 10);
+
+        let after = env.step(0b1);
+        assert_eq!(after.reward, 0.0);
+        assert!(after.done);
+    }
+
+    #[test]
+    fn test_faults_and_max_steps() {
+        let mut env = Env::new(Config {
+            max_steps: Some(3),
+            ..Config::default()
+        });
+
+        env.reset(&[0x00, 0xEE], 0).unwrap();
+        let step = env.step(0);
+        assert!(step.done);
+        assert_eq!(step.fault, Some(Fault::StackUnderflow));
+
+        env.reset(&[0x12, 0x00], 0).unwrap();
+        let dones: Vec<bool> = (0..3).map(|_| env.step(0).done).collect();
+        assert_eq!(dones, [false, false, true]);
+    }
+
+    #[test]
+    fn test_batch_is_deterministic() {
+        let rom = assemble(SOURCE).unwrap();
+        let seeds = [1, 2, 3, 1, 5];
+        let actions: Vec<Vec<u16>> = (0..6)
+            .map(|step| (0..5).map(|env| ((step + env) % 2) as u16).collect())
+            .collect();
+
+        let run = |threads, backend| {
+            let mut config = config(&rom);
+            config.backend = backend;
+            let mut batch = BatchEnv::new(config, seeds.len());
+            batch.set_threads(threads);
+            batch.reset(&rom, &seeds).unwrap();
+
+            actions
+                .iter()
+                .map(|actions| batch.step(actions))
+                .collect::<Vec<_>>()
+        };
+
+        let steps = run(1, Backend::Interpreter);
+        assert_eq!(steps, run(2, Backend::Interpreter));
+        assert_eq!(steps, run(8, Backend::Interpreter));
+        assert_eq!(steps, run(2, Backend::Recompiler));
+
+        // Changing the threads between steps keeps every env where it was
+        let mut batch = BatchEnv::new(config(&rom), seeds.len());
+        batch.reset(&rom, &seeds).unwrap();
+
+        for (threads, (actions, expected)) in actions.iter().zip(&steps).enumerate() {
+            batch.set_threads(threads + 1);
+            assert_eq!(&batch.step(actions), expected);
+        }
+
+        // Envs with the same seed and actions match while others differ
+        let actions: Vec<Vec<u16>> = (0..6).map(|_| vec![1; 5]).collect();
+        let mut batch = BatchEnv::new(config(&rom), seeds.len());
+        batch.reset(&rom, &seeds).unwrap();
+
+        for actions in &actions {
+            let steps = batch.step(actions);
+            assert_eq!(steps[0], steps[3]);
+        }
+
+        assert_ne!(batch.env(0).observation(), batch.env(1).observation());
+    }
+}
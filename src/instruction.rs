@@ -1,6 +1,7 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, fmt};
 
 /// A parsed instruction for the Chip-8 CPU
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Instruction {
     Cls,
     Ret,
@@ -98,6 +99,95 @@ impl TryFrom<u16> for Instruction {
     }
 }
 
+impl From<Instruction> for u16 {
+    /// Encodes the instruction as its two-byte opcode
+    fn from(instruction: Instruction) -> Self {
+        let addr = |prefix: u16, addr: usize| prefix | addr as u16 & 0xFFF;
+        let byte = |prefix: u16, vx: usize, byte: u8| prefix | (vx as u16) << 8 | byte as u16;
+        let vv = |prefix: u16, vx: usize, vy: usize| prefix | (vx as u16) << 8 | (vy as u16) << 4;
+        let v = |prefix: u16, vx: usize| prefix | (vx as u16) << 8;
+
+        match instruction {
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Jp { addr: a } => addr(0x1000, a),
+            Instruction::Call { addr: a } => addr(0x2000, a),
+            Instruction::SeV { vx, byte: b } => byte(0x3000, vx, b),
+            Instruction::SneV { vx, byte: b } => byte(0x4000, vx, b),
+            Instruction::SeVV { vx, vy } => vv(0x5000, vx, vy),
+            Instruction::LdV { vx, byte: b } => byte(0x6000, vx, b),
+            Instruction::AddV { vx, byte: b } => byte(0x7000, vx, b),
+            Instruction::LdVV { vx, vy } => vv(0x8000, vx, vy),
+            Instruction::OrVV { vx, vy } => vv(0x8001, vx, vy),
+            Instruction::AndVV { vx, vy } => vv(0x8002, vx, vy),
+            Instruction::XorVV { vx, vy } => vv(0x8003, vx, vy),
+            Instruction::AddVV { vx, vy } => vv(0x8004, vx, vy),
+            Instruction::SubVV { vx, vy } => vv(0x8005, vx, vy),
+            Instruction::ShrVV { vx, vy } => vv(0x8006, vx, vy),
+            Instruction::SubnVV { vx, vy } => vv(0x8007, vx, vy),
+            Instruction::ShlVV { vx, vy } => vv(0x800E, vx, vy),
+            Instruction::SneVV { vx, vy } => vv(0x9000, vx, vy),
+            Instruction::LdI { addr: a } => addr(0xA000, a),
+            Instruction::JpV { addr: a } => addr(0xB000, a),
+            Instruction::RndV { vx, byte: b } => byte(0xC000, vx, b),
+            Instruction::Drw { vx, vy, n } => vv(0xD000, vx, vy) | n as u16 & 0xF,
+            Instruction::SkpV { vx } => v(0xE09E, vx),
+            Instruction::SknpV { vx } => v(0xE0A1, vx),
+            Instruction::LdVDt { vx } => v(0xF007, vx),
+            Instruction::LdVK { vx } => v(0xF00A, vx),
+            Instruction::LdDtV { vx } => v(0xF015, vx),
+            Instruction::LdStV { vx } => v(0xF018, vx),
+            Instruction::AddIV { vx } => v(0xF01E, vx),
+            Instruction::LdFV { vx } => v(0xF029, vx),
+            Instruction::LdBV { vx } => v(0xF033, vx),
+            Instruction::LdIV { vx } => v(0xF055, vx),
+            Instruction::LdVI { vx } => v(0xF065, vx),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Writes the instruction with the mnemonics from Cowgod's reference
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Jp { addr } => write!(f, "JP 0x{:03X}", addr),
+            Instruction::Call { addr } => write!(f, "CALL 0x{:03X}", addr),
+            Instruction::SeV { vx, byte } => write!(f, "SE V{:X}, 0x{:02X}", vx, byte),
+            Instruction::SneV { vx, byte } => write!(f, "SNE V{:X}, 0x{:02X}", vx, byte),
+            Instruction::SeVV { vx, vy } => write!(f, "SE V{:X}, V{:X}", vx, vy),
+            Instruction::LdV { vx, byte } => write!(f, "LD V{:X}, 0x{:02X}", vx, byte),
+            Instruction::AddV { vx, byte } => write!(f, "ADD V{:X}, 0x{:02X}", vx, byte),
+            Instruction::LdVV { vx, vy } => write!(f, "LD V{:X}, V{:X}", vx, vy),
+            Instruction::OrVV { vx, vy } => write!(f, "OR V{:X}, V{:X}", vx, vy),
+            Instruction::AndVV { vx, vy } => write!(f, "AND V{:X}, V{:X}", vx, vy),
+            Instruction::XorVV { vx, vy } => write!(f, "XOR V{:X}, V{:X}", vx, vy),
+            Instruction::AddVV { vx, vy } => write!(f, "ADD V{:X}, V{:X}", vx, vy),
+            Instruction::SubVV { vx, vy } => write!(f, "SUB V{:X}, V{:X}", vx, vy),
+            Instruction::ShrVV { vx, vy } => write!(f, "SHR V{:X}, V{:X}", vx, vy),
+            Instruction::SubnVV { vx, vy } => write!(f, "SUBN V{:X}, V{:X}", vx, vy),
+            Instruction::ShlVV { vx, vy } => write!(f, "SHL V{:X}, V{:X}", vx, vy),
+            Instruction::SneVV { vx, vy } => write!(f, "SNE V{:X}, V{:X}", vx, vy),
+            Instruction::LdI { addr } => write!(f, "LD I, 0x{:03X}", addr),
+            Instruction::JpV { addr } => write!(f, "JP V0, 0x{:03X}", addr),
+            Instruction::RndV { vx, byte } => write!(f, "RND V{:X}, 0x{:02X}", vx, byte),
+            Instruction::Drw { vx, vy, n } => write!(f, "DRW V{:X}, V{:X}, {}", vx, vy, n),
+            Instruction::SkpV { vx } => write!(f, "SKP V{:X}", vx),
+            Instruction::SknpV { vx } => write!(f, "SKNP V{:X}", vx),
+            Instruction::LdVDt { vx } => write!(f, "LD V{:X}, DT", vx),
+            Instruction::LdVK { vx } => write!(f, "LD V{:X}, K", vx),
+            Instruction::LdDtV { vx } => write!(f, "LD DT, V{:X}", vx),
+            Instruction::LdStV { vx } => write!(f, "LD ST, V{:X}", vx),
+            Instruction::AddIV { vx } => write!(f, "ADD I, V{:X}", vx),
+            Instruction::LdFV { vx } => write!(f, "LD F, V{:X}", vx),
+            Instruction::LdBV { vx } => write!(f, "LD B, V{:X}", vx),
+            Instruction::LdIV { vx } => write!(f, "LD [I], V{:X}", vx),
+            Instruction::LdVI { vx } => write!(f, "LD V{:X}, [I]", vx),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,4 +203,23 @@ mod tests {
         let result = Instruction::try_from(0x2999);
         assert!(matches!(result, Ok(Instruction::Call { addr: 0x999 })));
     }
+
+    #[test]
+    fn test_encode_every_opcode() {
+        for opcode in 0..=u16::MAX {
+            if let Ok(instruction) = Instruction::try_from(opcode) {
+                assert_eq!(u16::from(instruction), opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let instruction = Instruction::Drw {
+            vx: 0xA,
+            vy: 1,
+            n: 5,
+        };
+        assert_eq!(instruction.to_string(), "DRW VA, V1, 5");
+    }
 }
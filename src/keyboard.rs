@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 /// One of the hex keys on the Chip-8 keypad
 #[derive(Copy, Clone)]
 pub enum Key {
@@ -19,10 +21,12 @@ pub enum Key {
     F,
 }
 
-impl From<u8> for Key {
+impl TryFrom<u8> for Key {
+    type Error = String;
+
     /// Converts a byte into a Key
-    fn from(value: u8) -> Self {
-        match value {
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let key = match value {
             0x0 => Key::Num0,
             0x1 => Key::Num1,
             0x2 => Key::Num2,
@@ -39,8 +43,10 @@ impl From<u8> for Key {
             0xD => Key::D,
             0xE => Key::E,
             0xF => Key::F,
-            _ => panic!("Unsupported key {}", value),
-        }
+            _ => return Err(format!("Unsupported key {}", value)),
+        };
+
+        Ok(key)
     }
 }
 
@@ -88,4 +94,19 @@ impl Keyboard {
     pub fn press(&mut self, key: &Key) {
         self.pressed[*key as usize] = true;
     }
+
+    /// Returns the pressed keys as a mask with bit n set for key n
+    pub fn state(&self) -> u16 {
+        self.pressed
+            .iter()
+            .enumerate()
+            .fold(0, |state, (key, &pressed)| state | (pressed as u16) << key)
+    }
+
+    /// Presses exactly the keys set in a mask from `state`
+    pub fn set_state(&mut self, state: u16) {
+        for (key, pressed) in self.pressed.iter_mut().enumerate() {
+            *pressed = state >> key & 1 == 1;
+        }
+    }
 }
@@ -0,0 +1,20 @@
+pub mod analysis;
+pub mod assembler;
+pub mod audio;
+pub mod control;
+pub mod coverage;
+pub mod database;
+pub mod disassembler;
+pub mod display;
+pub mod emulator;
+pub mod font;
+pub mod gdb;
+pub mod gym;
+pub mod instruction;
+pub mod keyboard;
+pub mod memory;
+pub mod profile;
+pub mod quirks;
+pub mod recording;
+pub mod snapshot;
+pub mod trace;
@@ -1,105 +1,615 @@
 use std::{
-    convert::{TryFrom, TryInto},
+    collections::HashMap,
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    net::{TcpListener, TcpStream},
+    path::Path,
     time::{Duration, Instant},
 };
 
+use chip_8::{
+    analysis::Analysis,
+    assembler::assemble,
+    audio,
+    control::Session,
+    database::{self, Database, RomInfo},
+    disassembler::disassemble,
+    display,
+    emulator::{Emulator, StackStorage, VIP_STACK_LIMIT},
+    gdb::{self, GdbStub},
+    keyboard,
+    memory::PROGRAM_OFFSET,
+    profile::Profiler,
+    quirks::Platform,
+    recording::Recording,
+    trace::Tracer,
+};
 use sdl2::{
     audio::{AudioCallback, AudioSpecDesired, AudioStatus},
     event::Event,
     keyboard::Keycode,
     pixels::Color,
+    pixels::PixelFormatEnum,
     rect::Rect,
+    render::{BlendMode, Canvas, Texture},
+    video::{FullscreenType, Window},
+};
+
+use self::{
+    cli::{Command, Layout, Options, ProfileFormat, Target},
+    config::Config,
 };
 
-mod display;
-mod emulator;
-mod instruction;
-mod keyboard;
-mod memory;
+mod cli;
+mod config;
 
-const SCALE: usize = 20;
+const SCALE: u32 = 20;
+const FRAME_RATE: u32 = 60;
 const TARGET_FRAME_TIME: Duration = Duration::from_millis(16);
+const TICKS_PER_FRAME: usize = 8;
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+struct Buzzer(audio::SquareWave);
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Buzzer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
-    }
-}
-
-impl TryFrom<Keycode> for keyboard::Key {
-    type Error = ();
-
-    /// Maps between modern keyboard keys and Chip-8 hex keys
-    ///
-    /// ```
-    /// 1 2 3 4     1 2 3 C
-    /// q w e r --> 4 5 6 D
-    /// a s d f --> 7 8 9 E
-    /// z x c v     A 0 B F
-    /// ```
-    fn try_from(value: Keycode) -> Result<Self, Self::Error> {
-        match value {
-            Keycode::Num1 => Ok(keyboard::Key::Num1),
-            Keycode::Num2 => Ok(keyboard::Key::Num2),
-            Keycode::Num3 => Ok(keyboard::Key::Num3),
-            Keycode::Num4 => Ok(keyboard::Key::C),
-            Keycode::Q => Ok(keyboard::Key::Num4),
-            Keycode::W => Ok(keyboard::Key::Num5),
-            Keycode::E => Ok(keyboard::Key::Num6),
-            Keycode::R => Ok(keyboard::Key::D),
-            Keycode::A => Ok(keyboard::Key::Num7),
-            Keycode::S => Ok(keyboard::Key::Num8),
-            Keycode::D => Ok(keyboard::Key::Num9),
-            Keycode::F => Ok(keyboard::Key::E),
-            Keycode::Z => Ok(keyboard::Key::A),
-            Keycode::X => Ok(keyboard::Key::Num0),
-            Keycode::C => Ok(keyboard::Key::B),
-            Keycode::V => Ok(keyboard::Key::F),
-            _ => Err(()),
-        }
-    }
-}
-
-fn main() -> Result<(), String> {
+        self.0.fill(out);
+    }
+}
+
+/// The Chip-8 hex keys in the order they are laid out on the keypad
+const KEYPAD: [keyboard::Key; 16] = [
+    keyboard::Key::Num1,
+    keyboard::Key::Num2,
+    keyboard::Key::Num3,
+    keyboard::Key::C,
+    keyboard::Key::Num4,
+    keyboard::Key::Num5,
+    keyboard::Key::Num6,
+    keyboard::Key::D,
+    keyboard::Key::Num7,
+    keyboard::Key::Num8,
+    keyboard::Key::Num9,
+    keyboard::Key::E,
+    keyboard::Key::A,
+    keyboard::Key::Num0,
+    keyboard::Key::B,
+    keyboard::Key::F,
+];
+
+/// Maps between modern keyboard keys and Chip-8 hex keys
+///
+/// On a qwerty keyboard the keys are laid out as follows:
+///
+/// ```text
+/// 1 2 3 4     1 2 3 C
+/// q w e r --> 4 5 6 D
+/// a s d f --> 7 8 9 E
+/// z x c v     A 0 B F
+/// ```
+fn map_layout(layout: Layout) -> HashMap<Keycode, keyboard::Key> {
+    layout
+        .keys()
+        .iter()
+        .zip(KEYPAD.iter())
+        .filter_map(|(name, &key)| Some((Keycode::from_name(name)?, key)))
+        .collect()
+}
+
+/// The colours used to draw inactive and active pixels
+struct Palette {
+    background: Color,
+    foreground: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: Color::BLACK,
+            foreground: Color::WHITE,
+        }
+    }
+}
+
+impl Palette {
+    /// Uses a background and foreground colour
+    fn new(colors: &[[u8; 3]]) -> Option<Self> {
+        match colors {
+            [background, foreground, ..] => Some(Self {
+                background: Color::RGB(background[0], background[1], background[2]),
+                foreground: Color::RGB(foreground[0], foreground[1], foreground[2]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a rom after checking that the path points to one
+fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Unable to read rom {}: {}", path, e))?;
+
+    if !metadata.is_file() {
+        return Err(format!("Rom {} is not a file", path));
+    }
+
+    let rom = std::fs::read(path).map_err(|e| format!("Unable to read rom {}: {}", path, e))?;
+
+    if rom.is_empty() {
+        return Err(format!("Rom {} is empty", path));
+    }
+
+    Ok(rom)
+}
+
+/// Looks up a rom in the database given with `--database`, or otherwise the
+/// bundled one
+fn lookup(database: Option<&Path>, rom: &[u8]) -> Result<Option<RomInfo>, String> {
+    match database {
+        Some(directory) => Ok(Database::load(directory)?.lookup(rom)),
+        None => Ok(Database::bundled().lookup(rom)),
+    }
+}
+
+/// Creates the emulator for a rom and returns it with the number of
+/// instructions to run every frame
+///
+/// The speed and quirks given take priority over those of the platform.
+fn create_emulator(options: &Options, rom: &[u8]) -> Result<(Emulator, usize), String> {
+    let platform = options.platform;
+    let load_address = options.load_address.unwrap_or(PROGRAM_OFFSET);
+    let font = options.font.clone().unwrap_or_default();
+    let ticks_per_frame = options
+        .speed
+        .or(platform.map(Platform::tick_rate))
+        .unwrap_or(TICKS_PER_FRAME);
+    let quirks = options.quirks.or(platform.map(Platform::quirks));
+
+    let mut emulator = Emulator::with_load_address(rom, load_address)?;
+    emulator.set_font(&font)?;
+
+    if let Some(quirks) = quirks {
+        emulator.set_quirks(quirks);
+    }
+
+    match (options.stack_depth, platform) {
+        (Some(depth), _) => emulator.set_stack_limit(depth.limit()),
+        (None, Some(Platform::OriginalChip8 | Platform::HybridVip)) => {
+            emulator.set_stack_limit(Some(VIP_STACK_LIMIT))
+        }
+        (None, _) => (),
+    }
+
+    if options.stack_in_memory.unwrap_or_default() {
+        emulator.set_stack_storage(StackStorage::Memory);
+    }
+
+    if let Some(seed) = options.seed {
+        emulator.set_seed(seed);
+    }
+
+    if let Some(backend) = options.backend {
+        emulator.set_backend(backend);
+    }
+
+    if let Some(protection) = options.write_protection {
+        emulator.set_write_protection(protection);
+    }
+
+    Ok((emulator, ticks_per_frame))
+}
+
+/// Prints the next instruction and the registers for the debugger
+fn print_state(emulator: &Emulator) {
+    let program_counter = emulator.program_counter();
+    let listing = disassemble(
+        &[
+            emulator
+                .memory()
+                .get_byte(program_counter)
+                .unwrap_or_default(),
+            emulator
+                .memory()
+                .get_byte(program_counter + 1)
+                .unwrap_or_default(),
+        ],
+        program_counter,
+    );
+
+    println!("{}", listing.trim_end());
+    println!(
+        "    V={:02X?} I=0x{:03X} DT={} ST={}",
+        emulator.registers(),
+        emulator.i(),
+        emulator.delay_timer(),
+        emulator.sound_timer()
+    );
+}
+
+/// Opens the trace file if `--trace` was given
+fn create_tracer(options: &Options) -> Result<Option<Tracer<BufWriter<File>>>, String> {
+    match &options.trace {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("Unable to create trace: {}", e))?;
+            let mut tracer = Tracer::new(BufWriter::new(file));
+            tracer.set_address_filter(options.trace_addresses.clone());
+            tracer.set_cycle_filter(options.trace_cycles.clone());
+            Ok(Some(tracer))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Runs the emulator for a number of ticks, writing them to the trace and
+/// counting them in the profile if there are either
+fn step(
+    emulator: &mut Emulator,
+    tracer: &mut Option<Tracer<BufWriter<File>>>,
+    profiler: &mut Option<Profiler>,
+    display: &mut display::Display,
+    keyboard: &keyboard::Keyboard,
+    ticks: usize,
+) -> Result<(), String> {
+    let profiler = match profiler {
+        Some(profiler) => profiler,
+        None => {
+            return match tracer {
+                Some(tracer) => tracer.run(emulator, display, keyboard, ticks),
+                None => Ok(emulator.run(display, keyboard, ticks)?),
+            }
+        }
+    };
+
+    for _ in 0..ticks {
+        let program_counter = emulator.program_counter();
+        let opcode = emulator
+            .memory()
+            .get_instruction(program_counter)
+            .unwrap_or_default();
+
+        match tracer {
+            Some(tracer) => tracer.run(emulator, display, keyboard, 1)?,
+            None => emulator.tick(display, keyboard)?,
+        }
+
+        profiler.record(program_counter, opcode, emulator);
+    }
+
+    Ok(())
+}
+
+/// Prints the profile in the format chosen with `--profile`
+fn print_profile(options: &Options, profiler: &Option<Profiler>) -> Result<(), String> {
+    if let (Some(format), Some(profiler)) = (options.profile, profiler) {
+        match format {
+            ProfileFormat::Report => print!("{}", profiler.report()),
+            ProfileFormat::Folded => profiler
+                .write_folded(io::stdout().lock())
+                .map_err(|e| e.to_string())?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads the keys to replay if `--playback` was given
+fn load_playback(options: &Options) -> Result<Option<Recording>, String> {
+    match &options.playback {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read recording: {}", e))?;
+            Ok(Some(Recording::parse(&text)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Writes the keys pressed if `--record` was given
+fn save_recording(options: &Options, recording: &Recording) -> Result<(), String> {
+    if let Some(path) = &options.record {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        recording
+            .write(BufWriter::new(file))
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Writes the coverage map and annotated disassembly if they were asked for
+fn save_coverage(
+    options: &Options,
+    emulator: &Emulator,
+    rom: &[u8],
+    load_address: usize,
+) -> Result<(), String> {
+    let coverage = match emulator.coverage() {
+        Some(coverage) => coverage,
+        None => return Ok(()),
+    };
+
+    if let Some(path) = &options.coverage_map {
+        let file = File::create(path).map_err(|e| format!("Unable to create map: {}", e))?;
+        coverage
+            .write_map(BufWriter::new(file), load_address..load_address + rom.len())
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(path) = &options.coverage_asm {
+        std::fs::write(path, coverage.annotate(rom, load_address))
+            .map_err(|e| format!("Unable to write disassembly: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Runs a fixed number of frames without a window, saving every frame as a
+/// PBM image and the buzzer as a WAV file when an output directory is given
+fn run_headless(
+    emulator: &mut Emulator,
+    ticks_per_frame: usize,
+    options: &Options,
+) -> Result<(), String> {
+    let output_dir = options.output.as_deref();
+
+    if let Some(output_dir) = output_dir {
+        std::fs::create_dir_all(output_dir).map_err(|e| e.to_string())?;
+    }
+
+    let playback = load_playback(options)?;
+    let mut tracer = create_tracer(options)?;
+    let mut profiler = options
+        .profile
+        .map(|_| Profiler::new(emulator.program_counter()));
+    let mut recording = Recording::default();
+    let mut display = display::Display::default();
+    let mut keyboard = keyboard::Keyboard::default();
+    let mut audio = audio::WavRecorder::new(FRAME_RATE);
+    audio.set_volume(options.volume.unwrap_or(audio::VOLUME));
+
+    for frame in 0..options.frames.unwrap_or_default() {
+        if let Some(playback) = &playback {
+            playback.play(frame, &mut keyboard);
+        }
+
+        recording.record(frame, &keyboard);
+        emulator.decrement_timers();
+        step(
+            emulator,
+            &mut tracer,
+            &mut profiler,
+            &mut display,
+            &keyboard,
+            ticks_per_frame,
+        )?;
+
+        if let Some(profiler) = &mut profiler {
+            profiler.end_frame();
+        }
+
+        audio.record_frame(emulator.is_sound_playing() && !options.mute.unwrap_or_default());
+
+        if let Some(output_dir) = output_dir {
+            save_frame(
+                &display,
+                &output_dir.join(format!("frame_{:05}.pbm", frame)),
+            )?;
+        }
+    }
+
+    if let Some(output_dir) = output_dir {
+        let file = File::create(output_dir.join("audio.wav")).map_err(|e| e.to_string())?;
+        audio
+            .write(BufWriter::new(file))
+            .map_err(|e| e.to_string())?;
+    }
+
+    save_recording(options, &recording)?;
+    print_profile(options, &profiler)
+}
+
+/// Writes the display to a PBM image
+fn save_frame(display: &display::Display, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+
+    display
+        .write_pbm(BufWriter::new(file))
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the largest area with an integer scale of the display that fits
+/// in the window, centred so that the remaining space is letterboxed
+fn calculate_viewport(window_width: u32, window_height: u32) -> Rect {
+    let scale = (window_width / display::WIDTH as u32)
+        .min(window_height / display::HEIGHT as u32)
+        .max(1);
+    let width = display::WIDTH as u32 * scale;
+    let height = display::HEIGHT as u32 * scale;
+
+    Rect::new(
+        (window_width as i32 - width as i32) / 2,
+        (window_height as i32 - height as i32) / 2,
+        width,
+        height,
+    )
+}
+
+/// Switches between a desktop fullscreen window and a normal window
+fn toggle_fullscreen(canvas: &mut Canvas<Window>) -> Result<(), String> {
+    let window = canvas.window_mut();
+
+    match window.fullscreen_state() {
+        FullscreenType::Off => window.set_fullscreen(FullscreenType::Desktop),
+        _ => window.set_fullscreen(FullscreenType::Off),
+    }
+}
+
+/// Copies the display into the texture with one texel for each pixel
+fn upload_display(
+    texture: &mut Texture,
+    display: &display::Display,
+    palette: &Palette,
+) -> Result<(), String> {
+    texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+        for (y, row) in display.rows().iter().enumerate() {
+            for x in 0..display::WIDTH {
+                let offset = y * pitch + x * 3;
+                let active = row >> (display::WIDTH - 1 - x) & 1 == 1;
+                let color = if active {
+                    palette.foreground
+                } else {
+                    palette.background
+                };
+                buffer[offset..offset + 3].copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+    })
+}
+
+/// Draws the display scaled into the viewport along with any overlays
+fn render(
+    canvas: &mut Canvas<Window>,
+    texture: &mut Texture,
+    display: &mut display::Display,
+    palette: &Palette,
+    options: &Options,
+) -> Result<(), String> {
+    let (window_width, window_height) = canvas.output_size()?;
+    let viewport = calculate_viewport(window_width, window_height);
+    let scale = viewport.width() / display::WIDTH as u32;
+
+    // Locking a texture discards its contents so the whole screen is
+    // uploaded whenever any row changes
+    if display.is_dirty() {
+        upload_display(texture, display, palette)?;
+        display.mark_clean();
+    }
+
+    canvas.set_draw_color(palette.background);
+    canvas.clear();
+    canvas.copy(texture, None, viewport)?;
+
+    if options.grid.unwrap_or_default() && 2 < scale {
+        canvas.set_draw_color(Color::RGB(0x20, 0x20, 0x20));
+
+        for x in 0..=display::WIDTH as i32 {
+            let x = viewport.x() + x * scale as i32;
+            canvas.draw_line((x, viewport.top()), (x, viewport.bottom()))?;
+        }
+
+        for y in 0..=display::HEIGHT as i32 {
+            let y = viewport.y() + y * scale as i32;
+            canvas.draw_line((viewport.left(), y), (viewport.right(), y))?;
+        }
+    }
+
+    if options.scanlines.unwrap_or_default() && 1 < scale {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0x00, 0x00, 0x00, 0x60));
+
+        for y in (viewport.top()..viewport.bottom()).step_by(2) {
+            canvas.draw_line((viewport.left(), y), (viewport.right(), y))?;
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+
+    canvas.present();
+
+    Ok(())
+}
+
+/// Waits for a GDB client to connect on the port of the local machine
+fn connect_gdb(port: u16) -> Result<GdbStub<TcpStream>, String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Unable to listen for GDB on port {}: {}", port, e))?;
+
+    eprintln!("Waiting for GDB on 127.0.0.1:{}", port);
+
+    let (stream, address) = listener.accept().map_err(|e| e.to_string())?;
+    stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+
+    eprintln!("GDB connected from {}", address);
+
+    Ok(GdbStub::new(stream))
+}
+
+/// Runs the rom in a window until it is closed
+fn run_window(
+    emulator: &mut Emulator,
+    ticks_per_frame: usize,
+    options: &Options,
+    info: Option<&RomInfo>,
+) -> Result<(), String> {
+    let palette = options
+        .palette
+        .and_then(|palette| Palette::new(&palette))
+        .unwrap_or_default();
+    let frame_time = options
+        .frame_time
+        .map_or(TARGET_FRAME_TIME, Duration::from_millis);
+    let mut keymap = map_layout(options.layout.unwrap_or(Layout::Qwerty));
+
+    for (name, &key) in &options.bindings {
+        let keycode = Keycode::from_name(name).ok_or(format!("Unknown key {}", name))?;
+
+        let key = keyboard::Key::try_from(key).map_err(|_| {
+            format!(
+                "Key {} is bound to 0x{:X} which is not a hex key",
+                name, key
+            )
+        })?;
+
+        keymap.insert(keycode, key);
+    }
+
+    let playback = load_playback(options)?;
+    let mut tracer = create_tracer(options)?;
+    let mut profiler = options
+        .profile
+        .map(|_| Profiler::new(emulator.program_counter()));
+    let mut recording = Recording::default();
+    let mut gdb = options.gdb.map(connect_gdb).transpose()?;
+
     let sdl_context = sdl2::init()?;
-    let audio_subsystem = sdl_context.audio()?;
     let video_subsystem = sdl_context.video()?;
 
-    let audio_spec = AudioSpecDesired {
-        freq: Some(44100),
-        channels: Some(1),
-        samples: None,
-    };
+    let audio_device = if options.mute.unwrap_or_default() {
+        None
+    } else {
+        let audio_spec = AudioSpecDesired {
+            freq: Some(audio::SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: None,
+        };
 
-    let audio_device = audio_subsystem.open_playback(None, &audio_spec, |spec| SquareWave {
-        phase_inc: 440.0 / spec.freq as f32,
-        phase: 0.0,
-        volume: 0.5,
-    })?;
+        Some(
+            sdl_context
+                .audio()?
+                .open_playback(None, &audio_spec, |spec| {
+                    Buzzer(audio::SquareWave::new(
+                        audio::FREQUENCY,
+                        spec.freq as u32,
+                        options.volume.unwrap_or(audio::VOLUME),
+                    ))
+                })?,
+        )
+    };
 
     let window = video_subsystem
         .window(
-            "Chip-8",
-            (display::WIDTH * SCALE) as u32,
-            (display::HEIGHT * SCALE) as u32,
+            &match info {
+                Some(info) => format!("Chip-8 - {}", info.title),
+                None => "Chip-8".to_string(),
+            },
+            display::WIDTH as u32 * options.scale.unwrap_or(SCALE),
+            display::HEIGHT as u32 * options.scale.unwrap_or(SCALE),
         )
         .position_centered()
+        .resizable()
         .build()
         .expect("Could not create a window");
 
@@ -110,13 +620,31 @@ fn main() -> Result<(), String> {
 
     let mut event_pump = sdl_context.event_pump()?;
 
-    let args: Vec<String> = std::env::args().collect();
-    let path = &args[1];
-    let rom = std::fs::read(path).expect("Unable to read rom");
+    canvas
+        .window_mut()
+        .set_minimum_size(display::WIDTH as u32, display::HEIGHT as u32)
+        .map_err(|e| e.to_string())?;
+
+    // Keep pixels sharp when the texture is scaled up
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            display::WIDTH as u32,
+            display::HEIGHT as u32,
+        )
+        .map_err(|e| e.to_string())?;
 
-    let mut emulator = emulator::Emulator::new(&rom);
     let mut display = display::Display::default();
     let mut keyboard = keyboard::Keyboard::default();
+    let mut paused = options.debugger;
+    let mut frame = 0;
+
+    if paused {
+        print_state(emulator);
+    }
 
     'is_running: loop {
         let frame_start = Instant::now();
@@ -124,18 +652,50 @@ fn main() -> Result<(), String> {
         // Input
         for event in event_pump.poll_iter() {
             match event {
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(keycode) = keycode {
-                        if let Ok(key) = keycode.try_into() {
-                            keyboard.press(&key);
-                        }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => toggle_fullscreen(&mut canvas)?,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } if options.debugger => {
+                    paused = !paused;
+
+                    if paused {
+                        print_state(emulator);
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    ..
+                } if paused => {
+                    step(
+                        emulator,
+                        &mut tracer,
+                        &mut profiler,
+                        &mut display,
+                        &keyboard,
+                        1,
+                    )?;
+                    print_state(emulator);
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = keymap.get(&keycode) {
+                        keyboard.press(key);
                     }
                 }
-                Event::KeyUp { keycode, .. } => {
-                    if let Some(keycode) = keycode {
-                        if let Ok(key) = keycode.try_into() {
-                            keyboard.release(&key);
-                        }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = keymap.get(&keycode) {
+                        keyboard.release(key);
                     }
                 }
                 Event::Quit { .. } => break 'is_running,
@@ -143,47 +703,216 @@ fn main() -> Result<(), String> {
             }
         }
 
-        emulator.decrement_timers();
+        if let Some(stub) = &mut gdb {
+            stub.poll(emulator, &mut display, &keyboard)
+                .map_err(|e| format!("GDB connection failed: {}", e))?;
 
-        // Update at 500Hz
-        for _ in 0..8 {
-            emulator.tick(&mut display, &keyboard)?;
+            match stub.state() {
+                gdb::State::Detached => gdb = None,
+                gdb::State::Killed => break 'is_running,
+                gdb::State::Stopped | gdb::State::Running => {}
+            }
         }
 
-        // Sound
-        if emulator.is_sound_playing() && audio_device.status() != AudioStatus::Playing {
-            audio_device.resume();
-        } else if !emulator.is_sound_playing() && audio_device.status() != AudioStatus::Paused {
-            audio_device.pause();
+        let running = !paused
+            && gdb
+                .as_ref()
+                .is_none_or(|stub| stub.state() == gdb::State::Running);
+
+        if running {
+            if let Some(playback) = &playback {
+                playback.play(frame, &mut keyboard);
+            }
+
+            recording.record(frame, &keyboard);
+            frame += 1;
+
+            emulator.decrement_timers();
+
+            // Update at 500Hz unless the rom needs a different speed
+            match &mut gdb {
+                Some(stub) => stub
+                    .run(emulator, &mut display, &keyboard, ticks_per_frame)
+                    .map_err(|e| format!("GDB connection failed: {}", e))?,
+                None => step(
+                    emulator,
+                    &mut tracer,
+                    &mut profiler,
+                    &mut display,
+                    &keyboard,
+                    ticks_per_frame,
+                )?,
+            }
+
+            if let Some(profiler) = &mut profiler {
+                profiler.end_frame();
+            }
         }
 
-        // Render at 62.5Hz
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        canvas.set_draw_color(Color::WHITE);
-
-        for x in 0..display::WIDTH {
-            for y in 0..display::HEIGHT {
-                if display.get_pixel(x, y) {
-                    canvas.fill_rect(Rect::new(
-                        (x * SCALE) as i32,
-                        (y * SCALE) as i32,
-                        SCALE as u32,
-                        SCALE as u32,
-                    ))?;
-                }
+        // Sound
+        if let Some(audio_device) = &audio_device {
+            let is_sound_playing = emulator.is_sound_playing() && running;
+
+            if is_sound_playing && audio_device.status() != AudioStatus::Playing {
+                audio_device.resume();
+            } else if !is_sound_playing && audio_device.status() != AudioStatus::Paused {
+                audio_device.pause();
             }
         }
 
-        canvas.present();
+        // Render at 62.5Hz
+        render(&mut canvas, &mut texture, &mut display, &palette, options)?;
 
         // Framerate
         let elapsed = frame_start.elapsed();
 
-        if elapsed < TARGET_FRAME_TIME {
-            std::thread::sleep(TARGET_FRAME_TIME - elapsed);
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+
+    save_recording(options, &recording)?;
+    print_profile(options, &profiler)
+}
+
+/// Reads a rom and analyses its control flow from its load address, which
+/// is looked up in the database when not given
+fn analyse(target: &Target) -> Result<(Vec<u8>, Analysis), String> {
+    let rom = read_rom(&target.path)?;
+    let load_address = match target.load_address {
+        Some(load_address) => load_address,
+        None => lookup(target.database.as_deref(), &rom)?
+            .and_then(|info| info.start_address)
+            .unwrap_or(PROGRAM_OFFSET),
+    };
+    let analysis = Analysis::new(&rom, load_address);
+
+    Ok((rom, analysis))
+}
+
+/// Answers JSON requests on stdin, or from clients connecting to the port
+/// on the local machine one after another, until one of them quits
+fn serve_control(port: Option<u16>) -> Result<(), String> {
+    let mut session = Session::default();
+
+    let port = match port {
+        Some(port) => port,
+        None => {
+            return session
+                .serve(io::stdin().lock(), io::stdout().lock())
+                .map_err(|e| e.to_string())
+        }
+    };
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Unable to listen on port {}: {}", port, e))?;
+
+    eprintln!("Listening on 127.0.0.1:{}", port);
+
+    while !session.is_finished() {
+        let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+        // A client failing only ends its own connection
+        if let Err(e) = session.serve(reader, stream) {
+            eprintln!("Connection failed: {}", e);
         }
     }
 
     Ok(())
 }
+
+/// Prints what is known about a rom
+fn print_info(path: &str, database: Option<&Path>) -> Result<(), String> {
+    let rom = read_rom(path)?;
+
+    println!("Size: {} bytes", rom.len());
+    println!("SHA-1: {}", database::hash(&rom));
+
+    match lookup(database, &rom)? {
+        Some(info) => {
+            println!("Title: {}", info.title);
+
+            if !info.authors.is_empty() {
+                println!("Authors: {}", info.authors.join(", "));
+            }
+
+            if let Some(platform) = info.platform {
+                println!("Platform: {}", platform);
+            }
+
+            if let Some(tick_rate) = info.tick_rate {
+                println!("Speed: {} instructions a frame", tick_rate);
+            }
+
+            if let Some(start_address) = info.start_address {
+                println!("Load address: 0x{:03X}", start_address);
+            }
+        }
+        None => println!("Not found in the database"),
+    }
+
+    Ok(())
+}
+
+/// Carries out the command given on the command line
+fn execute(command: Command) -> Result<(), String> {
+    match command {
+        Command::Run(mut options) => {
+            let rom = read_rom(&options.path)?;
+
+            let config = Config::load(options.config.as_deref())?;
+
+            // Sections for the rom outrank the database, which outranks the
+            // defaults
+            config.settings(&options.path, &rom).apply(&mut options)?;
+            let database = options.database.as_deref().or(config.defaults().database());
+            let info = lookup(database, &rom)?;
+            config::apply_database(&mut options, info.as_ref());
+            config.defaults().apply(&mut options)?;
+
+            let (mut emulator, ticks_per_frame) = create_emulator(&options, &rom)?;
+            let load_address = emulator.program_counter();
+
+            if options.coverage_map.is_some() || options.coverage_asm.is_some() {
+                emulator.enable_coverage();
+            }
+
+            let result = if options.headless {
+                run_headless(&mut emulator, ticks_per_frame, &options)
+            } else {
+                run_window(&mut emulator, ticks_per_frame, &options, info.as_ref())
+            };
+
+            // Save the coverage even when the program faulted to show how far
+            // it got
+            save_coverage(&options, &emulator, &rom, load_address)?;
+            result?;
+        }
+        Command::Disasm(target) => {
+            let (rom, analysis) = analyse(&target)?;
+            print!("{}", analysis.annotate(&rom));
+        }
+        Command::Analyze(target) => print!("{}", analyse(&target)?.1.report()),
+        Command::Cfg(target) => print!("{}", analyse(&target)?.1.to_dot()),
+        Command::Asm { source, output } => {
+            let source = std::fs::read_to_string(&source)
+                .map_err(|e| format!("Unable to read source: {}", e))?;
+            let rom = assemble(&source)?;
+
+            std::fs::write(&output, rom).map_err(|e| format!("Unable to write rom: {}", e))?;
+        }
+        Command::Info { path, database } => print_info(&path, database.as_deref())?,
+        Command::Control { port } => serve_control(port)?,
+        Command::Help => print!("{}", cli::USAGE),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(message) = cli::parse(std::env::args().skip(1)).and_then(execute) {
+        eprintln!("{}", message.trim_end());
+        std::process::exit(1);
+    }
+}
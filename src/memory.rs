@@ -1,169 +1,328 @@
+use std::{convert::TryFrom, fmt};
+
+use crate::{
+    emulator::Fault,
+    font::{Font, DIGIT_AMOUNT, DIGIT_SPRITE_LENGTH, FONT_SIZE},
+    instruction::Instruction,
+};
+
 pub const PROGRAM_OFFSET: usize = 0x200;
 
-const MEMORY_SIZE: usize = 0x1000;
-const DIGITS_OFFSET: usize = 0x000;
-const DIGIT_AMOUNT: usize = 16;
-const DIGIT_SPRITE_LENGTH: usize = 5;
-
-#[rustfmt::skip]
-const DIGITS: [u8; DIGIT_AMOUNT * DIGIT_SPRITE_LENGTH] = [
-    // 0
-    0b11110000,
-    0b10010000,
-    0b10010000,
-    0b10010000,
-    0b11110000,
-    // 1
-    0b00100000,
-    0b01100000,
-    0b00100000,
-    0b00100000,
-    0b01110000,
-    // 2
-    0b11110000,
-    0b00010000,
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    // 3
-    0b11110000,
-    0b00010000,
-    0b11110000,
-    0b00010000,
-    0b11110000,
-    // 4
-    0b10010000,
-    0b10010000,
-    0b11110000,
-    0b00010000,
-    0b00010000,
-    // 5
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    0b00010000,
-    0b11110000,
-    // 6
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    0b10010000,
-    0b11110000,
-    // 7
-    0b11110000,
-    0b00010000,
-    0b00100000,
-    0b01000000,
-    0b01000000,
-    // 8
-    0b11110000,
-    0b10010000,
-    0b11110000,
-    0b10010000,
-    0b11110000,
-    // 9
-    0b11110000,
-    0b10010000,
-    0b11110000,
-    0b00010000,
-    0b11110000,
-    // A
-    0b11110000,
-    0b10010000,
-    0b11110000,
-    0b10010000,
-    0b10010000,
-    // B
-    0b11100000,
-    0b10010000,
-    0b11100000,
-    0b10010000,
-    0b11100000,
-    // C
-    0b11110000,
-    0b10000000,
-    0b10000000,
-    0b10000000,
-    0b11110000,
-    // D
-    0b11100000,
-    0b10010000,
-    0b10010000,
-    0b10010000,
-    0b11100000,
-    // E
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    // F
-    0b11110000,
-    0b10000000,
-    0b11110000,
-    0b10000000,
-    0b10000000,
-];
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const STACK_OFFSET: usize = 0xEA0;
+pub const STACK_SIZE: usize = 0x60;
+
+/// An error which prevents a rom from being loaded into memory
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// The rom does not fit between the load address and the end of memory
+    RomTooLarge { size: usize, capacity: usize },
+    /// The load address is not inside memory
+    InvalidLoadAddress(usize),
+    /// The font does not fit between its offset and the end of memory
+    InvalidFontOffset(usize),
+    /// The saved state does not fit the memory, screen or stack
+    InvalidState,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::RomTooLarge { size, capacity } => write!(
+                f,
+                "Rom is {} bytes but only {} bytes are available",
+                size, capacity
+            ),
+            LoadError::InvalidLoadAddress(address) => {
+                write!(f, "Invalid load address 0x{:X}", address)
+            }
+            LoadError::InvalidFontOffset(offset) => {
+                write!(f, "Invalid font offset 0x{:X}", offset)
+            }
+            LoadError::InvalidState => write!(f, "Saved state does not fit the emulator"),
+        }
+    }
+}
+
+impl From<LoadError> for String {
+    fn from(error: LoadError) -> Self {
+        error.to_string()
+    }
+}
 
 /// Stores the current state of the Chip-8 memory
 pub struct Memory {
     memory: [u8; MEMORY_SIZE],
+    font_offset: usize,
+    decoded: Option<Vec<Option<Instruction>>>,
+    writes: Option<Vec<usize>>,
 }
 
 impl Memory {
-    /// Loads a Chip-8 rom into memory
+    /// Loads a Chip-8 rom into memory starting at the load address
     ///
     /// The returned Memory struct contains the rom loaded into memory and also
     /// the sprites for the digits that exist in the area specifically reserved
     /// for the interpreter.
-    pub fn new(rom: &[u8]) -> Self {
+    pub fn new(rom: &[u8], load_address: usize) -> Result<Self, LoadError> {
+        if MEMORY_SIZE <= load_address {
+            return Err(LoadError::InvalidLoadAddress(load_address));
+        }
+
+        let capacity = MEMORY_SIZE - load_address;
+
+        if capacity < rom.len() {
+            return Err(LoadError::RomTooLarge {
+                size: rom.len(),
+                capacity,
+            });
+        }
+
+        let font = Font::default();
         let mut memory = [0; MEMORY_SIZE];
 
-        for (index, &byte) in DIGITS.iter().enumerate() {
-            memory[DIGITS_OFFSET + index] = byte;
+        memory[font.offset()..font.offset() + FONT_SIZE].copy_from_slice(font.glyphs());
+        memory[load_address..load_address + rom.len()].copy_from_slice(rom);
+
+        Ok(Self {
+            memory,
+            font_offset: font.offset(),
+            decoded: None,
+            writes: None,
+        })
+    }
+
+    /// Copies the sprites of a different font into memory at its offset
+    ///
+    /// The sprites of the previous font are left in place in case the program
+    /// has been loaded over them.
+    pub fn load_font(&mut self, font: &Font) -> Result<(), LoadError> {
+        if MEMORY_SIZE < font.offset() + FONT_SIZE {
+            return Err(LoadError::InvalidFontOffset(font.offset()));
         }
 
-        for (index, &byte) in rom.iter().enumerate() {
-            memory[PROGRAM_OFFSET + index] = byte;
+        for (index, &byte) in font.glyphs().iter().enumerate() {
+            self.set_byte(font.offset() + index, byte)
+                .map_err(|_| LoadError::InvalidFontOffset(font.offset()))?;
         }
 
-        Self { memory }
+        self.font_offset = font.offset();
+
+        Ok(())
     }
 
-    /// Returns the position in memory for the given digit
-    ///
-    /// # Panics
+    /// Starts remembering decoded instructions by their offset
     ///
-    /// This function will panic if there is no sprite available for the given
-    /// digit.
-    pub fn calculate_digit_offset(digit: u8) -> usize {
+    /// Cached instructions are forgotten whenever either of their bytes is
+    /// replaced with `set_byte` so that self-modifying programs still work.
+    pub fn enable_decode_cache(&mut self) {
+        if self.decoded.is_none() {
+            self.decoded = Some(vec![None; MEMORY_SIZE]);
+        }
+    }
+
+    /// Starts recording the offset of every byte replaced with `set_byte`
+    pub fn enable_write_log(&mut self) {
+        if self.writes.is_none() {
+            self.writes = Some(vec![]);
+        }
+    }
+
+    /// Stops recording writes and forgets any that have not been drained
+    pub fn disable_write_log(&mut self) {
+        self.writes = None;
+    }
+
+    /// Returns the offsets written to since the write log was last drained
+    pub fn drain_writes(&mut self) -> impl Iterator<Item = usize> + '_ {
+        self.writes.iter_mut().flat_map(|writes| writes.drain(..))
+    }
+
+    /// Returns every byte of memory from address 0
+    pub fn bytes(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Returns where the sprites for the hex digits start
+    pub fn font_offset(&self) -> usize {
+        self.font_offset
+    }
+
+    /// Replaces every byte of memory and the position of the font, such as
+    /// when loading a saved state
+    pub fn restore(&mut self, bytes: &[u8], font_offset: usize) -> Result<(), LoadError> {
+        if bytes.len() != MEMORY_SIZE || MEMORY_SIZE < font_offset + FONT_SIZE {
+            return Err(LoadError::InvalidState);
+        }
+
+        for (offset, &byte) in bytes.iter().enumerate() {
+            if self.memory[offset] != byte {
+                self.set_byte(offset, byte)
+                    .map_err(|_| LoadError::InvalidState)?;
+            }
+        }
+
+        self.font_offset = font_offset;
+
+        Ok(())
+    }
+
+    /// Returns the position in memory for the given digit, or `None` if
+    /// there is no sprite for it
+    pub fn calculate_digit_offset(&self, digit: u8) -> Option<usize> {
         if (digit as usize) < DIGIT_AMOUNT {
-            DIGITS_OFFSET + digit as usize * DIGIT_SPRITE_LENGTH
+            Some(self.font_offset + digit as usize * DIGIT_SPRITE_LENGTH)
         } else {
-            panic!("No sprite for digit {}", digit)
+            None
         }
     }
 
     /// Returns the byte stored at the given offset
-    pub fn get_byte(&self, offset: usize) -> u8 {
-        self.memory[offset]
+    pub fn get_byte(&self, offset: usize) -> Result<u8, Fault> {
+        self.memory
+            .get(offset)
+            .copied()
+            .ok_or(Fault::InvalidAddress(offset))
     }
 
     /// Returns the two-byte instruction stored at the given offset
-    pub fn get_instruction(&self, offset: usize) -> u16 {
-        let byte_1 = self.memory[offset];
-        let byte_2 = self.memory[offset + 1];
+    pub fn get_instruction(&self, offset: usize) -> Result<u16, Fault> {
+        let byte_1 = self.get_byte(offset)?;
+        let byte_2 = self.get_byte(offset + 1)?;
+
+        Ok((byte_1 as u16) << 8 | byte_2 as u16)
+    }
+
+    /// Returns the parsed instruction stored at the given offset
+    pub fn decode_instruction(&mut self, offset: usize) -> Result<Instruction, Fault> {
+        if let Some(Some(instruction)) = self
+            .decoded
+            .as_ref()
+            .and_then(|decoded| decoded.get(offset))
+        {
+            return Ok(*instruction);
+        }
+
+        let instruction = Instruction::try_from(self.get_instruction(offset)?)?;
+
+        if let Some(decoded) = self.decoded.as_mut() {
+            decoded[offset] = Some(instruction);
+        }
 
-        (byte_1 as u16) << 8 | byte_2 as u16
+        Ok(instruction)
     }
 
     /// Returns the bytes from the given offset to be interpreted as a sprite
-    pub fn get_sprite(&self, offset: usize, n: usize) -> &[u8] {
-        &self.memory[offset..offset + n]
+    pub fn get_sprite(&self, offset: usize, n: usize) -> Result<&[u8], Fault> {
+        self.memory
+            .get(offset..offset + n)
+            .ok_or(Fault::InvalidAddress(offset.max(MEMORY_SIZE)))
     }
 
     /// Replaces the byte at the given offset with the new value
-    pub fn set_byte(&mut self, offset: usize, byte: u8) {
-        self.memory[offset] = byte;
+    pub fn set_byte(&mut self, offset: usize, byte: u8) -> Result<(), Fault> {
+        *self
+            .memory
+            .get_mut(offset)
+            .ok_or(Fault::InvalidAddress(offset))? = byte;
+
+        if let Some(writes) = self.writes.as_mut() {
+            writes.push(offset);
+        }
+
+        if let Some(decoded) = self.decoded.as_mut() {
+            decoded[offset] = None;
+
+            if 0 < offset {
+                decoded[offset - 1] = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::FontSet;
+
+    #[test]
+    fn test_new_largest_rom() {
+        let rom = [0xAA; MEMORY_SIZE - PROGRAM_OFFSET];
+
+        let memory = Memory::new(&rom, PROGRAM_OFFSET).unwrap();
+
+        assert_eq!(memory.get_byte(MEMORY_SIZE - 1), Ok(0xAA));
+    }
+
+    #[test]
+    fn test_new_rom_too_large() {
+        let rom = [0xAA; MEMORY_SIZE - PROGRAM_OFFSET + 1];
+
+        let result = Memory::new(&rom, PROGRAM_OFFSET);
+
+        assert!(matches!(
+            result,
+            Err(LoadError::RomTooLarge {
+                size: 3585,
+                capacity: 3584
+            })
+        ));
+    }
+
+    #[test]
+    fn test_load_font() {
+        let mut memory = Memory::new(&[], PROGRAM_OFFSET).unwrap();
+        let font = Font::new(FontSet::Vip).with_offset(0x50);
+
+        memory.load_font(&font).unwrap();
+
+        assert_eq!(memory.calculate_digit_offset(0x1), Some(0x55));
+        assert_eq!(
+            memory.get_sprite(0x55, 5),
+            Ok(&[0x60, 0x20, 0x20, 0x20, 0x70][..])
+        );
+    }
+
+    #[test]
+    fn test_load_font_invalid_offset() {
+        let mut memory = Memory::new(&[], PROGRAM_OFFSET).unwrap();
+        let font = Font::default().with_offset(MEMORY_SIZE - 79);
+
+        let result = memory.load_font(&font);
+
+        assert_eq!(result, Err(LoadError::InvalidFontOffset(MEMORY_SIZE - 79)));
+    }
+
+    #[test]
+    fn test_new_load_address() {
+        let memory = Memory::new(&[0x12, 0x34], 0x600).unwrap();
+
+        assert_eq!(memory.get_instruction(0x600), Ok(0x1234));
+        assert_eq!(memory.get_instruction(PROGRAM_OFFSET), Ok(0x0000));
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut memory = Memory::new(&[], PROGRAM_OFFSET).unwrap();
+
+        assert_eq!(
+            memory.get_byte(MEMORY_SIZE),
+            Err(Fault::InvalidAddress(0x1000))
+        );
+        assert_eq!(
+            memory.get_instruction(MEMORY_SIZE - 1),
+            Err(Fault::InvalidAddress(0x1000))
+        );
+        assert_eq!(
+            memory.get_sprite(0xFFE, 5),
+            Err(Fault::InvalidAddress(0x1000))
+        );
+        assert_eq!(
+            memory.set_byte(0xFFFF, 0x1),
+            Err(Fault::InvalidAddress(0xFFFF))
+        );
+        assert_eq!(memory.calculate_digit_offset(0x10), None);
     }
 }
@@ -0,0 +1,334 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use crate::{display::Display, emulator::Emulator, instruction::Instruction, keyboard::Keyboard};
+
+/// The number of addresses listed in the hotspots of the report
+const HOTSPOTS: usize = 20;
+
+/// Counts where a program spends its time
+///
+/// Every instruction is counted by its address and by its kind, and by the
+/// chain of subroutines it ran in, which is followed through `CALL` and
+/// `RET`. The draws on every frame are counted as well so that the work done
+/// between frames can be compared with the speed the rom needs.
+pub struct Profiler {
+    instructions: u64,
+    addresses: HashMap<usize, (u64, u16)>,
+    kinds: HashMap<&'static str, u64>,
+    stack: Vec<usize>,
+    stacks: HashMap<Vec<usize>, u64>,
+    calls: HashMap<usize, u64>,
+    draws: u64,
+    frames: Vec<u64>,
+}
+
+impl Profiler {
+    /// Creates a profiler for a program starting at the given address
+    pub fn new(start_address: usize) -> Self {
+        Self {
+            instructions: 0,
+            addresses: HashMap::new(),
+            kinds: HashMap::new(),
+            stack: vec![start_address],
+            stacks: HashMap::new(),
+            calls: HashMap::new(),
+            draws: 0,
+            frames: vec![],
+        }
+    }
+
+    /// Evaluates the given number of CPU instructions one at a time,
+    /// counting each one
+    pub fn run(
+        &mut self,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+        ticks: usize,
+    ) -> Result<(), String> {
+        for _ in 0..ticks {
+            let program_counter = emulator.program_counter();
+            let opcode = emulator
+                .memory()
+                .get_instruction(program_counter)
+                .unwrap_or_default();
+
+            emulator.tick(display, keyboard)?;
+            self.record(program_counter, opcode, emulator);
+        }
+
+        Ok(())
+    }
+
+    /// Counts an instruction after the emulator has executed it
+    pub fn record(&mut self, program_counter: usize, opcode: u16, emulator: &Emulator) {
+        let instruction = match Instruction::try_from(opcode) {
+            Ok(instruction) => instruction,
+            Err(_) => return,
+        };
+
+        self.instructions += 1;
+        self.addresses
+            .entry(program_counter)
+            .or_insert((0, opcode))
+            .0 += 1;
+        *self.kinds.entry(kind(instruction)).or_default() += 1;
+        *self.stacks.entry(self.stack.clone()).or_default() += 1;
+
+        match instruction {
+            Instruction::Call { addr } => {
+                self.stack.push(addr);
+                *self.calls.entry(addr).or_default() += 1;
+            }
+            Instruction::Ret if 1 < self.stack.len() => {
+                self.stack.pop();
+            }
+            // Draws waiting for the vertical blank run again next frame
+            Instruction::Drw { .. } if emulator.program_counter() != program_counter => {
+                self.draws += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// Marks the end of a frame, keeping the number of draws made during it
+    pub fn end_frame(&mut self) {
+        self.frames.push(self.draws);
+        self.draws = 0;
+    }
+
+    /// Describes the busiest addresses, kinds of instruction and subroutines
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let percent = |count: u64| count as f64 * 100.0 / self.instructions.max(1) as f64;
+
+        writeln!(report, "Instructions: {}", self.instructions).unwrap();
+
+        if !self.frames.is_empty() {
+            let frames = self.frames.len() as u64;
+            let draws: u64 = self.frames.iter().sum();
+
+            writeln!(
+                report,
+                "Frames: {} with {:.1} instructions a frame",
+                frames,
+                self.instructions as f64 / frames as f64
+            )
+            .unwrap();
+            writeln!(
+                report,
+                "Draws a frame: {} min, {:.1} mean, {} max",
+                self.frames.iter().min().unwrap_or(&0),
+                draws as f64 / frames as f64,
+                self.frames.iter().max().unwrap_or(&0)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nHotspots:").unwrap();
+
+        for (address, (count, opcode)) in sorted(&self.addresses).into_iter().take(HOTSPOTS) {
+            let instruction = Instruction::try_from(opcode)
+                .map(|instruction| instruction.to_string())
+                .unwrap_or_default();
+
+            writeln!(
+                report,
+                "  {:>10} {:>5.1}%  0x{:03X}  {}",
+                count,
+                percent(count),
+                address,
+                instruction
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nInstructions by kind:").unwrap();
+
+        for (kind, count) in sorted(&self.kinds) {
+            writeln!(report, "  {:>10} {:>5.1}%  {}", count, percent(count), kind).unwrap();
+        }
+
+        writeln!(report, "\nSubroutines:").unwrap();
+        writeln!(
+            report,
+            "  {:>10} {:>10} {:>8}  address",
+            "total", "self", "calls"
+        )
+        .unwrap();
+
+        for (address, (total, own)) in sorted(&self.subroutines()) {
+            writeln!(
+                report,
+                "  {:>10} {:>10} {:>8}  0x{:03X}",
+                total,
+                own,
+                self.calls.get(&address).unwrap_or(&0),
+                address
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    /// Writes the count of every chain of subroutines in the folded format
+    /// read by flamegraph tools, such as `0x200;0x2A4 120`
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut stacks: Vec<(String, u64)> = self
+            .stacks
+            .iter()
+            .map(|(stack, &count)| {
+                let frames: Vec<String> = stack
+                    .iter()
+                    .map(|address| format!("0x{:03X}", address))
+                    .collect();
+                (frames.join(";"), count)
+            })
+            .collect();
+        stacks.sort();
+
+        for (stack, count) in stacks {
+            writeln!(writer, "{} {}", stack, count)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the instructions run inside each subroutine including the
+    /// ones it called, and the ones run by the subroutine itself
+    fn subroutines(&self) -> HashMap<usize, (u64, u64)> {
+        let mut subroutines: HashMap<usize, (u64, u64)> = HashMap::new();
+
+        for (stack, &count) in &self.stacks {
+            // Count recursive subroutines once for each instruction
+            let unique: HashSet<&usize> = stack.iter().collect();
+
+            for &address in unique {
+                subroutines.entry(address).or_default().0 += count;
+            }
+
+            if let Some(&address) = stack.last() {
+                subroutines.entry(address).or_default().1 += count;
+            }
+        }
+
+        subroutines
+    }
+}
+
+/// Returns the name of the kind of instruction, such as `Drw`
+fn kind(instruction: Instruction) -> &'static str {
+    match instruction {
+        Instruction::Cls => "Cls",
+        Instruction::Ret => "Ret",
+        Instruction::Jp { .. } => "Jp",
+        Instruction::Call { .. } => "Call",
+        Instruction::SeV { .. } => "SeV",
+        Instruction::SneV { .. } => "SneV",
+        Instruction::SeVV { .. } => "SeVV",
+        Instruction::LdV { .. } => "LdV",
+        Instruction::AddV { .. } => "AddV",
+        Instruction::LdVV { .. } => "LdVV",
+        Instruction::OrVV { .. } => "OrVV",
+        Instruction::AndVV { .. } => "AndVV",
+        Instruction::XorVV { .. } => "XorVV",
+        Instruction::AddVV { .. } => "AddVV",
+        Instruction::SubVV { .. } => "SubVV",
+        Instruction::ShrVV { .. } => "ShrVV",
+        Instruction::SubnVV { .. } => "SubnVV",
+        Instruction::ShlVV { .. } => "ShlVV",
+        Instruction::SneVV { .. } => "SneVV",
+        Instruction::LdI { .. } => "LdI",
+        Instruction::JpV { .. } => "JpV",
+        Instruction::RndV { .. } => "RndV",
+        Instruction::Drw { .. } => "Drw",
+        Instruction::SkpV { .. } => "SkpV",
+        Instruction::SknpV { .. } => "SknpV",
+        Instruction::LdVDt { .. } => "LdVDt",
+        Instruction::LdVK { .. } => "LdVK",
+        Instruction::LdDtV { .. } => "LdDtV",
+        Instruction::LdStV { .. } => "LdStV",
+        Instruction::AddIV { .. } => "AddIV",
+        Instruction::LdFV { .. } => "LdFV",
+        Instruction::LdBV { .. } => "LdBV",
+        Instruction::LdIV { .. } => "LdIV",
+        Instruction::LdVI { .. } => "LdVI",
+    }
+}
+
+/// Returns the entries with the highest counts first, then by key
+fn sorted<K: Copy + Ord, V: Copy + Count>(counts: &HashMap<K, V>) -> Vec<(K, V)> {
+    let mut entries: Vec<(K, V)> = counts.iter().map(|(&key, &value)| (key, value)).collect();
+    entries.sort_by(|a, b| b.1.count().cmp(&a.1.count()).then(a.0.cmp(&b.0)));
+    entries
+}
+
+/// A value in a report which is ordered by a count
+trait Count {
+    fn count(&self) -> u64;
+}
+
+impl Count for u64 {
+    fn count(&self) -> u64 {
+        *self
+    }
+}
+
+impl<T> Count for (u64, T) {
+    fn count(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a loop which calls a subroutine that draws once a frame
+    fn profile() -> Profiler {
+        let rom = [0x60, 0x00, 0x22, 0x06, 0x12, 0x02, 0xD0, 0x01, 0x00, 0xEE];
+        let mut emulator = Emulator::new(&rom).unwrap();
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+        let mut profiler = Profiler::new(emulator.program_counter());
+
+        for _ in 0..3 {
+            emulator.decrement_timers();
+            profiler
+                .run(&mut emulator, &mut display, &keyboard, 5)
+                .unwrap();
+            profiler.end_frame();
+        }
+
+        profiler
+    }
+
+    #[test]
+    fn test_report() {
+        let report = profile().report();
+
+        assert!(report.starts_with("Instructions: 15\nFrames: 3 with 5.0 instructions a frame\n"));
+        assert!(report.contains("Draws a frame: 1 min, 1.3 mean, 2 max"));
+        assert!(report.contains("Hotspots:\n           4  26.7%  0x202  CALL 0x206\n"));
+        assert!(report.contains("           4  26.7%  Call\n"));
+        assert!(report.contains("          15          8        0  0x200\n"));
+        assert!(report.contains("           7          7        4  0x206\n"));
+    }
+
+    #[test]
+    fn test_write_folded() {
+        let mut folded = vec![];
+        profile().write_folded(&mut folded).unwrap();
+
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "0x200 8\n0x200;0x206 7\n"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::{fmt, str::FromStr};
+
+/// Behaviours which differ between Chip-8 interpreters
+///
+/// The names follow the quirks used by the community chip-8-database so that
+/// the settings recommended for a rom can be applied directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6 and 8xyE shift Vx in place instead of shifting Vy into Vx
+    pub shift: bool,
+    /// Fx55 and Fx65 increase Vi by x instead of x + 1
+    pub memory_increment_by_x: bool,
+    /// Fx55 and Fx65 leave Vi unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the screen instead of being clipped
+    pub wrap: bool,
+    /// Bnnn jumps to nnn + Vx where x is the highest nibble of nnn
+    pub jump: bool,
+    /// Dxyn waits for the vertical blank so only one sprite is drawn a frame
+    pub vblank: bool,
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    /// Returns the behaviour described by Cowgod's technical reference
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses either the id of a platform to use its quirks or a comma
+    /// separated list of the quirks to enable, such as `shift,jump`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(platform) = value.parse::<Platform>() {
+            return Ok(platform.quirks());
+        }
+
+        let mut quirks = Platform::ModernChip8.quirks();
+
+        for name in value
+            .split(',')
+            .filter(|name| !name.is_empty() && *name != "none")
+        {
+            match name {
+                "shift" => quirks.shift = true,
+                "memoryIncrementByX" => quirks.memory_increment_by_x = true,
+                "memoryLeaveIUnchanged" => quirks.memory_leave_i_unchanged = true,
+                "wrap" => quirks.wrap = true,
+                "jump" => quirks.jump = true,
+                "vblank" => quirks.vblank = true,
+                "logic" => quirks.logic = true,
+                _ => return Err(format!("Unknown quirk {}", name)),
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+/// A family of Chip-8 interpreters named as in the chip-8-database
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    OriginalChip8,
+    HybridVip,
+    ModernChip8,
+    Chip48,
+    Superchip1,
+    Superchip,
+    Xochip,
+}
+
+impl Platform {
+    /// Every platform in the order they appeared
+    pub const ALL: [Platform; 7] = [
+        Platform::OriginalChip8,
+        Platform::HybridVip,
+        Platform::ModernChip8,
+        Platform::Chip48,
+        Platform::Superchip1,
+        Platform::Superchip,
+        Platform::Xochip,
+    ];
+
+    /// Returns the number of instructions usually run each frame
+    pub fn tick_rate(self) -> usize {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => 15,
+            Platform::ModernChip8 => 12,
+            Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => 30,
+            Platform::Xochip => 100,
+        }
+    }
+
+    /// Returns the quirks that programs written for the platform expect
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+            },
+            Platform::ModernChip8 => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Chip48 | Platform::Superchip1 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Superchip => Quirks {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            Platform::Xochip => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: true,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .iter()
+            .copied()
+            .find(|platform| platform.to_string() == value)
+            .ok_or_else(|| format!("Unknown platform {}", value))
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Platform::OriginalChip8 => "originalChip8",
+            Platform::HybridVip => "hybridVIP",
+            Platform::ModernChip8 => "modernChip8",
+            Platform::Chip48 => "chip48",
+            Platform::Superchip1 => "superchip1",
+            Platform::Superchip => "superchip",
+            Platform::Xochip => "xochip",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quirks() {
+        assert_eq!("chip48".parse(), Ok(Platform::Chip48.quirks()));
+        assert_eq!(
+            "shift,wrap".parse(),
+            Ok(Quirks {
+                shift: true,
+                wrap: true,
+                ..Platform::ModernChip8.quirks()
+            })
+        );
+        assert_eq!("none".parse(), Ok(Platform::ModernChip8.quirks()));
+        assert_eq!(
+            "shift,bogus".parse::<Quirks>(),
+            Err("Unknown quirk bogus".to_string())
+        );
+    }
+}
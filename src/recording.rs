@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+
+use crate::keyboard::Keyboard;
+
+/// The keys held down on every frame of a run so that it can be played back
+///
+/// Only the frames where the pressed keys change are stored. The text format
+/// has one line per change holding the frame number and the mask from
+/// `Keyboard::state` in hex.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    changes: Vec<(usize, u16)>,
+}
+
+impl Recording {
+    /// Parses a recording from the text format
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut changes: Vec<(usize, u16)> = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let invalid = || format!("Invalid recording on line {}", index + 1);
+            let (frame, state) = line.trim().split_once(' ').ok_or_else(invalid)?;
+            let frame: usize = frame.parse().map_err(|_| invalid())?;
+            let state = u16::from_str_radix(state, 16).map_err(|_| invalid())?;
+
+            if changes.last().is_some_and(|&(last, _)| frame <= last) {
+                return Err(invalid());
+            }
+
+            changes.push((frame, state));
+        }
+
+        Ok(Self { changes })
+    }
+
+    /// Stores the keys pressed on a frame, which must come after any frame
+    /// already recorded
+    pub fn record(&mut self, frame: usize, keyboard: &Keyboard) {
+        let state = keyboard.state();
+
+        if self.state_at(frame) != state {
+            self.changes.push((frame, state));
+        }
+    }
+
+    /// Returns the mask of keys that were pressed on a frame
+    pub fn state_at(&self, frame: usize) -> u16 {
+        let index = self.changes.partition_point(|&(change, _)| change <= frame);
+
+        match index {
+            0 => 0,
+            _ => self.changes[index - 1].1,
+        }
+    }
+
+    /// Presses the keys that were pressed on a frame
+    pub fn play(&self, frame: usize, keyboard: &mut Keyboard) {
+        keyboard.set_state(self.state_at(frame));
+    }
+
+    /// Writes the recording in the text format
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (frame, state) in &self.changes {
+            writeln!(writer, "{} {:04X}", frame, state)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::Key;
+
+    #[test]
+    fn test_record_and_play() {
+        let mut keyboard = Keyboard::default();
+        let mut recording = Recording::default();
+
+        recording.record(0, &keyboard);
+        keyboard.press(&Key::A);
+        recording.record(3, &keyboard);
+        recording.record(4, &keyboard);
+        keyboard.release(&Key::A);
+        recording.record(7, &keyboard);
+
+        let mut text = vec![];
+        recording.write(&mut text).unwrap();
+        assert_eq!(String::from_utf8(text).unwrap(), "3 0400\n7 0000\n");
+
+        let mut keyboard = Keyboard::default();
+        recording.play(5, &mut keyboard);
+        assert!(keyboard.is_pressed(&Key::A));
+        recording.play(7, &mut keyboard);
+        assert!(!keyboard.is_pressed(&Key::A));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Recording::parse("1 0001\n5 0000\n"),
+            Ok(Recording {
+                changes: vec![(1, 0x0001), (5, 0x0000)]
+            })
+        );
+        assert!(Recording::parse("5 0001\n1 0000\n").is_err());
+        assert!(Recording::parse("5").is_err());
+    }
+}
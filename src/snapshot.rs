@@ -0,0 +1,161 @@
+use std::{fs, path::Path};
+
+use crate::display::{Display, HEIGHT, WIDTH};
+
+/// The environment variable which writes the current screen over any golden
+/// image instead of comparing with it
+pub const BLESS: &str = "CHIP8_BLESS";
+
+/// Compares the screen with a golden image and panics with a diff if they
+/// differ
+///
+/// Golden images ending in `.pbm` are plain PBM images and anything else is
+/// text from `Display::to_text`. Setting `CHIP8_BLESS=1` writes the golden
+/// image instead, creating it if it does not exist yet.
+pub fn assert_snapshot<P: AsRef<Path>>(display: &Display, path: P) {
+    let bless = std::env::var_os(BLESS).is_some_and(|value| !value.is_empty() && value != "0");
+
+    if let Err(message) = check(display, path.as_ref(), bless) {
+        panic!("{}", message);
+    }
+}
+
+/// Compares the screen with a golden image, or writes it when blessing
+pub fn check(display: &Display, path: &Path, bless: bool) -> Result<(), String> {
+    let is_pbm = path.extension().is_some_and(|extension| extension == "pbm");
+
+    if bless {
+        let contents = if is_pbm {
+            let mut image = vec![];
+            display
+                .write_pbm(&mut image)
+                .map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+            image
+        } else {
+            display.to_text().into_bytes()
+        };
+
+        return fs::write(path, contents)
+            .map_err(|e| format!("Unable to write {}: {}", path.display(), e));
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Unable to read {}: {}\nRun with {}=1 to create it",
+            path.display(),
+            e,
+            BLESS
+        )
+    })?;
+
+    let expected = if is_pbm {
+        Display::from_pbm(&text)
+    } else {
+        Display::from_text(&text)
+    }
+    .map_err(|e| format!("Invalid golden image {}: {}", path.display(), e))?;
+
+    match diff(&expected, display) {
+        Some(diff) => Err(format!(
+            "Screen does not match {}\n{}\nRun with {}=1 to update it",
+            path.display(),
+            diff,
+            BLESS
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Describes the differences between two screens, or returns `None` if they
+/// are the same
+///
+/// The expected and found screens are shown side by side with a third column
+/// marking pixels that are missing with `-` and unexpected with `+`, and each
+/// row that differs is marked with `<`.
+pub fn diff(expected: &Display, found: &Display) -> Option<String> {
+    let differences: u32 = expected
+        .rows()
+        .iter()
+        .zip(found.rows())
+        .map(|(expected, found)| (expected ^ found).count_ones())
+        .sum();
+
+    if differences == 0 {
+        return None;
+    }
+
+    let mut text = format!(
+        "{} pixels differ\n{:<width$} {:<width$} {:<width$}\n",
+        differences,
+        "expected",
+        "found",
+        "diff",
+        width = WIDTH
+    );
+
+    for y in 0..HEIGHT {
+        let mut columns = [String::new(), String::new(), String::new()];
+
+        for x in 0..WIDTH {
+            let wanted = expected.get_pixel(x, y);
+            let pixel = found.get_pixel(x, y);
+
+            columns[0].push(if wanted { '#' } else { '.' });
+            columns[1].push(if pixel { '#' } else { '.' });
+            columns[2].push(match (wanted, pixel) {
+                (true, false) => '-',
+                (false, true) => '+',
+                _ => '.',
+            });
+        }
+
+        let marker = if expected.rows()[y] != found.rows()[y] {
+            " <"
+        } else {
+            ""
+        };
+
+        text.push_str(&format!(
+            "{} {} {}{}\n",
+            columns[0], columns[1], columns[2], marker
+        ));
+    }
+
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let expected = Display::new(&[(0, 0), (1, 1)]);
+        let found = Display::new(&[(0, 0), (2, 1)]);
+
+        assert_eq!(diff(&expected, &expected), None);
+
+        let text = diff(&expected, &found).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "2 pixels differ");
+        assert!(!lines[2].ends_with('<'));
+        assert!(lines[3].starts_with(".#."));
+        assert!(lines[3]
+            .ends_with(" .-+............................................................. <"));
+    }
+
+    #[test]
+    fn test_bless_and_check() {
+        let path = std::env::temp_dir().join(format!("chip-8-snapshot-{}.pbm", std::process::id()));
+        let display = Display::new(&[(3, 4)]);
+
+        assert!(check(&display, &path, false).is_err());
+        assert_eq!(check(&display, &path, true), Ok(()));
+        assert_eq!(check(&display, &path, false), Ok(()));
+
+        let error = check(&Display::default(), &path, false).unwrap_err();
+        assert!(error.contains("1 pixels differ"));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,489 @@
+use std::{convert::TryFrom, fmt, io::Write, ops::RangeInclusive, str::FromStr};
+
+use crate::{
+    display::Display, emulator::Emulator, instruction::Instruction, keyboard::Keyboard,
+    recording::Recording,
+};
+
+/// The state recorded on one line of a trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceLine {
+    pub cycle: u64,
+    pub program_counter: usize,
+    pub opcode: u16,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// The program counter after the instruction ran
+    pub next_program_counter: usize,
+    pub stack_depth: usize,
+    /// A checksum of every byte of memory
+    pub memory: u32,
+    /// A checksum of every row of the screen
+    pub display: u32,
+}
+
+impl TraceLine {
+    /// Captures the state of the emulator and screen after running an
+    /// instruction
+    pub fn new(
+        cycle: u64,
+        program_counter: usize,
+        opcode: u16,
+        emulator: &Emulator,
+        display: &Display,
+    ) -> Self {
+        Self {
+            cycle,
+            program_counter,
+            opcode,
+            registers: *emulator.registers(),
+            i: emulator.i(),
+            delay_timer: emulator.delay_timer(),
+            sound_timer: emulator.sound_timer(),
+            next_program_counter: emulator.program_counter(),
+            stack_depth: emulator.stack_depth(),
+            memory: checksum(emulator.memory().bytes().iter().copied()),
+            display: checksum(display.rows().iter().flat_map(|row| row.to_be_bytes())),
+        }
+    }
+
+    /// Describes every field that differs from the expected line
+    pub fn diff(&self, expected: &TraceLine) -> Vec<String> {
+        let mut differences = vec![];
+        let mut compare = |name: String, found: String, wanted: String| {
+            if found != wanted {
+                differences.push(format!("{}: expected {} but found {}", name, wanted, found));
+            }
+        };
+
+        compare(
+            "PC".to_string(),
+            format!("0x{:03X}", self.program_counter),
+            format!("0x{:03X}", expected.program_counter),
+        );
+        compare(
+            "opcode".to_string(),
+            format!("0x{:04X}", self.opcode),
+            format!("0x{:04X}", expected.opcode),
+        );
+
+        for (register, (found, wanted)) in
+            self.registers.iter().zip(&expected.registers).enumerate()
+        {
+            compare(
+                format!("V{:X}", register),
+                format!("0x{:02X}", found),
+                format!("0x{:02X}", wanted),
+            );
+        }
+
+        compare(
+            "I".to_string(),
+            format!("0x{:04X}", self.i),
+            format!("0x{:04X}", expected.i),
+        );
+        compare(
+            "DT".to_string(),
+            self.delay_timer.to_string(),
+            expected.delay_timer.to_string(),
+        );
+        compare(
+            "ST".to_string(),
+            self.sound_timer.to_string(),
+            expected.sound_timer.to_string(),
+        );
+        compare(
+            "next PC".to_string(),
+            format!("0x{:03X}", self.next_program_counter),
+            format!("0x{:03X}", expected.next_program_counter),
+        );
+        compare(
+            "stack depth".to_string(),
+            self.stack_depth.to_string(),
+            expected.stack_depth.to_string(),
+        );
+        compare(
+            "memory checksum".to_string(),
+            format!("{:08X}", self.memory),
+            format!("{:08X}", expected.memory),
+        );
+        compare(
+            "display checksum".to_string(),
+            format!("{:08X}", self.display),
+            format!("{:08X}", expected.display),
+        );
+
+        differences
+    }
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registers: String = self
+            .registers
+            .iter()
+            .map(|register| format!("{:02X}", register))
+            .collect();
+        let instruction = Instruction::try_from(self.opcode)
+            .map(|instruction| instruction.to_string())
+            .unwrap_or_default();
+
+        write!(
+            f,
+            "{:010} {:03X} {:04X} {} {:04X} {:02X} {:02X} {:03X} {:02X} {:08X} {:08X} ; {}",
+            self.cycle,
+            self.program_counter,
+            self.opcode,
+            registers,
+            self.i,
+            self.delay_timer,
+            self.sound_timer,
+            self.next_program_counter,
+            self.stack_depth,
+            self.memory,
+            self.display,
+            instruction
+        )
+    }
+}
+
+impl FromStr for TraceLine {
+    type Err = String;
+
+    /// Parses a line ignoring the disassembled instruction
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid trace line {}", value);
+        let state = value.split(';').next().unwrap_or_default();
+        let fields: Vec<&str> = state.split_whitespace().collect();
+
+        let (cycle, program_counter, opcode, registers, i, delay_timer, sound_timer) =
+            match fields.as_slice() {
+                [a, b, c, d, e, f, g, ..] if fields.len() == 11 => (a, b, c, d, e, f, g),
+                _ => return Err(invalid()),
+            };
+        let (next_program_counter, stack_depth, memory, display) =
+            (fields[7], fields[8], fields[9], fields[10]);
+
+        let hex = |field: &str| u16::from_str_radix(field, 16).map_err(|_| invalid());
+        let checksum = |field: &str| u32::from_str_radix(field, 16).map_err(|_| invalid());
+        let byte = |field: &str| u8::from_str_radix(field, 16).map_err(|_| invalid());
+
+        if registers.len() != 32 || !registers.is_ascii() {
+            return Err(invalid());
+        }
+
+        let mut values = [0; 16];
+
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = byte(&registers[index * 2..index * 2 + 2])?;
+        }
+
+        Ok(Self {
+            cycle: cycle.parse().map_err(|_| invalid())?,
+            program_counter: hex(program_counter)? as usize,
+            opcode: hex(opcode)?,
+            registers: values,
+            i: hex(i)?,
+            delay_timer: byte(delay_timer)?,
+            sound_timer: byte(sound_timer)?,
+            next_program_counter: hex(next_program_counter)? as usize,
+            stack_depth: byte(stack_depth)? as usize,
+            memory: checksum(memory)?,
+            display: checksum(display)?,
+        })
+    }
+}
+
+/// Returns the 32 bit FNV-1a hash of the bytes, which is enough to tell when
+/// memory or the screen changed differently without writing them out
+fn checksum<I: IntoIterator<Item = u8>>(bytes: I) -> u32 {
+    bytes.into_iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Writes a line for every instruction the emulator executes
+///
+/// Each line holds the cycle counted from 0, the program counter and opcode
+/// before execution, then V0 to VF, I, the delay and sound timers, the
+/// program counter, the stack depth and checksums of memory and the screen
+/// after execution, and finally the disassembled instruction after a `;`:
+///
+/// ```text
+/// 0000000000 200 6005 05000000000000000000000000000000 0000 00 00 202 00 27C52415 E6A1D1C5 ; LD V0, 0x05
+/// ```
+///
+/// Every field has a fixed width so that traces can be compared with `diff`.
+pub struct Tracer<W: Write> {
+    writer: W,
+    cycle: u64,
+    addresses: Option<RangeInclusive<usize>>,
+    cycles: Option<RangeInclusive<u64>>,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Creates a tracer which writes every instruction
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            cycle: 0,
+            addresses: None,
+            cycles: None,
+        }
+    }
+
+    /// Only writes instructions whose address is in the range
+    pub fn set_address_filter(&mut self, addresses: Option<RangeInclusive<usize>>) {
+        self.addresses = addresses;
+    }
+
+    /// Only writes instructions executed on cycles in the range
+    pub fn set_cycle_filter(&mut self, cycles: Option<RangeInclusive<u64>>) {
+        self.cycles = cycles;
+    }
+
+    /// Evaluates the given number of CPU instructions one at a time, writing
+    /// each one that passes the filters
+    pub fn run(
+        &mut self,
+        emulator: &mut Emulator,
+        display: &mut Display,
+        keyboard: &Keyboard,
+        ticks: usize,
+    ) -> Result<(), String> {
+        for _ in 0..ticks {
+            let program_counter = emulator.program_counter();
+            let opcode = emulator
+                .memory()
+                .get_instruction(program_counter)
+                .unwrap_or_default();
+
+            emulator.tick(display, keyboard)?;
+
+            if self.is_traced(program_counter) {
+                let line = TraceLine::new(self.cycle, program_counter, opcode, emulator, display);
+                writeln!(self.writer, "{}", line)
+                    .map_err(|e| format!("Unable to write trace: {}", e))?;
+            }
+
+            self.cycle += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the current instruction passes the filters
+    fn is_traced(&self, program_counter: usize) -> bool {
+        self.addresses
+            .as_ref()
+            .is_none_or(|addresses| addresses.contains(&program_counter))
+            && self
+                .cycles
+                .as_ref()
+                .is_none_or(|cycles| cycles.contains(&self.cycle))
+    }
+}
+
+/// A complete trace of a known-good run to compare the emulator against
+pub struct Reference {
+    lines: Vec<TraceLine>,
+}
+
+impl Reference {
+    /// Parses a trace written without any filters
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lines = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<TraceLine>, _>>()?;
+
+        for (cycle, line) in lines.iter().enumerate() {
+            if line.cycle != cycle as u64 {
+                return Err(format!("Expected cycle {} in trace", cycle));
+            }
+        }
+
+        Ok(Self { lines })
+    }
+
+    /// Runs the emulator one frame at a time with the recorded keys, in the
+    /// same way as the headless mode, and compares the state after every tick
+    /// until the end of the trace
+    ///
+    /// The error describes the first line where the emulator diverged.
+    pub fn check(
+        &self,
+        emulator: &mut Emulator,
+        input: &Recording,
+        ticks_per_frame: usize,
+    ) -> Result<(), String> {
+        let mut display = Display::default();
+        let mut keyboard = Keyboard::default();
+        let mut expected = self.lines.iter();
+        let mut frame = 0;
+
+        while expected.len() != 0 {
+            input.play(frame, &mut keyboard);
+            emulator.decrement_timers();
+
+            for expected in expected.by_ref().take(ticks_per_frame) {
+                let program_counter = emulator.program_counter();
+                let opcode = emulator
+                    .memory()
+                    .get_instruction(program_counter)
+                    .unwrap_or_default();
+
+                emulator.tick(&mut display, &keyboard).map_err(|e| {
+                    format!("Cycle {}: {}\n  expected {}", expected.cycle, e, expected)
+                })?;
+
+                let found =
+                    TraceLine::new(expected.cycle, program_counter, opcode, emulator, &display);
+                let differences = found.diff(expected);
+
+                if !differences.is_empty() {
+                    return Err(format!(
+                        "Cycle {} on frame {} diverged from the reference\n  expected {}\n  found    {}\n  {}",
+                        expected.cycle,
+                        frame,
+                        expected,
+                        found,
+                        differences.join("\n  ")
+                    ));
+                }
+            }
+
+            frame += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    fn trace(tracer: &mut Tracer<Vec<u8>>, rom: &[u8], ticks: usize) -> String {
+        let mut emulator = Emulator::new(rom).unwrap();
+        let mut display = Display::default();
+        let keyboard = Keyboard::default();
+
+        tracer
+            .run(&mut emulator, &mut display, &keyboard, ticks)
+            .unwrap();
+
+        String::from_utf8(tracer.writer.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut tracer = Tracer::new(vec![]);
+
+        let output = trace(&mut tracer, &[0x60, 0x05, 0xA3, 0x00], 2);
+
+        assert_eq!(
+            output,
+            "0000000000 200 6005 05000000000000000000000000000000 0000 00 00 202 00 27C52415 E6A1D1C5 ; LD V0, 0x05\n\
+             0000000001 202 A300 05000000000000000000000000000000 0300 00 00 204 00 27C52415 E6A1D1C5 ; LD I, 0x300\n"
+        );
+    }
+
+    #[test]
+    fn test_trace_filters() {
+        let rom = [0x70, 0x01, 0x12, 0x00];
+
+        let mut tracer = Tracer::new(vec![]);
+        tracer.set_address_filter(Some(0x202..=0x202));
+        let output = trace(&mut tracer, &rom, 6);
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.lines().all(|line| line.contains(" 202 1200 ")));
+
+        let mut tracer = Tracer::new(vec![]);
+        tracer.set_cycle_filter(Some(4..=10));
+        let output = trace(&mut tracer, &rom, 6);
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.starts_with("0000000004 200 7001 03"));
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let text = "0000000001 202 A300 05000000000000000000000000000000 0300 00 00 204 00 27C52415 E6A1D1C5 ; LD I, 0x300";
+        let line: TraceLine = text.parse().unwrap();
+
+        assert_eq!(line.program_counter, 0x202);
+        assert_eq!(line.registers[0], 0x05);
+        assert_eq!(line.i, 0x300);
+        assert_eq!(line.next_program_counter, 0x204);
+        assert_eq!(line.stack_depth, 0);
+        assert_eq!(line.memory, 0x27C52415);
+        assert_eq!(line.display, 0xE6A1D1C5);
+        assert_eq!(line.to_string(), text);
+    }
+
+    #[test]
+    fn test_reference_check() {
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut tracer = Tracer::new(vec![]);
+        let text = trace(&mut tracer, &rom, 10);
+        let reference = Reference::parse(&text).unwrap();
+
+        let mut emulator = Emulator::new(&rom).unwrap();
+        assert_eq!(
+            reference.check(&mut emulator, &Recording::default(), 3),
+            Ok(())
+        );
+
+        let mut emulator = Emulator::new(&[0x70, 0x02, 0x12, 0x00]).unwrap();
+        let error = reference
+            .check(&mut emulator, &Recording::default(), 3)
+            .unwrap_err();
+        assert!(error.starts_with("Cycle 0 on frame 0 diverged"));
+        assert!(error.contains("V0: expected 0x01 but found 0x02"));
+        assert!(error.contains("opcode: expected 0x7001 but found 0x7002"));
+    }
+
+    #[test]
+    fn test_reference_check_state_outside_registers() {
+        let check = |rom: &[u8], quirks: Quirks, found: &[u8]| {
+            let mut tracer = Tracer::new(vec![]);
+            let reference = Reference::parse(&trace(&mut tracer, rom, 2)).unwrap();
+            let mut emulator = Emulator::new(found).unwrap();
+            emulator.set_quirks(quirks);
+            reference
+                .check(&mut emulator, &Recording::default(), 2)
+                .unwrap_err()
+        };
+        let wrapping = Quirks::default();
+
+        // LD V3, 0x04; JP V0, 0x300 with and without the jump quirk
+        let rom = [0x63, 0x04, 0xB3, 0x00];
+        let jump = Quirks {
+            jump: true,
+            ..wrapping
+        };
+        let error = check(&rom, jump, &rom);
+        assert!(error.starts_with("Cycle 1 on frame 0 diverged"));
+        assert!(error.contains("next PC: expected 0x300 but found 0x304"));
+
+        // Data that is never read into a register
+        let error = check(&[0x12, 0x00, 0x00], wrapping, &[0x12, 0x00, 0xFF]);
+        assert!(error.starts_with("Cycle 0 on frame 0 diverged"));
+        assert!(error.contains("memory checksum: expected"));
+
+        // LD V0, 0x3E; DRW V0, V1, 5 wrapped and clipped at the right edge
+        let rom = [0x60, 0x3E, 0xD0, 0x15];
+        let clipping = Quirks {
+            wrap: false,
+            ..wrapping
+        };
+        let error = check(&rom, clipping, &rom);
+        assert!(error.starts_with("Cycle 1 on frame 0 diverged"));
+        assert!(error.contains("display checksum: expected"));
+        assert!(!error.contains("memory checksum"));
+    }
+}
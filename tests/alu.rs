@@ -0,0 +1,114 @@
+//! Checks every 8xyn instruction against a simple model of the arithmetic
+//! and flags for every platform, including VF as either operand
+//!
+//! The model writes the result to Vx before setting VF, so that when VF is
+//! Vx the flag is what remains like on the original interpreters.
+
+use chip_8::{display::Display, emulator::Emulator, keyboard::Keyboard, quirks::Platform};
+use proptest::prelude::*;
+
+/// The last nibble of every 8xyn instruction
+const OPERATIONS: [u8; 9] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE];
+
+/// Returns the registers expected after running 8xyn under the quirks of the
+/// platform
+fn model(registers: [u8; 16], x: usize, y: usize, n: u8, platform: Platform) -> [u8; 16] {
+    let quirks = platform.quirks();
+    let mut registers = registers;
+    let (vx, vy) = (registers[x], registers[y]);
+    let shifted = if quirks.shift { vx } else { vy };
+
+    let (result, flag) = match n {
+        0x0 => (vy, None),
+        0x1..=0x3 => {
+            let result = match n {
+                0x1 => vx | vy,
+                0x2 => vx & vy,
+                _ => vx ^ vy,
+            };
+
+            (result, if quirks.logic { Some(0) } else { None })
+        }
+        0x4 => (
+            vx.wrapping_add(vy),
+            Some((vx as u16 + vy as u16 > 0xFF) as u8),
+        ),
+        0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+        0x6 => (shifted >> 1, Some(shifted & 1)),
+        0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+        0xE => (shifted << 1, Some(shifted >> 7)),
+        _ => unreachable!(),
+    };
+
+    registers[x] = result;
+
+    if let Some(flag) = flag {
+        registers[0xF] = flag;
+    }
+
+    registers
+}
+
+/// Loads the values into Vx, Vy and VF then runs 8xyn, returning the
+/// registers before and after
+fn run(values: [u8; 3], x: usize, y: usize, n: u8, platform: Platform) -> ([u8; 16], [u8; 16]) {
+    let mut rom = vec![];
+    let mut registers = [0; 16];
+
+    // Later loads win when registers are shared, as they do in the rom
+    for (&register, &value) in [0xF, y, x].iter().zip(values.iter().rev()) {
+        rom.extend_from_slice(&[0x60 | register as u8, value]);
+        registers[register] = value;
+    }
+
+    rom.extend_from_slice(&[0x80 | x as u8, (y as u8) << 4 | n]);
+
+    let mut emulator = Emulator::new(&rom).unwrap();
+    let mut display = Display::default();
+    let keyboard = Keyboard::default();
+
+    emulator.set_quirks(platform.quirks());
+    emulator
+        .run(&mut display, &keyboard, rom.len() / 2)
+        .unwrap();
+
+    (registers, *emulator.registers())
+}
+
+proptest! {
+    #[test]
+    fn test_alu(
+        values in any::<[u8; 3]>(),
+        x in 0..16usize,
+        y in 0..16usize,
+        n in prop::sample::select(OPERATIONS.to_vec()),
+        platform in prop::sample::select(Platform::ALL.to_vec()),
+    ) {
+        let (before, after) = run(values, x, y, n, platform);
+
+        prop_assert_eq!(after, model(before, x, y, n, platform));
+    }
+}
+
+#[test]
+fn test_flag_register_as_operand() {
+    for &platform in Platform::ALL.iter() {
+        for &n in OPERATIONS.iter() {
+            for &(x, y) in [(0xF, 0x1), (0x1, 0xF), (0xF, 0xF)].iter() {
+                for &values in [[0x80, 0x81, 0x00], [0x03, 0xFF, 0x01], [0x10, 0x20, 0x01]].iter() {
+                    let (before, after) = run(values, x, y, n, platform);
+
+                    assert_eq!(
+                        after,
+                        model(before, x, y, n, platform),
+                        "8{:X}{:X}{:X} under {}",
+                        x,
+                        y,
+                        n,
+                        platform
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Runs the test programs in `tests/conformance` headlessly under the quirks
+//! of every platform and compares the final screen with the one expected
+//!
+//! The programs draw a tick or a cross for each check in the style of the
+//! community test suites, and the quirks program draws a digit for each
+//! quirk. Keys are pressed from `<program>.keys` when it exists. The expected
+//! screens are drawn here from the layout of the programs and, for the quirks
+//! program, from a table of the digits each platform should show, so they do
+//! not depend on the emulator's own output or on `Platform::quirks`.
+
+use std::path::Path;
+
+use chip_8::{
+    assembler::assemble,
+    display::{Display, HEIGHT, WIDTH},
+    emulator::Emulator,
+    keyboard::Keyboard,
+    quirks::Platform,
+    recording::Recording,
+    snapshot,
+};
+
+const FRAMES: usize = 60;
+const TICKS_PER_FRAME: usize = 8;
+
+/// The mark drawn for a check that passed
+const TICK: [u8; 5] = [0x01, 0x02, 0x84, 0x48, 0x30];
+
+/// The mark drawn for a check that failed
+const CROSS: [u8; 5] = [0x88, 0x50, 0x20, 0x50, 0x88];
+
+/// The digits 0 to 3 as drawn by Cowgod's technical reference
+const DIGITS: [[u8; 5]; 4] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0],
+    [0x20, 0x60, 0x20, 0x20, 0x70],
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0],
+    [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+];
+
+/// Runs a program for a second with the recorded keys and returns the screen
+fn run(rom: &[u8], platform: Platform, recording: &Recording) -> Display {
+    let mut emulator = Emulator::new(rom).unwrap();
+    let mut display = Display::default();
+    let mut keyboard = Keyboard::default();
+
+    emulator.set_quirks(platform.quirks());
+
+    for frame in 0..FRAMES {
+        recording.play(frame, &mut keyboard);
+        emulator.decrement_timers();
+        emulator
+            .run(&mut display, &keyboard, TICKS_PER_FRAME)
+            .unwrap();
+    }
+
+    display
+}
+
+/// Returns the positions of the crosses drawn on the grid of marks
+fn crosses(display: &Display) -> Vec<(usize, usize)> {
+    let mut positions = vec![];
+
+    for y in (0..=HEIGHT - CROSS.len()).step_by(6) {
+        for x in (0..WIDTH).step_by(8) {
+            let mark: Vec<u8> = (y..y + CROSS.len())
+                .map(|y| (0..8).fold(0, |row, dx| row << 1 | display.get_pixel(x + dx, y) as u8))
+                .collect();
+
+            if mark == CROSS {
+                positions.push((x, y));
+            }
+        }
+    }
+
+    positions
+}
+
+/// Returns a screen with rows of ticks, one row for each count
+fn ticks(rows: &[usize]) -> Display {
+    let mut display = Display::default();
+
+    for (row, &count) in rows.iter().enumerate() {
+        for column in 0..count {
+            display.xor_sprite(column * 8, row * 6, &TICK);
+        }
+    }
+
+    display
+}
+
+/// Returns the digits the quirks program should draw on a platform, as the
+/// quirks test of the community test suite reports them for the original
+/// interpreters rather than as this emulator's presets configure them
+fn quirk_digits(platform: Platform) -> [usize; 6] {
+    match platform {
+        Platform::OriginalChip8 | Platform::HybridVip => [0, 3, 0, 0, 1, 1],
+        Platform::ModernChip8 => [0, 3, 0, 0, 0, 0],
+        Platform::Chip48 | Platform::Superchip1 => [1, 2, 0, 1, 0, 0],
+        Platform::Superchip => [1, 1, 0, 1, 0, 0],
+        Platform::Xochip => [0, 3, 1, 0, 0, 0],
+    }
+}
+
+/// Returns the screen a program should leave on a platform
+fn expected(program: &str, platform: Platform) -> Display {
+    match program {
+        "opcodes" => ticks(&[8, 8, 8, 1]),
+        "flags" => ticks(&[5, 7, 5, 5, 5]),
+        "keypad" => ticks(&[2, 4, 1, 2]),
+        "quirks" => {
+            let mut display = Display::default();
+
+            for (index, &digit) in quirk_digits(platform).iter().enumerate() {
+                display.xor_sprite(4 + index * 10, 13, &DIGITS[digit]);
+            }
+
+            display
+        }
+        _ => panic!("No expected screen for {}", program),
+    }
+}
+
+fn check(program: &str) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let source = std::fs::read_to_string(directory.join(program).with_extension("asm")).unwrap();
+    let rom = assemble(&source).unwrap();
+    let recording = match std::fs::read_to_string(directory.join(program).with_extension("keys")) {
+        Ok(text) => Recording::parse(&text).unwrap(),
+        Err(_) => Recording::default(),
+    };
+
+    for &platform in Platform::ALL.iter() {
+        let display = run(&rom, platform, &recording);
+
+        assert_eq!(
+            crosses(&display),
+            [],
+            "{} failed checks on {}",
+            program,
+            platform
+        );
+
+        if let Some(diff) = snapshot::diff(&expected(program, platform), &display) {
+            panic!(
+                "{} drew the wrong screen on {}\n{}",
+                program, platform, diff
+            );
+        }
+    }
+}
+#[test]
+fn test_opcodes() {
+    check("opcodes");
+}
+
+#[test]
+fn test_flags() {
+    check("flags");
+}
+
+#[test]
+fn test_quirks() {
+    check("quirks");
+}
+
+#[test]
+fn test_keypad() {
+    check("keypad");
+}
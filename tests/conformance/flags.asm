@@ -0,0 +1,165 @@
+; Checks the result and VF after each arithmetic instruction, drawing a tick
+; for every value that matches the expected value in V2 or a cross when it
+; does not, with one row for each instruction ending with a check that VF as
+; Vx is left holding the flag rather than the result
+;
+; VC and VD hold the position of the next mark and are not used by the tests
+
+        LD VC, 0
+        LD VD, 0
+
+; 8xy4 with and without a carry
+        LD V1, 0x80
+        LD V3, 0x80
+        ADD V1, V3
+        LD V4, VF
+        LD V2, 0x00
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD V1, 0x10
+        LD V3, 0x20
+        ADD V1, V3
+        LD V4, VF
+        LD V2, 0x30
+        CALL check
+        LD V1, V4
+        LD V2, 0
+        CALL check
+        LD VF, 0x80
+        LD V3, 0x80
+        ADD VF, V3
+        LD V1, VF
+        LD V2, 1
+        CALL check
+        CALL newline
+
+; 8xy5 with a borrow, without one and with equal values
+        LD V1, 0x10
+        LD V3, 0x20
+        SUB V1, V3
+        LD V4, VF
+        LD V2, 0xF0
+        CALL check
+        LD V1, V4
+        LD V2, 0
+        CALL check
+        LD V1, 0x20
+        LD V3, 0x10
+        SUB V1, V3
+        LD V4, VF
+        LD V2, 0x10
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD V1, 0x20
+        LD V3, 0x20
+        SUB V1, V3
+        LD V4, VF
+        LD V2, 0x00
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD VF, 0x10
+        LD V3, 0x20
+        SUB VF, V3
+        LD V1, VF
+        LD V2, 0
+        CALL check
+        CALL newline
+
+; 8xy7 with a borrow and without one
+        LD V1, 0x20
+        LD V3, 0x10
+        SUBN V1, V3
+        LD V4, VF
+        LD V2, 0xF0
+        CALL check
+        LD V1, V4
+        LD V2, 0
+        CALL check
+        LD V1, 0x10
+        LD V3, 0x20
+        SUBN V1, V3
+        LD V4, VF
+        LD V2, 0x10
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD VF, 0x20
+        LD V3, 0x10
+        SUBN VF, V3
+        LD V1, VF
+        LD V2, 0
+        CALL check
+        CALL newline
+
+; 8xy6 shifting out a one and a zero
+        LD V1, 0x03
+        SHR V1, V1
+        LD V4, VF
+        LD V2, 0x01
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD V1, 0x02
+        SHR V1, V1
+        LD V4, VF
+        LD V2, 0x01
+        CALL check
+        LD V1, V4
+        LD V2, 0
+        CALL check
+        LD VF, 0x02
+        SHR VF, VF
+        LD V1, VF
+        LD V2, 0
+        CALL check
+        CALL newline
+
+; 8xyE shifting out a one and a zero
+        LD V1, 0xC0
+        SHL V1, V1
+        LD V4, VF
+        LD V2, 0x80
+        CALL check
+        LD V1, V4
+        LD V2, 1
+        CALL check
+        LD V1, 0x40
+        SHL V1, V1
+        LD V4, VF
+        LD V2, 0x80
+        CALL check
+        LD V1, V4
+        LD V2, 0
+        CALL check
+        LD VF, 0x81
+        SHL VF, VF
+        LD V1, VF
+        LD V2, 1
+        CALL check
+
+end:    JP end
+
+; Draws a tick when V1 equals V2 and a cross otherwise
+check:  LD I, tick
+        SE V1, V2
+        LD I, cross
+        DRW VC, VD, 5
+        ADD VC, 8
+        RET
+
+; Moves the position of the next mark to the start of the next row
+newline:
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+tick:   DB 0x01, 0x02, 0x84, 0x48, 0x30
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
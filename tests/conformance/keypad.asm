@@ -0,0 +1,90 @@
+; Checks the keypad instructions against the keys pressed in keypad.keys,
+; drawing a tick for every result that matches the expected value in V2 or a
+; cross when it does not, with one row for each stage of the recording
+;
+; The recording presses 5 on frame 10, releases it on frame 20, presses A on
+; frame 30 and releases it on frame 40.
+;
+; VC and VD hold the position of the next mark and are not used by the tests
+
+        LD VC, 0
+        LD VD, 0
+
+; Ex9E and ExA1 with no keys pressed
+        LD V3, 5
+        LD V1, 0
+        SKP V3
+        LD V1, 1
+        LD V2, 1
+        CALL check
+        LD V1, 0
+        SKNP V3
+        LD V1, 1
+        LD V2, 0
+        CALL check
+        CALL newline
+
+; Fx0A waits for a key and stores it
+        LD V0, K
+        LD V1, V0
+        LD V2, 5
+        CALL check
+
+; Ex9E and ExA1 with the key held while other keys stay up
+        LD V1, 0
+        SKP V0
+        LD V1, 1
+        LD V2, 0
+        CALL check
+        LD V1, 0
+        SKNP V0
+        LD V1, 1
+        LD V2, 1
+        CALL check
+        LD V3, 6
+        LD V1, 0
+        SKP V3
+        LD V1, 1
+        LD V2, 1
+        CALL check
+        CALL newline
+
+; ExA1 skips once the key is released
+held:   SKNP V0
+        JP held
+        LD V1, 0
+        SKP V0
+        LD V1, 1
+        LD V2, 1
+        CALL check
+        CALL newline
+
+; Fx0A with a different key
+        LD V0, K
+        LD V1, V0
+        LD V2, 0xA
+        CALL check
+        LD V1, 0
+        SKP V0
+        LD V1, 1
+        LD V2, 0
+        CALL check
+
+end:    JP end
+
+; Draws a tick when V1 equals V2 and a cross otherwise
+check:  LD I, tick
+        SE V1, V2
+        LD I, cross
+        DRW VC, VD, 5
+        ADD VC, 8
+        RET
+
+; Moves the position of the next mark to the start of the next row
+newline:
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+tick:   DB 0x01, 0x02, 0x84, 0x48, 0x30
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
@@ -0,0 +1,211 @@
+; Runs each instruction once and draws a tick for every result that matches
+; the expected value in V2, or a cross when it does not, in rows of eight
+;
+; VC and VD hold the position of the next mark and are not used by the tests
+
+        LD VC, 0
+        LD VD, 0
+
+; 3xkk skips when equal
+        LD V1, 0
+        LD V3, 5
+        SE V3, 5
+        LD V1, 1
+        LD V2, 0
+        CALL check
+
+; 4xkk skips when not equal
+        LD V1, 0
+        SNE V3, 6
+        LD V1, 1
+        LD V2, 0
+        CALL check
+
+; 5xy0 skips when registers are equal
+        LD V1, 0
+        LD V4, 5
+        SE V3, V4
+        LD V1, 1
+        LD V2, 0
+        CALL check
+
+; 9xy0 skips when registers are not equal
+        LD V1, 0
+        LD V4, 6
+        SNE V3, V4
+        LD V1, 1
+        LD V2, 0
+        CALL check
+
+; 7xkk wraps around without touching VF
+        LD VF, 7
+        LD V1, 0xFF
+        ADD V1, 3
+        LD V4, VF
+        LD V2, 2
+        CALL check
+        LD V1, V4
+        LD V2, 7
+        CALL check
+
+; 8xy0
+        LD V3, 0x42
+        LD V1, V3
+        LD V2, 0x42
+        CALL check
+
+; 8xy1
+        LD V1, 0x0F
+        LD V3, 0xF0
+        OR V1, V3
+        LD V2, 0xFF
+        CALL check
+
+; 8xy2
+        LD V1, 0x3C
+        LD V3, 0x0F
+        AND V1, V3
+        LD V2, 0x0C
+        CALL check
+
+; 8xy3
+        LD V1, 0x3C
+        LD V3, 0x0F
+        XOR V1, V3
+        LD V2, 0x33
+        CALL check
+
+; 8xy4
+        LD V1, 0xF0
+        LD V3, 0x20
+        ADD V1, V3
+        LD V2, 0x10
+        CALL check
+
+; 8xy5
+        LD V1, 0x20
+        LD V3, 0x30
+        SUB V1, V3
+        LD V2, 0xF0
+        CALL check
+
+; 8xy6 with x and y the same so the shift quirk does not matter
+        LD V1, 0x81
+        SHR V1, V1
+        LD V2, 0x40
+        CALL check
+
+; 8xy7
+        LD V1, 0x20
+        LD V3, 0x30
+        SUBN V1, V3
+        LD V2, 0x10
+        CALL check
+
+; 8xyE
+        LD V1, 0x81
+        SHL V1, V1
+        LD V2, 0x02
+        CALL check
+
+; Bnnn with V0, V2 and V3 equal so the jump quirk does not matter
+        LD V0, 2
+        LD V2, 2
+        LD V3, 2
+        LD V1, 0
+        JP V0, table
+after:  LD V2, 1
+        CALL check
+
+; Cxkk masks the random number
+        RND V1, 0
+        LD V2, 0
+        CALL check
+
+; Fx15 and Fx07 count the delay timer down to zero
+        LD V3, 3
+        LD DT, V3
+wait:   LD V1, DT
+        SE V1, 0
+        JP wait
+        LD V2, 0
+        CALL check
+
+; Fx29 points I at the sprite for a digit
+        LD V3, 0xA
+        LD F, V3
+        LD V1, [I]
+        LD V1, V0
+        LD V2, 0xF0
+        CALL check
+
+; Fx33 stores the decimal digits
+        LD V3, 234
+        LD I, scratch
+        LD B, V3
+        LD V2, [I]
+        LD V3, V0
+        LD V1, V1
+        LD V2, 3
+        CALL check
+        LD V1, V3
+        LD V2, 2
+        CALL check
+
+; Fx55 and Fx65 store and load registers
+        LD V0, 0x12
+        LD V1, 0x34
+        LD I, scratch
+        LD [I], V1
+        LD V0, 0
+        LD V1, 0
+        LD I, scratch
+        LD V1, [I]
+        LD V2, 0x34
+        CALL check
+
+; Fx1E adds to I
+        LD I, scratch
+        LD V3, 1
+        ADD I, V3
+        LD V0, [I]
+        LD V1, V0
+        LD V2, 0x34
+        CALL check
+
+; Dxyn sets VF when drawing over a sprite and erases it
+        LD I, tick
+        LD V3, 0
+        LD V4, 26
+        DRW V3, V4, 5
+        LD V1, VF
+        LD V2, 0
+        CALL check
+        LD I, tick
+        DRW V3, V4, 5
+        LD V1, VF
+        LD V2, 1
+        CALL check
+
+end:    JP end
+
+table:  JP after
+        LD V1, 1
+        JP after
+
+; Draws a tick when V1 equals V2 and a cross otherwise, which overwrites VF
+check:  LD I, tick
+        SE V1, V2
+        LD I, cross
+        DRW VC, VD, 5
+        ADD VC, 8
+        SE VC, 64
+        RET
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+tick:   DB 0x01, 0x02, 0x84, 0x48, 0x30
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
+scratch:
+        DB 0, 0, 0
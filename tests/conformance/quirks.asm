@@ -0,0 +1,98 @@
+; Detects the quirks of the interpreter and draws a digit for each of them
+; from left to right:
+;
+; - shift: 1 when 8xy6 shifts Vx in place
+; - memory: 1 when Fx65 leaves I unchanged, 2 when it adds x and 3 when it
+;   adds x + 1
+; - wrap: 1 when sprites wrap around the edges of the screen
+; - jump: 1 when Bnnn adds Vx where x is the highest nibble of nnn
+; - vblank: 1 when Dxyn waits for the vertical blank
+; - logic: 1 when 8xy1 resets VF
+
+        LD VC, 4
+        LD VD, 13
+
+; shift
+        LD V3, 0x04
+        LD V4, 0x10
+        SHR V3, V4
+        LD V1, 0
+        SNE V3, 0x02
+        LD V1, 1
+        CALL digit
+
+; memory
+        LD I, values
+        LD V1, [I]
+        LD V0, [I]
+        LD V1, V0
+        LD V3, 0x0F
+        AND V1, V3
+        CALL digit
+
+; wrap, drawing over the left edge from the right and checking for a
+; collision before erasing both sprites
+        LD I, line
+        LD V3, 60
+        LD V4, 0
+        DRW V3, V4, 1
+        LD I, pixel
+        LD V5, 0
+        DRW V5, V4, 1
+        LD V1, VF
+        DRW V5, V4, 1
+        LD I, line
+        DRW V3, V4, 1
+        CALL digit
+
+; jump, where the table is placed so that both V0 and Vx lead to a valid
+; instruction
+        LD V0, 0
+        LD V1, 0
+        LD V2, 2
+        LD V3, 2
+        JP V0, table
+jumped: CALL digit
+
+; vblank, counting how many frames pass while drawing four times
+        LD V3, 255
+        LD DT, V3
+        LD I, pixel
+        LD V4, 0
+        DRW V4, V4, 1
+        DRW V4, V4, 1
+        DRW V4, V4, 1
+        DRW V4, V4, 1
+        LD V3, DT
+        LD V4, 255
+        SUB V4, V3
+        SHR V4, V4
+        LD V1, 0
+        SE V4, 0
+        LD V1, 1
+        CALL digit
+
+; logic
+        LD VF, 5
+        LD V3, 1
+        OR V3, V3
+        LD V1, 1
+        SE VF, 0
+        LD V1, 0
+        CALL digit
+
+end:    JP end
+
+table:  JP jumped
+        LD V1, 1
+        JP jumped
+
+; Draws the digit in V1 and moves along
+digit:  LD F, V1
+        DRW VC, VD, 5
+        ADD VC, 10
+        RET
+
+values: DB 0x31, 0x32, 0x33
+line:   DB 0xFF
+pixel:  DB 0x80
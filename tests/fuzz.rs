@@ -0,0 +1,69 @@
+//! Runs the checks from the fuzz targets in `fuzz/` over a fixed set of
+//! random inputs so that `cargo test` catches panics without needing
+//! `cargo fuzz`, apart from the instruction target whose round trip is
+//! already checked for every opcode in `src/instruction.rs`
+
+use chip_8::{
+    display::Display,
+    emulator::{Backend, Emulator},
+    keyboard::Keyboard,
+    memory::{MEMORY_SIZE, PROGRAM_OFFSET},
+    quirks::Platform,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ROMS: usize = 200;
+const FRAMES: usize = 100;
+const TICKS_PER_FRAME: usize = 8;
+
+#[test]
+fn test_tick() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for index in 0..ROMS {
+        let length = rng.gen_range(0..=MEMORY_SIZE - PROGRAM_OFFSET);
+        let rom: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+        let mut emulator = Emulator::new(&rom).unwrap();
+        let mut display = Display::default();
+        let mut keyboard = Keyboard::default();
+
+        emulator.set_quirks(Platform::ALL[index % Platform::ALL.len()].quirks());
+        emulator.set_seed(index as u64);
+        keyboard.set_state(rng.gen());
+
+        if index % 2 == 1 {
+            emulator.set_backend(Backend::Recompiler);
+        }
+
+        for _ in 0..FRAMES {
+            emulator.decrement_timers();
+
+            if emulator
+                .run(&mut display, &keyboard, TICKS_PER_FRAME)
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_xor_sprite() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..1000 {
+        let x = if rng.gen() { rng.gen() } else { usize::MAX };
+        let y = if rng.gen() { rng.gen() } else { usize::MAX };
+        let length = rng.gen_range(0..100);
+        let sprite: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+        let mut display = Display::default();
+
+        for _ in 0..2 {
+            display.xor_sprite(x, y, &sprite);
+            display.xor_sprite_clipped(x, y, &sprite);
+        }
+
+        assert!(display.rows().iter().all(|&row| row == 0));
+    }
+}
@@ -0,0 +1,49 @@
+//! Replays the programs in `tests/traces` and compares the emulator against
+//! their checked-in reference traces after every tick
+//!
+//! Each program has an assembly source, an optional `.keys` recording of the
+//! input and a `.trace` which was recorded with the default quirks and speed:
+//!
+//! ```bash
+//! cargo run -- asm tests/traces/<name>.asm <name>.ch8
+//! cargo run -- --headless --frames 100 [--playback tests/traces/<name>.keys] \
+//!     --trace tests/traces/<name>.trace <name>.ch8
+//! ```
+//!
+//! A trace should only be recorded again when a change in behaviour is
+//! intended.
+
+use std::path::Path;
+
+use chip_8::{assembler::assemble, emulator::Emulator, recording::Recording, trace::Reference};
+
+const TICKS_PER_FRAME: usize = 8;
+
+fn check(name: &str) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/traces");
+    let read =
+        |extension: &str| std::fs::read_to_string(directory.join(name).with_extension(extension));
+
+    let rom = assemble(&read("asm").unwrap()).unwrap();
+    let input = match read("keys") {
+        Ok(text) => Recording::parse(&text).unwrap(),
+        Err(_) => Recording::default(),
+    };
+    let reference = Reference::parse(&read("trace").unwrap()).unwrap();
+
+    let mut emulator = Emulator::new(&rom).unwrap();
+
+    if let Err(divergence) = reference.check(&mut emulator, &input, TICKS_PER_FRAME) {
+        panic!("{}: {}", name, divergence);
+    }
+}
+
+#[test]
+fn test_digits() {
+    check("digits");
+}
+
+#[test]
+fn test_keys() {
+    check("keys");
+}
@@ -0,0 +1,35 @@
+; Counts up in steps of 7 and draws the count as three decimal digits,
+; waiting on the delay timer between counts and mixing the flags into VA
+start:  LD V5, 0
+loop:   CLS
+        LD I, digits
+        LD B, V5
+        LD V2, [I]
+        LD V6, 20
+        LD V7, 12
+        LD F, V0
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V1
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V2
+        DRW V6, V7, 5
+        LD V8, 2
+        LD DT, V8
+wait:   LD V8, DT
+        SE V8, 0
+        JP wait
+        ADD V5, 7
+        CALL flags
+        JP loop
+
+flags:  LD V9, V5
+        SHL V9
+        LD VA, VF
+        SUB V9, V5
+        OR VA, VF
+        ADD I, VA
+        RET
+
+digits: DB 0, 0, 0
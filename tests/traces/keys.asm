@@ -0,0 +1,12 @@
+; Waits for a key, draws its digit and beeps until the key is released
+loop:   LD V0, K
+        CLS
+        LD F, V0
+        LD V1, 28
+        LD V2, 13
+        DRW V1, V2, 5
+        LD V3, 4
+        LD ST, V3
+held:   SKNP V0
+        JP held
+        JP loop